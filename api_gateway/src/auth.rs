@@ -0,0 +1,520 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, HeaderMap},
+        StatusCode,
+    },
+    middleware::Next,
+    web, Error, HttpMessage, HttpResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::{ApiKeyConfig, AuthConfig},
+    request_id::TraceContext,
+    routes, Gateway,
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Client identified by an authenticator; stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub key_id: String,
+    /// Allowed path prefixes, empty for all.
+    pub routes: Vec<String>,
+    pub requests_per_minute: Option<u32>,
+}
+
+impl From<&ApiKeyConfig> for Principal {
+    fn from(key: &ApiKeyConfig) -> Self {
+        Self {
+            key_id: key.id.clone(),
+            routes: key.routes.clone(),
+            requests_per_minute: key.requests_per_minute,
+        }
+    }
+}
+
+impl Principal {
+    fn may_call(&self, path: &str) -> bool {
+        self.routes.is_empty()
+            || self
+                .routes
+                .iter()
+                .any(|prefix| matches_prefix(prefix, path))
+    }
+}
+
+#[derive(Debug)]
+pub enum Rejection {
+    Missing,
+    Invalid(&'static str),
+    Expired,
+    Forbidden(String),
+}
+
+impl Rejection {
+    fn response(&self, request_id: &str) -> HttpResponse {
+        let (status, code, message) = match self {
+            Self::Missing => (
+                StatusCode::UNAUTHORIZED,
+                "UNAUTHORIZED",
+                "Send an X-Api-Key header or an Authorization: Bearer token".to_string(),
+            ),
+            Self::Invalid(reason) => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED", reason.to_string()),
+            Self::Expired => (
+                StatusCode::UNAUTHORIZED,
+                "TOKEN_EXPIRED",
+                "Bearer token has expired".to_string(),
+            ),
+            Self::Forbidden(key_id) => (
+                StatusCode::FORBIDDEN,
+                "FORBIDDEN",
+                format!("Client {} may not call this route", key_id),
+            ),
+        };
+
+        let mut response = HttpResponse::build(status);
+        if status == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.json(json!({
+            "error": message,
+            "code": code,
+            "request_id": request_id
+        }))
+    }
+}
+
+/// One way of proving who the client is.
+pub trait Authenticator: Send + Sync {
+    /// `None` when the request carries no credential of this kind.
+    fn authenticate(&self, headers: &HeaderMap) -> Option<Result<Principal, Rejection>>;
+}
+
+/// Static keys sent in `X-Api-Key`.
+pub struct ApiKeys {
+    keys: Vec<ApiKeyConfig>,
+}
+
+impl Authenticator for ApiKeys {
+    fn authenticate(&self, headers: &HeaderMap) -> Option<Result<Principal, Rejection>> {
+        let sent = headers.get(API_KEY_HEADER)?.as_bytes();
+        // Проверяем все ключи, чтобы время ответа не выдавало совпавший префикс
+        let found = self.keys.iter().fold(None, |found, key| {
+            let matches = key
+                .key
+                .as_deref()
+                .is_some_and(|secret| constant_time_eq(secret.as_bytes(), sent));
+            found.or(matches.then_some(key))
+        });
+        Some(
+            found
+                .map(Principal::from)
+                .ok_or(Rejection::Invalid("Unknown API key")),
+        )
+    }
+}
+
+/// Claims of a bearer token.
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// Key id the token was issued for.
+    sub: String,
+    /// Expiry, Unix seconds.
+    exp: u64,
+}
+
+/// `Authorization: Bearer <claims>.<signature>`: base64url JSON claims signed with
+/// HMAC-SHA256. Permissions and limits come from the key named by `sub`.
+#[derive(Clone)]
+pub struct SignedTokens {
+    secret: Vec<u8>,
+    keys: Vec<ApiKeyConfig>,
+}
+
+impl SignedTokens {
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn issue(&self, key_id: &str, ttl: Duration) -> String {
+        let claims = Claims {
+            sub: key_id.to_string(),
+            exp: unix_secs() + ttl.as_secs(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap_or_default());
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        format!(
+            "{}.{}",
+            payload,
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+}
+
+impl Authenticator for SignedTokens {
+    fn authenticate(&self, headers: &HeaderMap) -> Option<Result<Principal, Rejection>> {
+        let token = headers
+            .get(header::AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")?
+            .trim();
+
+        let verify = || {
+            let (payload, signature) = token
+                .split_once('.')
+                .ok_or(Rejection::Invalid("Malformed bearer token"))?;
+            let signature = URL_SAFE_NO_PAD
+                .decode(signature)
+                .map_err(|_| Rejection::Invalid("Malformed bearer token"))?;
+            let mut mac = self.mac();
+            mac.update(payload.as_bytes());
+            mac.verify_slice(&signature)
+                .map_err(|_| Rejection::Invalid("Bearer token signature does not match"))?;
+
+            let claims: Claims = URL_SAFE_NO_PAD
+                .decode(payload)
+                .ok()
+                .and_then(|json| serde_json::from_slice(&json).ok())
+                .ok_or(Rejection::Invalid("Malformed bearer token"))?;
+            if claims.exp <= unix_secs() {
+                return Err(Rejection::Expired);
+            }
+            self.keys
+                .iter()
+                .find(|key| key.id == claims.sub)
+                .map(Principal::from)
+                .ok_or(Rejection::Invalid(
+                    "Bearer token was issued for an unknown client",
+                ))
+        };
+        Some(verify())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Rejections {
+    missing: AtomicU64,
+    invalid: AtomicU64,
+    expired: AtomicU64,
+    forbidden: AtomicU64,
+}
+
+/// Authenticators tried in order and route permissions. Per-key request rates are enforced
+/// by [`crate::rate_limit::ClientLimiter`].
+pub struct Auth {
+    authenticators: Vec<Box<dyn Authenticator>>,
+    tokens: Option<SignedTokens>,
+    public_paths: Vec<String>,
+    rejected: Rejections,
+}
+
+impl Auth {
+    /// `None` while no keys or token secret are configured.
+    pub fn from_config(config: &AuthConfig) -> Option<Self> {
+        if !config.is_enabled() {
+            return None;
+        }
+        let tokens = config.token_secret.as_ref().map(|secret| SignedTokens {
+            secret: secret.as_bytes().to_vec(),
+            keys: config.keys.clone(),
+        });
+        let mut authenticators: Vec<Box<dyn Authenticator>> = vec![Box::new(ApiKeys {
+            keys: config.keys.clone(),
+        })];
+        if let Some(tokens) = &tokens {
+            authenticators.push(Box::new(tokens.clone()));
+        }
+
+        Some(Self {
+            authenticators,
+            tokens,
+            public_paths: config.public_paths.clone(),
+            rejected: Rejections::default(),
+        })
+    }
+
+    pub fn tokens(&self) -> Option<&SignedTokens> {
+        self.tokens.as_ref()
+    }
+
+    /// Who sent the request, if it may pass; `Ok(None)` for public paths.
+    ///
+    /// Only the API needs credentials: `/health` and the frontend served from `static_dir`
+    /// load in a browser before it has a key, so paths outside `/api` are always public.
+    fn check(&self, path: &str, headers: &HeaderMap) -> Result<Option<Principal>, Rejection> {
+        let is_api = path == "/api" || path.starts_with("/api/") || routes::has_dot_segments(path);
+        if !is_api
+            || self
+                .public_paths
+                .iter()
+                .any(|prefix| matches_prefix(prefix, path))
+        {
+            return Ok(None);
+        }
+        let principal = self
+            .authenticators
+            .iter()
+            .find_map(|authenticator| authenticator.authenticate(headers))
+            .unwrap_or(Err(Rejection::Missing))?;
+
+        if !principal.may_call(path) {
+            return Err(Rejection::Forbidden(principal.key_id));
+        }
+        Ok(Some(principal))
+    }
+
+    fn count(&self, rejection: &Rejection) {
+        let counter = match rejection {
+            Rejection::Missing => &self.rejected.missing,
+            Rejection::Invalid(_) => &self.rejected.invalid,
+            Rejection::Expired => &self.rejected.expired,
+            Rejection::Forbidden(_) => &self.rejected.forbidden,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Summary for `/api/status`.
+    pub fn status(&self) -> serde_json::Value {
+        json!({
+            "enabled": true,
+            "bearer_tokens": self.tokens.is_some(),
+            "rejected": self.rejected
+        })
+    }
+}
+
+/// Middleware: lets public paths and authorised clients through and answers everything else
+/// with `401` or `403`. Paths are checked as the router sees them, with escapes such as
+/// `/%61pi/...` decoded, so an encoded path cannot reach an API route as a public one.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let gateway = req.app_data::<web::Data<Gateway>>().cloned();
+    let Some(auth) = gateway.as_ref().and_then(|gateway| gateway.auth.as_ref()) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+
+    match auth.check(req.match_info().as_str(), req.headers()) {
+        Ok(principal) => {
+            if let Some(principal) = principal {
+                req.extensions_mut().insert(principal);
+            }
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        }
+        Err(rejection) => {
+            auth.count(&rejection);
+            let request_id = TraceContext::of(req.request()).request_id;
+            println!(
+                "[{}] Rejected {} {}: {:?}",
+                request_id,
+                req.method(),
+                req.path(),
+                rejection
+            );
+            let response = rejection.response(&request_id);
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+// Совпадение по границе сегмента, как у маршрутов: /api/unfold не открывает /api/unfolding.
+// Путь с . или .. мог бы выйти за префикс, такие пути не совпадают ни с чем
+fn matches_prefix(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    !routes::has_dot_segments(path)
+        && path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderValue;
+
+    const KEY: &str = "0123456789abcdef";
+
+    fn auth() -> Auth {
+        Auth::from_config(&AuthConfig {
+            keys: vec![ApiKeyConfig {
+                id: "viewer".to_string(),
+                key: Some(KEY.to_string()),
+                routes: vec!["/api/unfold/jobs".to_string()],
+                requests_per_minute: None,
+            }],
+            token_secret: Some("secret".to_string()),
+            ..AuthConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        headers(header::AUTHORIZATION, &format!("Bearer {}", token))
+    }
+
+    #[test]
+    fn test_api_key_must_match() {
+        let auth = auth();
+        let key = header::HeaderName::from_static(API_KEY_HEADER);
+
+        let principal = auth
+            .check("/api/unfold/jobs/1", &headers(key.clone(), KEY))
+            .unwrap()
+            .unwrap();
+        assert_eq!(principal.key_id, "viewer");
+        assert!(matches!(
+            auth.check("/api/unfold/jobs/1", &headers(key, "0123456789abcdeX")),
+            Err(Rejection::Invalid(_))
+        ));
+        assert!(matches!(
+            auth.check("/api/unfold/jobs/1", &HeaderMap::new()),
+            Err(Rejection::Missing)
+        ));
+        assert!(auth.check("/health", &HeaderMap::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_frontend_is_public() {
+        let auth = auth();
+        for path in [
+            "/",
+            "/index.html",
+            "/assets/app.3f2a.js",
+            "/projects/7",
+            "/apiary",
+        ] {
+            assert!(
+                auth.check(path, &HeaderMap::new()).unwrap().is_none(),
+                "{}",
+                path
+            );
+        }
+        for path in [
+            "/api",
+            "/api/status",
+            "/api/unfold/jobs",
+            "/assets/../api/status",
+        ] {
+            assert!(
+                matches!(auth.check(path, &HeaderMap::new()), Err(Rejection::Missing)),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_signed_token_is_verified() {
+        let auth = auth();
+        let token = auth
+            .tokens()
+            .unwrap()
+            .issue("viewer", Duration::from_secs(60));
+        let principal = auth.check("/api/unfold/jobs", &bearer(&token)).unwrap();
+        assert_eq!(principal.unwrap().key_id, "viewer");
+
+        let unknown = auth
+            .tokens()
+            .unwrap()
+            .issue("admin", Duration::from_secs(60));
+        assert!(matches!(
+            auth.check("/api/unfold/jobs", &bearer(&unknown)),
+            Err(Rejection::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_tampered_token_is_rejected() {
+        let auth = auth();
+        let token = auth
+            .tokens()
+            .unwrap()
+            .issue("viewer", Duration::from_secs(60));
+        let (_, signature) = token.split_once('.').unwrap();
+        // Подменяем срок действия, оставляя старую подпись
+        let claims = URL_SAFE_NO_PAD.encode(br#"{"sub":"viewer","exp":99999999999}"#);
+        let forged = format!("{}.{}", claims, signature);
+
+        assert!(matches!(
+            auth.check("/api/unfold/jobs", &bearer(&forged)),
+            Err(Rejection::Invalid("Bearer token signature does not match"))
+        ));
+        let other_secret = SignedTokens {
+            secret: b"other".to_vec(),
+            keys: Vec::new(),
+        };
+        let foreign = other_secret.issue("viewer", Duration::from_secs(60));
+        assert!(matches!(
+            auth.check("/api/unfold/jobs", &bearer(&foreign)),
+            Err(Rejection::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let auth = auth();
+        let token = auth.tokens().unwrap().issue("viewer", Duration::ZERO);
+        assert!(matches!(
+            auth.check("/api/unfold/jobs", &bearer(&token)),
+            Err(Rejection::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_routes_are_matched_by_segment() {
+        let auth = auth();
+        let key = headers(header::HeaderName::from_static(API_KEY_HEADER), KEY);
+
+        assert!(auth.check("/api/unfold/jobs", &key).is_ok());
+        assert!(auth.check("/api/unfold/jobs/7/events", &key).is_ok());
+        for path in [
+            "/api/unfold/unfold",
+            "/api/unfold/jobsx",
+            "/api/unfold/jobs/../../unfold",
+            "/api/unfold/jobs/%2e%2e/%2e%2e/unfold",
+        ] {
+            assert!(
+                matches!(auth.check(path, &key), Err(Rejection::Forbidden(_))),
+                "{}",
+                path
+            );
+        }
+        assert!(!matches_prefix("/health", "/health/../api/unfold"));
+    }
+}
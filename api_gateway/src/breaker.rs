@@ -0,0 +1,309 @@
+use serde::Serialize;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// When a circuit opens and how long it stays open. `failures == 0` disables the breaker.
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerSettings {
+    pub failures: u32,
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerSettings {
+    fn default() -> Self {
+        Self {
+            failures: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// Cooldown is over; one trial request decides whether the circuit closes again.
+    HalfOpen {
+        trial_running: bool,
+    },
+}
+
+/// State of a circuit as shown by `/api/status`.
+#[derive(Debug, Serialize)]
+pub struct CircuitStatus {
+    pub state: &'static str,
+    pub failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+/// Closed / open / half-open breaker for one upstream: after `failures` failed requests in a
+/// row it rejects requests for `cooldown`, then lets a single trial through.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    upstream: String,
+    settings: BreakerSettings,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(upstream: &str, settings: BreakerSettings) -> Self {
+        Self {
+            upstream: upstream.to_string(),
+            settings,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Permission to send a request, or how long until the circuit may close.
+    pub fn allow(&self) -> Result<Permit<'_>, Duration> {
+        if self.settings.failures == 0 {
+            return Ok(Permit::new(self, false));
+        }
+        let mut state = self.lock();
+        match *state {
+            State::Closed { .. } => Ok(Permit::new(self, false)),
+            State::Open { until } => {
+                let now = Instant::now();
+                if until > now {
+                    return Err(until - now);
+                }
+                println!(
+                    "Circuit for {} is half-open, sending a trial request",
+                    self.upstream
+                );
+                *state = State::HalfOpen {
+                    trial_running: true,
+                };
+                Ok(Permit::new(self, true))
+            }
+            // Пока идёт пробный запрос, остальные получают отказ
+            State::HalfOpen {
+                trial_running: true,
+            } => Err(Duration::from_secs(1)),
+            State::HalfOpen {
+                trial_running: false,
+            } => {
+                *state = State::HalfOpen {
+                    trial_running: true,
+                };
+                Ok(Permit::new(self, true))
+            }
+        }
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        let state = *self.lock();
+        let (name, failures, retry_in) = match state {
+            State::Closed { failures } => ("closed", failures, None),
+            State::Open { until } => {
+                let left = until.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    ("half_open", 0, None)
+                } else {
+                    ("open", 0, Some(left.as_secs_f64().ceil() as u64))
+                }
+            }
+            State::HalfOpen { .. } => ("half_open", 0, None),
+        };
+        CircuitStatus {
+            state: name,
+            failures,
+            retry_in_secs: retry_in,
+        }
+    }
+
+    fn record(&self, success: bool, trial: bool) {
+        if self.settings.failures == 0 {
+            return;
+        }
+        let open = || {
+            println!(
+                "Circuit for {} opened for {}s",
+                self.upstream,
+                self.settings.cooldown.as_secs()
+            );
+            State::Open {
+                until: Instant::now() + self.settings.cooldown,
+            }
+        };
+        let mut state = self.lock();
+        *state = match (*state, success) {
+            (State::Closed { .. }, true) => State::Closed { failures: 0 },
+            (State::Closed { failures }, false) if failures + 1 < self.settings.failures => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (State::Closed { .. }, false) => open(),
+            (State::HalfOpen { .. }, true) if trial => {
+                println!(
+                    "Circuit for {} closed after a successful trial",
+                    self.upstream
+                );
+                State::Closed { failures: 0 }
+            }
+            (State::HalfOpen { .. }, false) if trial => open(),
+            // Ответы запросов, начатых до открытия цепи, состояние не меняют
+            (current, _) => current,
+        };
+    }
+
+    fn abandon_trial(&self) {
+        let mut state = self.lock();
+        if let State::HalfOpen { .. } = *state {
+            *state = State::HalfOpen {
+                trial_running: false,
+            };
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A request allowed through the breaker; report its outcome with `succeeded` or `failed`.
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    trial: bool,
+    recorded: bool,
+}
+
+impl<'a> Permit<'a> {
+    fn new(breaker: &'a CircuitBreaker, trial: bool) -> Self {
+        Self {
+            breaker,
+            trial,
+            recorded: false,
+        }
+    }
+
+    pub fn succeeded(mut self) {
+        self.recorded = true;
+        self.breaker.record(true, self.trial);
+    }
+
+    pub fn failed(mut self) {
+        self.recorded = true;
+        self.breaker.record(false, self.trial);
+    }
+}
+
+impl Drop for Permit<'_> {
+    // Клиент ушёл, не дождавшись пробного запроса: следующий запрос станет новой пробой
+    fn drop(&mut self) {
+        if self.trial && !self.recorded {
+            self.breaker.abandon_trial();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const COOLDOWN: Duration = Duration::from_millis(100);
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(
+            "core",
+            BreakerSettings {
+                failures: 2,
+                cooldown: COOLDOWN,
+            },
+        )
+    }
+
+    fn open(breaker: &CircuitBreaker) {
+        for _ in 0..2 {
+            breaker.allow().ok().unwrap().failed();
+        }
+        assert_eq!(breaker.status().state, "open");
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = breaker();
+        breaker.allow().ok().unwrap().failed();
+        // Успех обнуляет счётчик, открывают только ошибки подряд
+        breaker.allow().ok().unwrap().succeeded();
+        breaker.allow().ok().unwrap().failed();
+        let status = breaker.status();
+        assert_eq!((status.state, status.failures), ("closed", 1));
+
+        breaker.allow().ok().unwrap().failed();
+        let wait = breaker.allow().err().unwrap();
+        assert!(wait <= COOLDOWN);
+        assert_eq!(breaker.status().retry_in_secs, Some(1));
+    }
+
+    #[test]
+    fn test_successful_trial_closes_circuit() {
+        let breaker = breaker();
+        open(&breaker);
+        sleep(COOLDOWN);
+        assert_eq!(breaker.status().state, "half_open");
+
+        let trial = breaker.allow().ok().unwrap();
+        // Пока идёт проба, остальные запросы получают отказ
+        assert!(breaker.allow().is_err());
+        trial.succeeded();
+        assert_eq!(breaker.status().state, "closed");
+        assert!(breaker.allow().is_ok());
+    }
+
+    #[test]
+    fn test_failed_trial_reopens_circuit() {
+        let breaker = breaker();
+        open(&breaker);
+        sleep(COOLDOWN);
+
+        breaker.allow().ok().unwrap().failed();
+        assert_eq!(breaker.status().state, "open");
+        assert!(breaker.allow().is_err());
+    }
+
+    #[test]
+    fn test_abandoned_trial_lets_next_request_probe() {
+        let breaker = breaker();
+        open(&breaker);
+        sleep(COOLDOWN);
+
+        drop(breaker.allow().ok().unwrap());
+        let trial = breaker.allow().ok().unwrap();
+        trial.succeeded();
+        assert_eq!(breaker.status().state, "closed");
+    }
+
+    #[test]
+    fn test_late_results_do_not_change_open_circuit() {
+        let breaker = breaker();
+        let late = breaker.allow().ok().unwrap();
+        open(&breaker);
+        late.succeeded();
+        assert_eq!(breaker.status().state, "open");
+    }
+
+    #[test]
+    fn test_zero_failures_disables_breaker() {
+        let breaker = CircuitBreaker::new(
+            "core",
+            BreakerSettings {
+                failures: 0,
+                cooldown: COOLDOWN,
+            },
+        );
+        for _ in 0..10 {
+            breaker.allow().ok().unwrap().failed();
+        }
+        assert_eq!(breaker.status().state, "closed");
+    }
+}
@@ -0,0 +1,605 @@
+use serde::Deserialize;
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::{
+    breaker::BreakerSettings,
+    routes::{Balance, Ejection, Route, AI_ENGINE, UNFOLDING_CORE},
+};
+
+/// One entry of the routing table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+    pub prefix: String,
+    /// Name used in logs, error bodies and `/api/status`; defaults to the prefix.
+    #[serde(default)]
+    pub upstream: Option<String>,
+    pub targets: Vec<String>,
+    /// Overrides `upstream_timeout_secs` for this route.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Whether the gateway is unhealthy while this upstream is down.
+    #[serde(default = "default_required")]
+    pub required: bool,
+    #[serde(default = "default_health_path")]
+    pub health_path: String,
+    #[serde(default)]
+    pub balance: Balance,
+    /// Overrides `retries` for this route.
+    #[serde(default)]
+    pub retries: Option<usize>,
+    /// Override `breaker_failures` and `breaker_cooldown_secs` for this route.
+    #[serde(default)]
+    pub breaker_failures: Option<u32>,
+    #[serde(default)]
+    pub breaker_cooldown_secs: Option<u64>,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+fn default_health_path() -> String {
+    "/health".to_string()
+}
+
+impl RouteConfig {
+    fn new(prefix: &str, upstream: Option<&str>, targets: Vec<String>) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            upstream: upstream.map(str::to_string),
+            targets,
+            timeout_secs: None,
+            required: default_required(),
+            health_path: default_health_path(),
+            balance: Balance::default(),
+            retries: None,
+            breaker_failures: None,
+            breaker_cooldown_secs: None,
+        }
+    }
+}
+
+/// A client allowed through the gateway.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Name in logs and the `sub` of bearer tokens issued for this client.
+    pub id: String,
+    /// Static key sent in `X-Api-Key`; clients without one can only use bearer tokens.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Path prefixes the client may call; empty allows every route.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+}
+
+/// Authentication is on as soon as a key or a token secret is configured.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub keys: Vec<ApiKeyConfig>,
+    /// HMAC-SHA256 secret for bearer tokens (`GATEWAY_TOKEN_SECRET`).
+    pub token_secret: Option<String>,
+    /// API path prefixes served without credentials. Paths outside `/api` (`/health` and the
+    /// frontend from `static_dir`) never need them.
+    pub public_paths: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            token_secret: None,
+            public_paths: vec!["/health".to_string()],
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty() || self.token_secret.is_some()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (index, key) in self.keys.iter().enumerate() {
+            if key.id.is_empty() {
+                return Err("auth key ids must not be empty".to_string());
+            }
+            if self.keys[..index].iter().any(|other| other.id == key.id) {
+                return Err(format!("auth key id {} is configured twice", key.id));
+            }
+            if key.key.as_deref().is_some_and(|secret| secret.len() < 16) {
+                return Err(format!(
+                    "auth key {} must be at least 16 characters",
+                    key.id
+                ));
+            }
+            if key.key.is_none() && self.token_secret.is_none() {
+                return Err(format!(
+                    "auth key {} has neither a key nor a token_secret to sign tokens with",
+                    key.id
+                ));
+            }
+        }
+        if self
+            .token_secret
+            .as_deref()
+            .is_some_and(|secret| secret.len() < 32)
+        {
+            return Err("auth token_secret must be at least 32 characters".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Cross-origin access for browser clients.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the gateway (`GATEWAY_CORS_ORIGINS`); empty allows only
+    /// `frontend_url`, `*` allows any origin.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// Response headers scripts may read.
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight answer.
+    pub max_age_secs: usize,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: strings(&["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS"]),
+            allowed_headers: strings(&[
+                "accept",
+                "authorization",
+                "content-type",
+                "traceparent",
+                "x-api-key",
+                "x-request-id",
+            ]),
+            expose_headers: strings(&["content-disposition", "retry-after", "x-request-id"]),
+            allow_credentials: false,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.allows_any_origin() && self.allow_credentials {
+            return Err("cors: allow_credentials cannot be combined with the * origin".to_string());
+        }
+        for origin in self.allowed_origins.iter().filter(|origin| *origin != "*") {
+            let url = reqwest::Url::parse(origin)
+                .map_err(|e| format!("cors: invalid origin '{}': {}", origin, e))?;
+            if url.path() != "/" || origin.ends_with('/') {
+                return Err(format!(
+                    "cors: origin '{}' must be scheme://host[:port] without a path",
+                    origin
+                ));
+            }
+        }
+        if let Some(method) = self
+            .allowed_methods
+            .iter()
+            .find(|method| method.parse::<reqwest::Method>().is_err())
+        {
+            return Err(format!("cors: invalid method '{}'", method));
+        }
+        if let Some(name) = self
+            .allowed_headers
+            .iter()
+            .chain(&self.expose_headers)
+            .find(|name| reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err())
+        {
+            return Err(format!("cors: invalid header name '{}'", name));
+        }
+        Ok(())
+    }
+}
+
+/// Gateway settings: defaults, then the JSON file named by `--config` or `GATEWAY_CONFIG`,
+/// then environment variables.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GatewayConfig {
+    /// `host:port` to listen on (`GATEWAY_BIND`).
+    pub bind: String,
+    /// Shown by `/api/status` (`GATEWAY_FRONTEND_URL`).
+    pub frontend_url: String,
+    /// `GATEWAY_CONNECT_TIMEOUT_SECS`.
+    pub connect_timeout_secs: u64,
+    /// Wait for an upstream's response headers (`GATEWAY_UPSTREAM_TIMEOUT_SECS`).
+    pub upstream_timeout_secs: u64,
+    /// Largest request body forwarded upstream (`GATEWAY_MAX_BODY_BYTES`).
+    pub max_body_bytes: usize,
+    /// Limit for one upstream health probe (`GATEWAY_HEALTH_TIMEOUT_SECS`).
+    pub health_timeout_secs: u64,
+    /// How long probe results are reused (`GATEWAY_HEALTH_CACHE_SECS`).
+    pub health_cache_secs: u64,
+    /// Extra attempts on another instance for idempotent requests (`GATEWAY_RETRIES`).
+    pub retries: usize,
+    /// Consecutive failures before an instance is taken out of rotation, 0 to never eject
+    /// (`GATEWAY_EJECT_AFTER_FAILURES`).
+    pub eject_after_failures: u32,
+    /// How long an ejected instance is skipped (`GATEWAY_EJECT_SECS`).
+    pub eject_secs: u64,
+    /// Failed requests in a row that open an upstream's circuit, 0 to never open it
+    /// (`GATEWAY_BREAKER_FAILURES`).
+    pub breaker_failures: u32,
+    /// How long an open circuit rejects requests before a trial (`GATEWAY_BREAKER_COOLDOWN_SECS`).
+    pub breaker_cooldown_secs: u64,
+    /// Requests per minute for each client without its own limit, 0 for none
+    /// (`GATEWAY_CLIENT_REQUESTS_PER_MINUTE`).
+    pub client_requests_per_minute: u32,
+    /// Identify anonymous clients by the last `X-Forwarded-For` entry instead of the peer
+    /// address, and pass the chain on; only behind a trusted proxy (`GATEWAY_TRUST_FORWARDED_FOR`).
+    pub trust_forwarded_for: bool,
+    /// gzip/brotli/zstd for clients that accept it (`GATEWAY_COMPRESSION`).
+    pub compression: bool,
+    /// Built frontend served for paths outside the API, with `index.html` as SPA fallback
+    /// (`GATEWAY_STATIC_DIR`). Served without credentials even when auth is enabled.
+    pub static_dir: Option<PathBuf>,
+    pub routes: Vec<RouteConfig>,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            // 8080 занят unfolding-core, 8000 — ai-gateway, 9000 — AI-движком
+            bind: "0.0.0.0:8090".to_string(),
+            frontend_url: "http://localhost:3000".to_string(),
+            connect_timeout_secs: 5,
+            upstream_timeout_secs: 60,
+            max_body_bytes: 64 * 1024 * 1024,
+            health_timeout_secs: 2,
+            health_cache_secs: 5,
+            retries: 1,
+            eject_after_failures: 3,
+            eject_secs: 30,
+            breaker_failures: 5,
+            breaker_cooldown_secs: 30,
+            client_requests_per_minute: 600,
+            trust_forwarded_for: false,
+            routes: vec![
+                RouteConfig::new(
+                    "/api/unfold",
+                    Some(UNFOLDING_CORE),
+                    vec!["http://localhost:8080".to_string()],
+                ),
+                RouteConfig::new(
+                    "/api/ai",
+                    Some(AI_ENGINE),
+                    vec!["http://localhost:9000".to_string()],
+                ),
+            ],
+            auth: AuthConfig::default(),
+            cors: CorsConfig::default(),
+            compression: true,
+            static_dir: None,
+        }
+    }
+}
+
+impl GatewayConfig {
+    /// Reads the config file given by `--config <path>` or `GATEWAY_CONFIG`, applies environment
+    /// overrides and checks the result.
+    pub fn load() -> Result<Self, String> {
+        let args: Vec<String> = std::env::args().collect();
+        let path = args
+            .iter()
+            .position(|arg| arg == "--config")
+            .map(|index| {
+                args.get(index + 1)
+                    .cloned()
+                    .ok_or("--config needs a file path")
+            })
+            .transpose()?
+            .or_else(|| std::env::var("GATEWAY_CONFIG").ok());
+
+        let mut config = match path {
+            Some(path) => {
+                let data = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("cannot read {}: {}", path, e))?;
+                serde_json::from_str(&data)
+                    .map_err(|e| format!("invalid config file {}: {}", path, e))?
+            }
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), String> {
+        if let Ok(bind) = std::env::var("GATEWAY_BIND") {
+            self.bind = bind;
+        }
+        if let Ok(url) = std::env::var("GATEWAY_FRONTEND_URL") {
+            self.frontend_url = url;
+        }
+        if let Ok(origins) = std::env::var("GATEWAY_CORS_ORIGINS") {
+            self.cors.allowed_origins = split_list(&origins);
+        }
+        if let Ok(value) = std::env::var("GATEWAY_COMPRESSION") {
+            self.compression = is_enabled(&value);
+        }
+        if let Ok(dir) = std::env::var("GATEWAY_STATIC_DIR") {
+            self.static_dir = (!dir.trim().is_empty()).then(|| PathBuf::from(dir.trim()));
+        }
+        if let Some(secs) = env_number("GATEWAY_CONNECT_TIMEOUT_SECS")? {
+            self.connect_timeout_secs = secs;
+        }
+        if let Some(secs) = env_number("GATEWAY_UPSTREAM_TIMEOUT_SECS")? {
+            self.upstream_timeout_secs = secs;
+        }
+        if let Some(bytes) = env_number("GATEWAY_MAX_BODY_BYTES")? {
+            self.max_body_bytes = bytes as usize;
+        }
+        if let Some(secs) = env_number("GATEWAY_HEALTH_TIMEOUT_SECS")? {
+            self.health_timeout_secs = secs;
+        }
+        if let Some(secs) = env_number("GATEWAY_HEALTH_CACHE_SECS")? {
+            self.health_cache_secs = secs;
+        }
+        if let Some(retries) = env_number("GATEWAY_RETRIES")? {
+            self.retries = retries as usize;
+        }
+        if let Some(failures) = env_number("GATEWAY_EJECT_AFTER_FAILURES")? {
+            self.eject_after_failures = failures as u32;
+        }
+        if let Some(secs) = env_number("GATEWAY_EJECT_SECS")? {
+            self.eject_secs = secs;
+        }
+        if let Some(failures) = env_number("GATEWAY_BREAKER_FAILURES")? {
+            self.breaker_failures = failures as u32;
+        }
+        if let Some(secs) = env_number("GATEWAY_BREAKER_COOLDOWN_SECS")? {
+            self.breaker_cooldown_secs = secs;
+        }
+        if let Some(limit) = env_number("GATEWAY_CLIENT_REQUESTS_PER_MINUTE")? {
+            self.client_requests_per_minute = limit as u32;
+        }
+        if let Ok(value) = std::env::var("GATEWAY_TRUST_FORWARDED_FOR") {
+            self.trust_forwarded_for = is_enabled(&value);
+        }
+        if let Ok(urls) = std::env::var("UNFOLDING_CORE_URLS") {
+            self.set_targets(UNFOLDING_CORE, "/api/unfold", &urls);
+        }
+        if let Ok(balance) = std::env::var("UNFOLDING_CORE_BALANCE") {
+            let balance = balance
+                .trim()
+                .parse()
+                .map_err(|e| format!("UNFOLDING_CORE_BALANCE: {}", e))?;
+            if let Some(route) = self
+                .routes
+                .iter_mut()
+                .find(|route| route.upstream.as_deref() == Some(UNFOLDING_CORE))
+            {
+                route.balance = balance;
+            }
+        }
+        if let Ok(urls) = std::env::var("AI_ENGINE_URL") {
+            self.set_targets(AI_ENGINE, "/api/ai", &urls);
+        }
+
+        // GATEWAY_API_KEYS=id=key,id=key — клиенты с доступом ко всем маршрутам
+        if let Ok(keys) = std::env::var("GATEWAY_API_KEYS") {
+            for entry in split_list(&keys) {
+                let (id, key) = entry.split_once('=').ok_or_else(|| {
+                    format!("GATEWAY_API_KEYS entry '{}' must look like id=key", entry)
+                })?;
+                self.auth.keys.retain(|other| other.id != id);
+                self.auth.keys.push(ApiKeyConfig {
+                    id: id.to_string(),
+                    key: Some(key.to_string()),
+                    routes: Vec::new(),
+                    requests_per_minute: None,
+                });
+            }
+        }
+        if let Ok(secret) = std::env::var("GATEWAY_TOKEN_SECRET") {
+            self.auth.token_secret = Some(secret);
+        }
+
+        if let Ok(extra) = std::env::var("GATEWAY_ROUTES") {
+            for entry in extra
+                .split(';')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+            {
+                let (prefix, targets) = entry
+                    .split_once('=')
+                    .filter(|(prefix, _)| prefix.starts_with('/'))
+                    .ok_or_else(|| {
+                        format!(
+                            "GATEWAY_ROUTES entry '{}' must look like /prefix=http://host",
+                            entry
+                        )
+                    })?;
+                let prefix = prefix.trim().trim_end_matches('/');
+                // Запись с тем же префиксом заменяет маршрут из файла или встроенный
+                let upstream = self
+                    .routes
+                    .iter()
+                    .find(|route| route.prefix == prefix)
+                    .and_then(|route| route.upstream.clone());
+                self.routes.retain(|route| route.prefix != prefix);
+                self.routes.push(RouteConfig::new(
+                    prefix,
+                    upstream.as_deref(),
+                    split_list(targets),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn set_targets(&mut self, upstream: &str, prefix: &str, urls: &str) {
+        let targets = split_list(urls);
+        match self
+            .routes
+            .iter_mut()
+            .find(|route| route.upstream.as_deref() == Some(upstream))
+        {
+            Some(route) => route.targets = targets,
+            None => self
+                .routes
+                .push(RouteConfig::new(prefix, Some(upstream), targets)),
+        }
+    }
+
+    /// Rejects settings the gateway cannot run with, including routes that point back at the
+    /// gateway's own listener.
+    fn validate(&self) -> Result<(), String> {
+        let bind = self.bind_addr()?;
+        self.auth.validate()?;
+        self.cors.validate()?;
+        if self.cors.allowed_origins.is_empty() {
+            reqwest::Url::parse(&self.frontend_url)
+                .map_err(|e| format!("invalid frontend_url '{}': {}", self.frontend_url, e))?;
+        }
+        if let Some(dir) = &self.static_dir {
+            if !dir.join("index.html").is_file() {
+                return Err(format!(
+                    "static_dir {} has no index.html; build the frontend or unset GATEWAY_STATIC_DIR",
+                    dir.display()
+                ));
+            }
+        }
+
+        for (index, route) in self.routes.iter().enumerate() {
+            if !route.prefix.starts_with('/') {
+                return Err(format!("route prefix '{}' must start with /", route.prefix));
+            }
+            if self.routes[..index].iter().any(|other| {
+                other.prefix.trim_end_matches('/') == route.prefix.trim_end_matches('/')
+            }) {
+                return Err(format!("route prefix {} is configured twice", route.prefix));
+            }
+            if route.targets.is_empty() {
+                return Err(format!("route {} has no upstream URLs", route.prefix));
+            }
+            for target in &route.targets {
+                let url = reqwest::Url::parse(target).map_err(|e| {
+                    format!("route {}: invalid URL '{}': {}", route.prefix, target, e)
+                })?;
+                if points_at(&url, bind) {
+                    return Err(format!(
+                        "route {} points at {}, which is the gateway's own address {}; \
+                         change GATEWAY_BIND or the upstream URL",
+                        route.prefix, target, bind
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bind_addr(&self) -> Result<SocketAddr, String> {
+        self.bind
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| format!("bind address '{}' must look like 0.0.0.0:8090", self.bind))
+    }
+
+    pub fn health_timeout(&self) -> Duration {
+        Duration::from_secs(self.health_timeout_secs)
+    }
+
+    pub fn health_cache(&self) -> Duration {
+        Duration::from_secs(self.health_cache_secs)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn build_routes(&self) -> Vec<Route> {
+        self.routes
+            .iter()
+            .map(|route| {
+                let upstream = route
+                    .upstream
+                    .clone()
+                    .unwrap_or_else(|| route.prefix.trim_start_matches('/').to_string());
+                let timeout =
+                    Duration::from_secs(route.timeout_secs.unwrap_or(self.upstream_timeout_secs));
+                let ejection = Ejection {
+                    failures: self.eject_after_failures,
+                    duration: Duration::from_secs(self.eject_secs),
+                };
+                Route::new(&route.prefix, &upstream, route.targets.clone(), timeout)
+                    .with_health(route.required, &route.health_path)
+                    .with_balancing(
+                        route.balance,
+                        route.retries.unwrap_or(self.retries),
+                        ejection,
+                    )
+                    .with_breaker(BreakerSettings {
+                        failures: route.breaker_failures.unwrap_or(self.breaker_failures),
+                        cooldown: Duration::from_secs(
+                            route
+                                .breaker_cooldown_secs
+                                .unwrap_or(self.breaker_cooldown_secs),
+                        ),
+                    })
+            })
+            .collect()
+    }
+}
+
+// Упирается ли URL апстрима в собственный адрес шлюза (тот же порт на локальной машине)
+fn points_at(url: &reqwest::Url, bind: SocketAddr) -> bool {
+    if url.port_or_known_default() != Some(bind.port()) {
+        return false;
+    }
+    let local = matches!(
+        url.host_str(),
+        Some("localhost" | "127.0.0.1" | "[::1]" | "0.0.0.0")
+    );
+    local || url.host_str() == Some(bind.ip().to_string().as_str())
+}
+
+fn env_number(name: &str) -> Result<Option<u64>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} must be a whole number, got '{}'", name, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn is_enabled(value: &str) -> bool {
+    matches!(value.trim(), "1" | "true" | "yes")
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect()
+}
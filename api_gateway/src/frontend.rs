@@ -0,0 +1,142 @@
+use actix_cors::Cors;
+use actix_files::{Files, NamedFile};
+use actix_web::{
+    dev::{fn_service, ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, HeaderValue},
+        Method,
+    },
+    HttpResponse,
+};
+use std::path::{Path, PathBuf};
+
+use crate::config::CorsConfig;
+
+/// CORS policy for browsers calling the gateway from another origin. Preflight requests are
+/// answered here, before authentication.
+pub fn cors(config: &CorsConfig, frontend_url: &str) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .expose_headers(config.expose_headers.iter().map(String::as_str))
+        .max_age(config.max_age_secs);
+    if config.allows_any_origin() {
+        cors = cors.allow_any_origin().send_wildcard();
+    } else if config.allowed_origins.is_empty() {
+        cors = cors.allowed_origin(frontend_url.trim_end_matches('/'));
+    } else {
+        for origin in &config.allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+    if config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+    cors
+}
+
+/// Serves a built single-page frontend from `dir`. Paths without a file extension that match
+/// no file get `index.html`, so client-side routes survive a reload.
+pub fn files(dir: &Path) -> Files {
+    let index = dir.join("index.html");
+    Files::new("/", dir)
+        .index_file("index.html")
+        .default_handler(fn_service(move |req: ServiceRequest| {
+            spa_fallback(req, index.clone())
+        }))
+}
+
+async fn spa_fallback(
+    req: ServiceRequest,
+    index: PathBuf,
+) -> Result<ServiceResponse, actix_web::Error> {
+    let (req, _) = req.into_parts();
+    // Отсутствующий файл ресурса (/assets/app.js) — честный 404, а не страница приложения
+    let is_page = matches!(*req.method(), Method::GET | Method::HEAD)
+        && !req.path().starts_with("/api/")
+        && !req
+            .path()
+            .rsplit('/')
+            .next()
+            .is_some_and(|segment| segment.contains('.'));
+    if !is_page {
+        return Ok(ServiceResponse::new(req, HttpResponse::NotFound().finish()));
+    }
+
+    let mut response = NamedFile::open_async(&index).await?.into_response(&req);
+    // index.html ссылается на файлы с хешами в именах, поэтому его самого не кешируем
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    Ok(ServiceResponse::new(req, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::{self, Auth},
+        config::{ApiKeyConfig, AuthConfig},
+        health::HealthChecker,
+        rate_limit::ClientLimiter,
+        routes::RoutingTable,
+        Gateway,
+    };
+    use actix_web::{
+        http::StatusCode,
+        middleware,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+    use std::{fs, time::Duration};
+
+    #[actix_web::test]
+    async fn test_frontend_is_served_without_credentials() {
+        let dir = std::env::temp_dir().join(format!("gateway-frontend-{}", std::process::id()));
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::write(dir.join("index.html"), "<!doctype html>").unwrap();
+        fs::write(dir.join("assets/app.js"), "export {}").unwrap();
+
+        let auth = Auth::from_config(&AuthConfig {
+            keys: vec![ApiKeyConfig {
+                id: "web".to_string(),
+                key: Some("0123456789abcdef".to_string()),
+                routes: Vec::new(),
+                requests_per_minute: None,
+            }],
+            ..AuthConfig::default()
+        });
+        let client = reqwest::Client::new();
+        let gateway = web::Data::new(Gateway {
+            client: client.clone(),
+            routes: RoutingTable::new(Vec::new()).unwrap(),
+            health: HealthChecker::new(client, Duration::from_secs(1), Duration::from_secs(1)),
+            auth,
+            limiter: ClientLimiter::new(0, false),
+            frontend_url: "http://localhost:3000".to_string(),
+            static_dir: Some(dir.clone()),
+        });
+        let app = init_service(
+            App::new()
+                .app_data(gateway)
+                .wrap(middleware::from_fn(auth::authenticate))
+                .route("/api/{path:.*}", web::route().to(HttpResponse::Ok))
+                .service(files(&dir)),
+        )
+        .await;
+
+        for (path, status) in [
+            ("/", StatusCode::OK),
+            ("/index.html", StatusCode::OK),
+            ("/assets/app.js", StatusCode::OK),
+            ("/projects/7", StatusCode::OK),
+            ("/assets/missing.js", StatusCode::NOT_FOUND),
+            ("/api/status", StatusCode::UNAUTHORIZED),
+            ("/%61pi/status", StatusCode::UNAUTHORIZED),
+        ] {
+            let response = call_service(&app, TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(response.status(), status, "{}", path);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Mutex;
+
+use crate::routes::RoutingTable;
+
+/// Result of probing one upstream instance.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetHealth {
+    pub url: String,
+    pub healthy: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An upstream is up while at least one of its instances is healthy.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamHealth {
+    pub prefix: String,
+    pub status: &'static str,
+    pub required: bool,
+    /// Fastest healthy instance.
+    pub latency_ms: Option<u64>,
+    pub targets: Vec<TargetHealth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// `ok`, `degraded` (an optional upstream is down) or `unavailable` (a required one is).
+    pub status: &'static str,
+    /// Unix time of the probes in milliseconds; reports are reused for a few seconds.
+    pub checked_at: u64,
+    pub upstreams: BTreeMap<String, UpstreamHealth>,
+}
+
+impl HealthReport {
+    pub fn is_available(&self) -> bool {
+        self.status != "unavailable"
+    }
+
+    /// `up` or `down` for every upstream, without target URLs, errors or latencies.
+    pub fn upstream_statuses(&self) -> BTreeMap<&str, &'static str> {
+        self.upstreams
+            .iter()
+            .map(|(name, upstream)| (name.as_str(), upstream.status))
+            .collect()
+    }
+}
+
+/// Probes every route's instances and caches the report for `ttl`.
+pub struct HealthChecker {
+    client: reqwest::Client,
+    timeout: Duration,
+    ttl: Duration,
+    // Асинхронный мьютекс держим на время проверки: параллельные запросы ждут один общий опрос
+    cached: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl HealthChecker {
+    pub fn new(client: reqwest::Client, timeout: Duration, ttl: Duration) -> Self {
+        Self {
+            client,
+            timeout,
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub async fn report(&self, routes: &RoutingTable) -> HealthReport {
+        let mut cached = self.cached.lock().await;
+        if let Some((at, report)) = cached.as_ref() {
+            if at.elapsed() < self.ttl {
+                return report.clone();
+            }
+        }
+
+        let report = self.probe_all(routes).await;
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    async fn probe_all(&self, routes: &RoutingTable) -> HealthReport {
+        // Все экземпляры опрашиваем одновременно, чтобы /health не ждал сумму таймаутов
+        let probes: Vec<Vec<_>> = routes
+            .routes()
+            .iter()
+            .map(|route| {
+                route
+                    .targets
+                    .iter()
+                    .map(|target| {
+                        let url =
+                            format!("{}/{}", target, route.health_path.trim_start_matches('/'));
+                        tokio::spawn(probe(self.client.clone(), url, self.timeout))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut upstreams = BTreeMap::new();
+        let mut status = "ok";
+        for (route, handles) in routes.routes().iter().zip(probes) {
+            let mut targets = Vec::with_capacity(handles.len());
+            for (target, handle) in route.targets.iter().zip(handles) {
+                targets.push(handle.await.unwrap_or_else(|e| TargetHealth {
+                    url: target.clone(),
+                    healthy: false,
+                    latency_ms: 0,
+                    error: Some(e.to_string()),
+                }));
+            }
+            let latency_ms = targets
+                .iter()
+                .filter(|target| target.healthy)
+                .map(|target| target.latency_ms)
+                .min();
+            let up = latency_ms.is_some();
+            if !up {
+                status = match (route.required, status) {
+                    (true, _) => "unavailable",
+                    (false, "ok") => "degraded",
+                    (false, current) => current,
+                };
+            }
+            upstreams.insert(
+                route.upstream.clone(),
+                UpstreamHealth {
+                    prefix: route.prefix.clone(),
+                    status: if up { "up" } else { "down" },
+                    required: route.required,
+                    latency_ms,
+                    targets,
+                },
+            );
+        }
+
+        HealthReport {
+            status,
+            checked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64),
+            upstreams,
+        }
+    }
+}
+
+async fn probe(client: reqwest::Client, url: String, timeout: Duration) -> TargetHealth {
+    let start = Instant::now();
+    let outcome = client.get(&url).timeout(timeout).send().await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let error = match outcome {
+        Ok(response) if response.status().is_success() => None,
+        Ok(response) => Some(format!("health check returned {}", response.status())),
+        Err(e) if e.is_timeout() => Some(format!("no answer within {} ms", timeout.as_millis())),
+        Err(e) => Some(e.to_string()),
+    };
+    TargetHealth {
+        url,
+        healthy: error.is_none(),
+        latency_ms,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_statuses_hide_targets() {
+        let target = TargetHealth {
+            url: "http://10.0.0.5:8080/health".to_string(),
+            healthy: false,
+            latency_ms: 3,
+            error: Some("connection refused".to_string()),
+        };
+        let report = HealthReport {
+            status: "unavailable",
+            checked_at: 0,
+            upstreams: BTreeMap::from([(
+                "unfolding_core".to_string(),
+                UpstreamHealth {
+                    prefix: "/api/unfold".to_string(),
+                    status: "down",
+                    required: true,
+                    latency_ms: None,
+                    targets: vec![target],
+                },
+            )]),
+        };
+
+        let statuses = serde_json::to_string(&report.upstream_statuses()).unwrap();
+        assert_eq!(statuses, r#"{"unfolding_core":"down"}"#);
+    }
+}
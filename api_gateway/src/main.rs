@@ -1,41 +1,231 @@
-use actix_web::{web, App, HttpServer, HttpResponse, Result};
+use actix_web::{middleware, web, App, HttpRequest, HttpServer, HttpResponse, Result};
 use serde_json::json;
 
+mod auth;
+mod breaker;
+mod config;
+mod frontend;
+mod health;
+mod proxy;
+mod rate_limit;
+mod request_id;
+mod routes;
+
+use auth::Auth;
+use config::GatewayConfig;
+use health::HealthChecker;
+use rate_limit::ClientLimiter;
+use request_id::TraceContext;
+use routes::RoutingTable;
+
+struct Gateway {
+    client: reqwest::Client,
+    routes: RoutingTable,
+    health: HealthChecker,
+    auth: Option<Auth>,
+    limiter: ClientLimiter,
+    frontend_url: String,
+    static_dir: Option<std::path::PathBuf>,
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).cloned()
+}
+
+/// `api_gateway --issue-token <key id> [--token-ttl-secs N]` prints a bearer token and exits.
+fn issue_token(config: &GatewayConfig, auth: Option<&Auth>, key_id: &str) -> ! {
+    let Some(tokens) = auth.and_then(Auth::tokens) else {
+        eprintln!("Cannot issue tokens: set token_secret in the auth config or GATEWAY_TOKEN_SECRET");
+        std::process::exit(2);
+    };
+    if !config.auth.keys.iter().any(|key| key.id == key_id) {
+        eprintln!("Cannot issue a token for {}: no auth key with this id is configured", key_id);
+        std::process::exit(2);
+    }
+    let ttl_secs = match arg_value("--token-ttl-secs").map(|value| value.parse::<u64>()) {
+        Some(Ok(secs)) => secs,
+        Some(Err(_)) => {
+            eprintln!("--token-ttl-secs must be a whole number");
+            std::process::exit(2);
+        }
+        None => 24 * 60 * 60,
+    };
+    println!("{}", tokens.issue(key_id, std::time::Duration::from_secs(ttl_secs)));
+    std::process::exit(0);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    println!("Pepakura Next API Gateway starting on 0.0.0.0:8080...");
-    
-    HttpServer::new(|| {
+    let config = match GatewayConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let auth = Auth::from_config(&config.auth);
+    if let Some(key_id) = arg_value("--issue-token") {
+        issue_token(&config, auth.as_ref(), &key_id);
+    }
+
+    let addr = config.bind_addr().map_err(std::io::Error::other)?;
+    println!("Pepakura Next API Gateway starting on {}...", addr);
+    match &auth {
+        Some(_) => println!("Authentication required for {} client(s)", config.auth.keys.len()),
+        None => println!("Authentication disabled: no auth keys or token_secret configured"),
+    }
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(config.connect_timeout())
+        .build()
+        .map_err(std::io::Error::other)?;
+    let routes = RoutingTable::new(config.build_routes()).map_err(std::io::Error::other)?;
+    for route in routes.routes() {
+        println!(
+            "Routing {}/* to {} ({}, {:?})",
+            route.prefix,
+            route.upstream,
+            route.targets.join(", "),
+            route.balance
+        );
+    }
+    let health = HealthChecker::new(client.clone(), config.health_timeout(), config.health_cache());
+    let gateway = web::Data::new(Gateway {
+        client,
+        routes,
+        health,
+        auth,
+        limiter: ClientLimiter::new(config.client_requests_per_minute, config.trust_forwarded_for),
+        frontend_url: config.frontend_url.clone(),
+        static_dir: config.static_dir.clone(),
+    });
+    let max_body_bytes = config.max_body_bytes;
+    let compression = config.compression;
+    if let Some(dir) = &config.static_dir {
+        println!("Serving the frontend from {}", dir.display());
+    }
+
+    let server = HttpServer::new(move || {
         App::new()
+            .app_data(gateway.clone())
+            .app_data(web::PayloadConfig::new(max_body_bytes))
+            // Middleware выполняются снизу вверх: trace, проверка пути, сжатие, CORS, auth, затем лимит
+            // по клиенту. CORS стоит перед auth, чтобы preflight-запросы без ключа не получали 401
+            .wrap(middleware::from_fn(rate_limit::limit))
+            .wrap(middleware::from_fn(auth::authenticate))
+            .wrap(frontend::cors(&config.cors, &config.frontend_url))
+            .wrap(middleware::Condition::new(compression, middleware::Compress::default()))
+            // Пути с . и .. отклоняем до проверки прав и выбора маршрута
+            .wrap(middleware::from_fn(routes::reject_dot_segments))
+            // X-Request-Id и traceparent: принимаем от клиента или создаём, ответ несёт id запроса
+            .wrap(middleware::from_fn(request_id::propagate))
             .route("/health", web::get().to(health_check))
             .route("/api/status", web::get().to(get_status))
-            .route("/api/proxy/ai", web::post().to(proxy_ai))
-    })
-    .bind("0.0.0.0:8080")?
-    .run()
-    .await
+            .route("/api/proxy/ai", web::route().to(proxy_ai))
+            .route("/api/proxy/ai/{path:.*}", web::route().to(proxy_ai))
+            .route("/api/{path:.*}", web::route().to(proxy_route))
+            .configure(|cfg| {
+                if let Some(dir) = &config.static_dir {
+                    cfg.service(frontend::files(dir));
+                }
+            })
+    });
+    // Занятый порт — частая ситуация рядом с unfolding-core, сообщаем об этом сразу
+    let server = match server.bind(addr) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!(
+                "Cannot listen on {}: {}. Another service may already use this port; set GATEWAY_BIND or \"bind\" in the config file.",
+                addr, e
+            );
+            std::process::exit(1);
+        }
+    };
+    server.run().await
 }
 
-async fn health_check() -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "ok",
+/// Probes every upstream (results are cached for `health_cache_secs`) and answers
+/// `503 Service Unavailable` while a required one has no healthy instance.
+///
+/// The endpoint is public, so it only reports statuses; target URLs, probe errors and
+/// latencies are in the authenticated `/api/status`.
+async fn health_check(gateway: web::Data<Gateway>) -> Result<HttpResponse> {
+    let report = gateway.health.report(&gateway.routes).await;
+    let mut response = if report.is_available() {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    Ok(response.json(json!({
+        "status": report.status,
         "service": "api_gateway",
-        "version": "1.0.0"
+        "version": "1.0.0",
+        "checked_at": report.checked_at,
+        "upstreams": report.upstream_statuses()
     })))
 }
 
-async fn get_status() -> Result<HttpResponse> {
+async fn get_status(gateway: web::Data<Gateway>) -> Result<HttpResponse> {
+    let report = gateway.health.report(&gateway.routes).await;
+    let routes: serde_json::Map<String, serde_json::Value> = gateway
+        .routes
+        .routes()
+        .iter()
+        .map(|route| {
+            let value = json!({
+                "upstream": route.upstream,
+                "targets": route.targets,
+                "timeout_secs": route.timeout.as_secs(),
+                "required": route.required,
+                "balance": route.balance,
+                "backends": route.backends(),
+                "circuit": route.breaker.status(),
+                "health": report.upstreams.get(&route.upstream)
+            });
+            (route.prefix.clone(), value)
+        })
+        .collect();
+
     Ok(HttpResponse::Ok().json(json!({
         "service": "Pepakura Next Gateway",
         "version": "1.0.0",
-        "ai_engine": "http://localhost:9000",
-        "frontend": "http://localhost:3000"
+        "ai_engine": gateway.routes.get(routes::AI_ENGINE).map(|route| route.targets.clone()),
+        "unfolding_core": gateway.routes.get(routes::UNFOLDING_CORE).map(|route| route.targets.clone()),
+        "frontend": gateway.frontend_url,
+        "static_dir": gateway.static_dir,
+        "auth": gateway.auth.as_ref().map_or_else(|| json!({ "enabled": false }), Auth::status),
+        "rate_limit": gateway.limiter.status(),
+        "status": report.status,
+        "routes": routes
     })))
 }
 
-async fn proxy_ai(body: web::Bytes) -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "proxied",
-        "message": "Request forwarded to AI Engine"
-    })))
+/// Forwards `/api/proxy/ai/<path>` to `<AI engine>/<path>`, any method. Kept for older clients;
+/// `/api/ai/<path>` does the same through the routing table.
+async fn proxy_ai(gateway: web::Data<Gateway>, req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    let path = req.match_info().get("path").unwrap_or("");
+    let Some(route) = gateway.routes.get(routes::AI_ENGINE) else {
+        return route_not_found(&req);
+    };
+    proxy::forward(&gateway.client, route, path, &req, body).await
+}
+
+/// Forwards any other `/api/...` request to the upstream whose prefix it matches,
+/// with the prefix removed.
+async fn proxy_route(gateway: web::Data<Gateway>, req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    // Маршрут ищем по тому же декодированному пути, что проверял auth
+    let Some((route, path)) = gateway.routes.resolve(req.match_info().as_str()) else {
+        return route_not_found(&req);
+    };
+    proxy::forward(&gateway.client, route, path, &req, body).await
+}
+
+fn route_not_found(req: &HttpRequest) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "error": format!("No upstream is configured for {}", req.path()),
+        "code": "ROUTE_NOT_FOUND",
+        "request_id": TraceContext::of(req).request_id
+    }))
 }
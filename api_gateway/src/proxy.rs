@@ -0,0 +1,303 @@
+use actix_web::{
+    body::SizedStream,
+    http::{header, Method, StatusCode},
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+use std::{fmt, time::Duration};
+
+use crate::{
+    rate_limit::{self, ClientId, CLIENT_ID_HEADER, FORWARDED_FOR_HEADER},
+    request_id::{TraceContext, REQUEST_ID_HEADER, TRACEPARENT_HEADER},
+    routes::Route,
+    Gateway,
+};
+
+// Заголовки, относящиеся к одному соединению; их не передаём ни в одну сторону
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Forwards `req` to an instance of `route` at `path` (with the original query string)
+/// and streams the upstream response back with its status and headers.
+///
+/// Idempotent requests that could not reach an instance, or got `502`/`503` from it, are
+/// retried on another instance up to `route.retries` times. `route.timeout` limits the wait
+/// for the response headers only, so streamed bodies such as job events are not cut off.
+/// Connection failures become `502 Bad Gateway` and timeouts `504 Gateway Timeout`, both
+/// with an `{ "error", "code", "upstream" }` body. While the route's circuit is open the request
+/// fails with `503 Service Unavailable` without reaching the upstream.
+pub async fn forward(
+    client: &reqwest::Client,
+    route: &Route,
+    path: &str,
+    req: &HttpRequest,
+    body: web::Bytes,
+) -> HttpResponse {
+    let attempts = if is_idempotent(req.method()) {
+        1 + route.retries.min(route.targets.len() - 1)
+    } else {
+        1
+    };
+    let mut tried = Vec::with_capacity(attempts);
+    let context = TraceContext::of(req);
+    let permit = match route.breaker.allow() {
+        Ok(permit) => permit,
+        Err(wait) => return circuit_open(&route.upstream, &context, wait),
+    };
+
+    loop {
+        let lease = route.acquire(&tried);
+        tried.push(lease.index());
+        let can_retry = tried.len() < attempts;
+
+        let mut url = format!("{}/{}", lease.target(), path.trim_start_matches('/'));
+        if !req.query_string().is_empty() {
+            url.push('?');
+            url.push_str(req.query_string());
+        }
+
+        // Ограничиваем только ожидание заголовков: поток событий задания может идти дольше
+        let sent = match tokio::time::timeout(
+            route.timeout,
+            build(client, &url, req, &context, body.clone()).send(),
+        )
+        .await
+        {
+            Ok(result) => result.map_err(Failure::Request),
+            Err(_) => Err(Failure::Timeout(route.timeout)),
+        };
+        match sent {
+            Ok(response) if is_unavailable(response.status()) => {
+                // 503 отдаёт и перегруженный экземпляр (QUEUE_FULL), его не исключаем
+                if response.status() == StatusCode::BAD_GATEWAY {
+                    lease.failed();
+                }
+                if can_retry {
+                    println!(
+                        "[{}] {} answered {}, retrying on another instance",
+                        context.request_id,
+                        url,
+                        response.status()
+                    );
+                    continue;
+                }
+                if response.status() == StatusCode::BAD_GATEWAY {
+                    permit.failed();
+                } else {
+                    permit.succeeded();
+                }
+                return relay(response);
+            }
+            Ok(response) => {
+                lease.succeeded();
+                permit.succeeded();
+                return relay(response);
+            }
+            Err(e) => {
+                lease.failed();
+                // По таймауту не повторяем: запрос мог дойти, и ожидание удвоилось бы
+                if can_retry && !e.is_timeout() {
+                    println!(
+                        "[{}] Proxy to {} failed: {}, retrying on another instance",
+                        context.request_id, url, e
+                    );
+                    continue;
+                }
+                permit.failed();
+                return upstream_error(&route.upstream, &url, &context, &e);
+            }
+        }
+    }
+}
+
+fn build(
+    client: &reqwest::Client,
+    url: &str,
+    req: &HttpRequest,
+    context: &TraceContext,
+    body: web::Bytes,
+) -> reqwest::RequestBuilder {
+    let mut request = client.request(req.method().clone(), url);
+    for (name, value) in req.headers() {
+        let replaced = name == header::HOST
+            || name == header::CONTENT_LENGTH
+            || name == REQUEST_ID_HEADER
+            || name == TRACEPARENT_HEADER
+            || name == CLIENT_ID_HEADER
+            || name == FORWARDED_FOR_HEADER
+            || name == "x-forwarded-host"
+            || name == "x-forwarded-proto";
+        if !is_hop_by_hop(name) && !replaced {
+            request = request.header(name, value);
+        }
+    }
+    // Свои значения заменяют входящие: апстрим видит span шлюза родителем
+    request = request
+        .header(REQUEST_ID_HEADER, context.request_id.as_str())
+        .header(TRACEPARENT_HEADER, context.traceparent());
+    // Ядро ограничивает число заданий на клиента по этому заголовку
+    if let Some(client) = req.extensions().get::<ClientId>() {
+        request = request.header(CLIENT_ID_HEADER, client.0.as_str());
+    }
+    // Цепочку клиента продолжаем только за доверенным прокси, иначе она подделана
+    let trusted = req
+        .app_data::<web::Data<Gateway>>()
+        .is_some_and(|gateway| gateway.limiter.trusts_forwarded_for());
+    let chain = trusted.then(|| rate_limit::forwarded_for(req)).flatten();
+    if let Some(peer) = req.peer_addr().map(|addr| addr.ip().to_string()) {
+        let forwarded = chain.map_or_else(|| peer.clone(), |chain| format!("{}, {}", chain, peer));
+        request = request.header(FORWARDED_FOR_HEADER, forwarded);
+    }
+    let connection = req.connection_info();
+    request
+        .header("x-forwarded-host", connection.host())
+        .header("x-forwarded-proto", connection.scheme())
+        .body(body)
+}
+
+fn relay(response: reqwest::Response) -> HttpResponse {
+    let mut builder = HttpResponse::build(response.status());
+    for (name, value) in response.headers() {
+        if !is_hop_by_hop(name) && name != header::CONTENT_LENGTH {
+            builder.append_header((name.clone(), value.clone()));
+        }
+    }
+    // Длину сохраняем, если апстрим её сообщил, иначе отдаём тело по частям
+    match response.content_length() {
+        Some(length) => builder.body(SizedStream::new(length, response.bytes_stream())),
+        None => builder.streaming(response.bytes_stream()),
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
+fn is_unavailable(status: StatusCode) -> bool {
+    status == StatusCode::BAD_GATEWAY || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+fn is_hop_by_hop(name: &header::HeaderName) -> bool {
+    HOP_BY_HOP.contains(&name.as_str())
+}
+
+fn circuit_open(upstream: &str, context: &TraceContext, wait: Duration) -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, wait.as_secs_f64().ceil() as u64))
+        .json(json!({
+            "error": format!("{} is failing, requests are paused", upstream),
+            "code": "CIRCUIT_OPEN",
+            "upstream": upstream,
+            "request_id": context.request_id
+        }))
+}
+
+/// Why an attempt got no response from the upstream.
+enum Failure {
+    Request(reqwest::Error),
+    /// No response headers within the route's timeout.
+    Timeout(Duration),
+}
+
+impl Failure {
+    fn is_timeout(&self) -> bool {
+        match self {
+            Failure::Request(error) => error.is_timeout(),
+            Failure::Timeout(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Request(error) => error.fmt(f),
+            Failure::Timeout(limit) => write!(f, "no response within {} s", limit.as_secs()),
+        }
+    }
+}
+
+fn upstream_error(
+    upstream: &str,
+    url: &str,
+    context: &TraceContext,
+    error: &Failure,
+) -> HttpResponse {
+    let (status, code) = if error.is_timeout() {
+        (StatusCode::GATEWAY_TIMEOUT, "UPSTREAM_TIMEOUT")
+    } else {
+        (StatusCode::BAD_GATEWAY, "UPSTREAM_UNAVAILABLE")
+    };
+    println!(
+        "[{}] Proxy to {} ({}) failed: {}",
+        context.request_id, upstream, url, error
+    );
+
+    HttpResponse::build(status).json(json!({
+        "error": format!("{} did not respond: {}", upstream, error),
+        "code": code,
+        "upstream": upstream,
+        "request_id": context.request_id
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_upstream_request_carries_gateway_trace() {
+        let incoming = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let req = TestRequest::post()
+            .uri("/api/unfold/unfold")
+            .insert_header((TRACEPARENT_HEADER, incoming))
+            .insert_header((REQUEST_ID_HEADER, "req-1"))
+            .insert_header((CLIENT_ID_HEADER, "key:spoofed"))
+            .insert_header((header::CONNECTION, "keep-alive"))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .insert_header((FORWARDED_FOR_HEADER, "198.51.100.1"))
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .to_http_request();
+        req.extensions_mut()
+            .insert(ClientId("ip:10.0.0.1".to_string()));
+        let context = TraceContext::from_headers(req.headers());
+
+        let request = build(
+            &reqwest::Client::new(),
+            "http://core/unfold",
+            &req,
+            &context,
+            web::Bytes::from_static(b"{}"),
+        )
+        .build()
+        .unwrap();
+        let headers = request.headers();
+        let sent = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        assert_eq!(sent(REQUEST_ID_HEADER), Some("req-1"));
+        assert_eq!(
+            sent(TRACEPARENT_HEADER),
+            Some(context.traceparent().as_str())
+        );
+        assert_ne!(sent(TRACEPARENT_HEADER), Some(incoming));
+        assert_eq!(sent(CLIENT_ID_HEADER), Some("ip:10.0.0.1"));
+        assert_eq!(sent("content-type"), Some("application/json"));
+        assert_eq!(sent("connection"), None);
+        assert_eq!(headers.get_all(TRACEPARENT_HEADER).iter().count(), 1);
+        // Без доверенного прокси цепочку клиента не передаём
+        assert_eq!(sent(FORWARDED_FOR_HEADER), Some("10.0.0.1"));
+        assert_eq!(headers.get_all(FORWARDED_FOR_HEADER).iter().count(), 1);
+    }
+}
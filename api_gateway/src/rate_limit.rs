@@ -0,0 +1,288 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{auth::Principal, request_id::TraceContext, Gateway};
+
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+pub const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+// Сколько клиентов помним, прежде чем выбросить полностью восстановившиеся корзины
+const PRUNE_ABOVE: usize = 10_000;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let per_second = self.capacity / 60.0;
+        let refilled = now.duration_since(self.updated).as_secs_f64() * per_second;
+        self.tokens = (self.tokens + refilled).min(self.capacity);
+        self.updated = now;
+    }
+}
+
+/// Token buckets by client: each holds `per_minute` tokens and refills continuously.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Takes a token from `client`'s bucket, or returns how long until one is available.
+    pub fn check(&self, client: &str, per_minute: u32) -> Result<(), Duration> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(per_minute);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() > PRUNE_ABOVE {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                bucket.tokens < bucket.capacity
+            });
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            capacity,
+            updated: now,
+        });
+        // Лимит могли поменять в конфигурации, корзина подстраивается под текущий
+        bucket.capacity = capacity;
+        bucket.refill(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) * 60.0 / capacity,
+            ))
+        }
+    }
+}
+
+/// Identity requests are limited by, forwarded upstream in `X-Client-Id`:
+/// `key:<id>` for authenticated clients, `ip:<address>` otherwise.
+#[derive(Debug, Clone)]
+pub struct ClientId(pub String);
+
+/// Per-client request rate: the key's `requests_per_minute`, or the gateway-wide default.
+#[derive(Debug)]
+pub struct ClientLimiter {
+    buckets: RateLimiter,
+    requests_per_minute: u32,
+    trust_forwarded_for: bool,
+    limited: AtomicU64,
+}
+
+impl ClientLimiter {
+    pub fn new(requests_per_minute: u32, trust_forwarded_for: bool) -> Self {
+        Self {
+            buckets: RateLimiter::default(),
+            requests_per_minute,
+            trust_forwarded_for,
+            limited: AtomicU64::new(0),
+        }
+    }
+
+    fn identify(&self, req: &ServiceRequest) -> (ClientId, u32) {
+        if let Some(principal) = req.extensions().get::<Principal>() {
+            let limit = principal
+                .requests_per_minute
+                .unwrap_or(self.requests_per_minute);
+            return (ClientId(format!("key:{}", principal.key_id)), limit);
+        }
+        let ip = client_ip(req.request(), self.trust_forwarded_for)
+            .unwrap_or_else(|| "unknown".to_string());
+        (ClientId(format!("ip:{}", ip)), self.requests_per_minute)
+    }
+
+    pub fn trusts_forwarded_for(&self) -> bool {
+        self.trust_forwarded_for
+    }
+
+    /// Summary for `/api/status`.
+    pub fn status(&self) -> serde_json::Value {
+        json!({
+            "requests_per_minute": self.requests_per_minute,
+            "trust_forwarded_for": self.trust_forwarded_for,
+            "limited": self.limited.load(Ordering::Relaxed)
+        })
+    }
+}
+
+/// Address of the client: with `trust_forwarded_for`, the rightmost `X-Forwarded-For` entry,
+/// else (or without the header) the peer address.
+fn client_ip(req: &HttpRequest, trust_forwarded_for: bool) -> Option<String> {
+    // Левые записи клиент пишет сам, правую добавил наш прокси
+    let forwarded = trust_forwarded_for
+        .then(|| forwarded_for(req))
+        .flatten()
+        .and_then(|chain| {
+            let last = chain.rsplit(',').next()?.trim();
+            let ip = last
+                .parse::<std::net::SocketAddr>()
+                .map_or_else(|_| last.to_string(), |addr| addr.ip().to_string());
+            (!ip.is_empty()).then_some(ip)
+        });
+    forwarded.or_else(|| req.peer_addr().map(|addr| addr.ip().to_string()))
+}
+
+/// All `X-Forwarded-For` headers of `req` as one comma-separated chain.
+pub fn forwarded_for(req: &HttpRequest) -> Option<String> {
+    let entries: Vec<&str> = req
+        .headers()
+        .get_all(FORWARDED_FOR_HEADER)
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    (!entries.is_empty()).then(|| entries.join(", "))
+}
+
+/// Middleware: takes a token from the client's bucket and answers `429 Too Many Requests`
+/// with `Retry-After` when it is empty. Runs after authentication.
+pub async fn limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(gateway) = req.app_data::<web::Data<Gateway>>().cloned() else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+    let limiter = &gateway.limiter;
+    let (client, per_minute) = limiter.identify(&req);
+
+    if let Err(wait) = limiter.buckets.check(&client.0, per_minute) {
+        limiter.limited.fetch_add(1, Ordering::Relaxed);
+        let request_id = TraceContext::of(req.request()).request_id;
+        println!(
+            "[{}] Rate limited {} {} for {}",
+            request_id,
+            req.method(),
+            req.path(),
+            client.0
+        );
+        // Округляем вверх, чтобы клиент не вернулся на мгновение раньше
+        let response = HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, wait.as_secs_f64().ceil() as u64))
+            .json(json!({
+                "error": format!("{} exceeded {} requests per minute", client.0, per_minute),
+                "code": "RATE_LIMITED",
+                "request_id": request_id
+            }));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    req.extensions_mut().insert(client);
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_bucket_refills_continuously_up_to_capacity() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 0.0,
+            capacity: 60.0,
+            updated: start,
+        };
+        bucket.refill(start + Duration::from_millis(1500));
+        assert!((bucket.tokens - 1.5).abs() < 1e-9);
+        bucket.refill(start + Duration::from_secs(600));
+        assert_eq!(bucket.tokens, 60.0);
+    }
+
+    #[test]
+    fn test_check_limits_each_client() {
+        let limiter = RateLimiter::default();
+        assert!(limiter.check("ip:1", 2).is_ok());
+        assert!(limiter.check("ip:1", 2).is_ok());
+        let wait = limiter.check("ip:1", 2).unwrap_err();
+        // Одна монета из двух в минуту копится 30 секунд
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+
+        assert!(limiter.check("ip:2", 2).is_ok());
+        assert!((0..100).all(|_| limiter.check("ip:1", 0).is_ok()));
+    }
+
+    #[test]
+    fn test_check_admits_again_after_refill() {
+        let limiter = RateLimiter::default();
+        // 6000 в минуту: новая монета каждые 10 мс
+        while limiter.check("ip:1", 6000).is_ok() {}
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(limiter.check("ip:1", 6000).is_ok());
+    }
+
+    fn request(forwarded_for: Option<&str>) -> ServiceRequest {
+        let mut request = TestRequest::get().peer_addr("10.0.0.1:4000".parse().unwrap());
+        if let Some(forwarded_for) = forwarded_for {
+            request = request.insert_header((FORWARDED_FOR_HEADER, forwarded_for));
+        }
+        request.to_srv_request()
+    }
+
+    #[test]
+    fn test_authenticated_clients_are_limited_by_key() {
+        let limiter = ClientLimiter::new(30, false);
+        let req = request(None);
+        req.extensions_mut().insert(Principal {
+            key_id: "viewer".to_string(),
+            routes: Vec::new(),
+            requests_per_minute: Some(5),
+        });
+        let (client, limit) = limiter.identify(&req);
+        assert_eq!((client.0.as_str(), limit), ("key:viewer", 5));
+
+        let req = request(None);
+        req.extensions_mut().insert(Principal {
+            key_id: "admin".to_string(),
+            routes: Vec::new(),
+            requests_per_minute: None,
+        });
+        assert_eq!(limiter.identify(&req).1, 30);
+    }
+
+    #[test]
+    fn test_anonymous_clients_are_limited_by_address() {
+        let limiter = ClientLimiter::new(30, false);
+        let (client, limit) = limiter.identify(&request(Some("203.0.113.7")));
+        assert_eq!((client.0.as_str(), limit), ("ip:10.0.0.1", 30));
+
+        let behind_proxy = ClientLimiter::new(30, true);
+        let (client, _) = behind_proxy.identify(&request(Some("203.0.113.7")));
+        assert_eq!(client.0, "ip:203.0.113.7");
+        let (client, _) = behind_proxy.identify(&request(None));
+        assert_eq!(client.0, "ip:10.0.0.1");
+        // Клиент может дописать что угодно слева, но не справа от записи прокси
+        let (client, _) = behind_proxy.identify(&request(Some("198.51.100.1, 203.0.113.7:5100")));
+        assert_eq!(client.0, "ip:203.0.113.7");
+    }
+}
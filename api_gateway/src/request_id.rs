@@ -0,0 +1,241 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage, HttpRequest,
+};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Request id and W3C trace context of one request through the gateway.
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    pub request_id: String,
+    pub trace_id: String,
+    /// Span of the gateway, sent upstream as the parent id.
+    pub span_id: String,
+    pub flags: String,
+}
+
+impl TraceContext {
+    /// Continues the caller's trace from `traceparent` or starts a new one, and keeps a valid
+    /// incoming `X-Request-Id`. Without one the trace id doubles as the request id.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let incoming = headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+        let (trace_id, flags) = incoming.unwrap_or_else(|| (random_hex(2), "01".to_string()));
+        let request_id = headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map_or_else(|| trace_id.clone(), str::to_string);
+
+        Self {
+            request_id,
+            trace_id,
+            span_id: random_hex(1),
+            flags,
+        }
+    }
+
+    /// Context stored on the request by [`propagate`].
+    pub fn of(req: &HttpRequest) -> Self {
+        req.extensions()
+            .get::<Self>()
+            .cloned()
+            .unwrap_or_else(|| Self::from_headers(req.headers()))
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, self.flags)
+    }
+
+    pub fn header_value(&self) -> HeaderValue {
+        // Идентификатор уже проверен или сгенерирован, так что значение всегда допустимо
+        HeaderValue::from_str(&self.request_id).unwrap_or_else(|_| HeaderValue::from_static("-"))
+    }
+}
+
+/// Stores the request's [`TraceContext`] for the handlers and returns its id in `X-Request-Id`.
+pub async fn propagate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let context = TraceContext::from_headers(req.headers());
+    let request_id = context.header_value();
+    req.extensions_mut().insert(context);
+    let mut response = next.call(req).await?;
+    response
+        .headers_mut()
+        .insert(HeaderName::from_static(REQUEST_ID_HEADER), request_id);
+    Ok(response)
+}
+
+// Принимаем только короткие печатные идентификаторы, чтобы чужой заголовок не ломал логи
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len())
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.:".contains(&byte))
+}
+
+/// Trace id and flags of `00-<trace id>-<parent id>-<flags>`; all-zero ids and version `ff`
+/// are invalid.
+fn parse_traceparent(value: &str) -> Option<(String, String)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+    // Версия 00 состоит ровно из четырёх полей; более новые версии могут добавлять поля в конце
+    if version == "00" && parts.next().is_some() {
+        return None;
+    }
+
+    let is_hex = |part: &str, len: usize| {
+        part.len() == len
+            && part
+                .bytes()
+                .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && is_hex(parent_id, 16)
+        && is_hex(flags, 2)
+        && trace_id.bytes().any(|byte| byte != b'0')
+        && parent_id.bytes().any(|byte| byte != b'0');
+    valid.then(|| (trace_id.to_string(), flags.to_string()))
+}
+
+// Случайность без отдельной зависимости: ключи RandomState случайны для процесса
+fn random_hex(words: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    (0..words)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            hasher.write_u128(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos()),
+            );
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        middleware,
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(
+                HeaderName::from_static(name),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_traceparent() {
+        let valid = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        assert_eq!(
+            parse_traceparent(&valid),
+            Some((TRACE_ID.to_string(), "01".to_string()))
+        );
+
+        let zero_trace = format!("00-{}-{}-01", "0".repeat(32), PARENT_ID);
+        let zero_parent = format!("00-{}-{}-01", TRACE_ID, "0".repeat(16));
+        let version_ff = format!("ff-{}-{}-01", TRACE_ID, PARENT_ID);
+        let extra_field = format!("00-{}-{}-01-extra", TRACE_ID, PARENT_ID);
+        let uppercase = format!("00-{}-{}-01", TRACE_ID.to_uppercase(), PARENT_ID);
+        for invalid in [zero_trace, zero_parent, version_ff, extra_field, uppercase] {
+            assert_eq!(parse_traceparent(&invalid), None, "{}", invalid);
+        }
+        // Более поздние версии могут нести дополнительные поля
+        let future = format!("01-{}-{}-01-extra", TRACE_ID, PARENT_ID);
+        assert!(parse_traceparent(&future).is_some());
+    }
+
+    #[test]
+    fn test_context_continues_incoming_trace() {
+        let traceparent = format!("00-{}-{}-00", TRACE_ID, PARENT_ID);
+        let context = TraceContext::from_headers(&headers(&[
+            (TRACEPARENT_HEADER, &traceparent),
+            (REQUEST_ID_HEADER, "req-1"),
+        ]));
+
+        assert_eq!(context.request_id, "req-1");
+        assert_eq!(context.trace_id, TRACE_ID);
+        assert_ne!(context.span_id, PARENT_ID);
+        // Апстрим видит span шлюза родителем в том же трейсе
+        let upstream = context.traceparent();
+        assert_eq!(
+            parse_traceparent(&upstream),
+            Some((TRACE_ID.to_string(), "00".to_string()))
+        );
+        assert!(upstream.contains(&context.span_id));
+    }
+
+    #[test]
+    fn test_context_starts_trace_for_invalid_headers() {
+        let context = TraceContext::from_headers(&headers(&[
+            (TRACEPARENT_HEADER, "00-zzz-1-01"),
+            (REQUEST_ID_HEADER, "bad id with spaces"),
+        ]));
+
+        assert_eq!(context.trace_id.len(), 32);
+        assert_eq!(context.span_id.len(), 16);
+        assert_eq!(context.flags, "01");
+        assert_eq!(context.request_id, context.trace_id);
+        assert_ne!(
+            TraceContext::from_headers(&HeaderMap::new()).trace_id,
+            context.trace_id
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_propagate_stores_context_and_returns_request_id() {
+        let app = init_service(App::new().wrap(middleware::from_fn(propagate)).route(
+            "/",
+            web::get().to(|req: HttpRequest| async move {
+                HttpResponse::Ok().body(TraceContext::of(&req).traceparent())
+            }),
+        ))
+        .await;
+
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let req = TestRequest::get()
+            .uri("/")
+            .insert_header((TRACEPARENT_HEADER, traceparent))
+            .to_request();
+        let response = call_service(&app, req).await;
+        let request_id = response.headers().get(REQUEST_ID_HEADER).cloned().unwrap();
+        let body = read_body(response).await;
+
+        assert_eq!(request_id, TRACE_ID);
+        assert!(body.starts_with(format!("00-{}-", TRACE_ID).as_bytes()));
+    }
+}
@@ -0,0 +1,481 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    Error, HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    breaker::{BreakerSettings, CircuitBreaker},
+    request_id::TraceContext,
+};
+
+pub const UNFOLDING_CORE: &str = "unfolding_core";
+pub const AI_ENGINE: &str = "ai_engine";
+
+/// How a route spreads requests over its instances.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Balance {
+    #[default]
+    RoundRobin,
+    /// Instance with the fewest requests in flight; ties go round-robin.
+    LeastOutstanding,
+}
+
+impl FromStr for Balance {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "round_robin" => Ok(Self::RoundRobin),
+            "least_outstanding" => Ok(Self::LeastOutstanding),
+            other => Err(format!(
+                "unknown balancing '{}', expected round_robin or least_outstanding",
+                other
+            )),
+        }
+    }
+}
+
+/// Passive health: an instance is skipped for `duration` after `failures` consecutive
+/// failed requests. `failures == 0` disables ejection.
+#[derive(Debug, Clone, Copy)]
+pub struct Ejection {
+    pub failures: u32,
+    pub duration: Duration,
+}
+
+impl Default for Ejection {
+    fn default() -> Self {
+        Self {
+            failures: 3,
+            duration: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Backend {
+    outstanding: AtomicUsize,
+    failures: AtomicU32,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl Backend {
+    fn is_ejected(&self, now: Instant) -> bool {
+        let ejected_until = self.ejected_until.lock().unwrap_or_else(|e| e.into_inner());
+        ejected_until.is_some_and(|until| until > now)
+    }
+}
+
+/// Load of one instance as shown by `/api/status`.
+#[derive(Debug, Serialize)]
+pub struct BackendStatus {
+    pub url: String,
+    pub outstanding: usize,
+    pub failures: u32,
+    pub ejected: bool,
+}
+
+/// A path prefix of the gateway and the upstream instances it is proxied to.
+#[derive(Debug)]
+pub struct Route {
+    pub prefix: String,
+    /// Upstream name used in logs and error bodies.
+    pub upstream: String,
+    pub targets: Vec<String>,
+    /// Limit for the upstream's response headers; the body may stream for longer.
+    pub timeout: Duration,
+    /// The gateway reports itself unavailable while no instance of a required route is healthy.
+    pub required: bool,
+    /// Probed on every instance by the gateway's `/health`.
+    pub health_path: String,
+    pub balance: Balance,
+    /// Extra attempts on other instances for idempotent requests.
+    pub retries: usize,
+    pub ejection: Ejection,
+    pub breaker: CircuitBreaker,
+    backends: Vec<Backend>,
+    next: AtomicUsize,
+}
+
+impl Route {
+    pub fn new(prefix: &str, upstream: &str, targets: Vec<String>, timeout: Duration) -> Self {
+        let backends = targets.iter().map(|_| Backend::default()).collect();
+        Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            upstream: upstream.to_string(),
+            targets: targets
+                .into_iter()
+                .map(|target| target.trim_end_matches('/').to_string())
+                .collect(),
+            timeout,
+            required: true,
+            health_path: "/health".to_string(),
+            balance: Balance::default(),
+            retries: 1,
+            ejection: Ejection::default(),
+            breaker: CircuitBreaker::new(upstream, BreakerSettings::default()),
+            backends,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_health(mut self, required: bool, health_path: &str) -> Self {
+        self.required = required;
+        self.health_path = health_path.to_string();
+        self
+    }
+
+    pub fn with_balancing(mut self, balance: Balance, retries: usize, ejection: Ejection) -> Self {
+        self.balance = balance;
+        self.retries = retries;
+        self.ejection = ejection;
+        self
+    }
+
+    pub fn with_breaker(mut self, settings: BreakerSettings) -> Self {
+        self.breaker = CircuitBreaker::new(&self.upstream, settings);
+        self
+    }
+
+    /// Reserves the next instance, skipping the indices in `tried` and ejected instances.
+    /// The reservation counts as an outstanding request until the lease is dropped.
+    pub fn acquire(&self, tried: &[usize]) -> Lease<'_> {
+        let now = Instant::now();
+        let mut candidates: Vec<usize> = (0..self.targets.len())
+            .filter(|index| !tried.contains(index))
+            .collect();
+        if candidates.is_empty() {
+            candidates = (0..self.targets.len()).collect();
+        }
+        let available: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&index| !self.backends[index].is_ejected(now))
+            .collect();
+        // Если исключены все экземпляры, пробуем их всё равно: попытка лучше гарантированного 502
+        let pool = if available.is_empty() {
+            candidates
+        } else {
+            available
+        };
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let index = match self.balance {
+            Balance::RoundRobin => pool[start % pool.len()],
+            Balance::LeastOutstanding => (0..pool.len())
+                .map(|offset| pool[(start + offset) % pool.len()])
+                .min_by_key(|&index| self.backends[index].outstanding.load(Ordering::Relaxed))
+                .unwrap_or(pool[0]),
+        };
+        self.backends[index]
+            .outstanding
+            .fetch_add(1, Ordering::Relaxed);
+        Lease { route: self, index }
+    }
+
+    pub fn backends(&self) -> Vec<BackendStatus> {
+        let now = Instant::now();
+        self.targets
+            .iter()
+            .zip(&self.backends)
+            .map(|(url, backend)| BackendStatus {
+                url: url.clone(),
+                outstanding: backend.outstanding.load(Ordering::Relaxed),
+                failures: backend.failures.load(Ordering::Relaxed),
+                ejected: backend.is_ejected(now),
+            })
+            .collect()
+    }
+
+    // Префикс совпадает только по границе сегмента: /api/unfolding не попадает в /api/unfold
+    fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let rest = path.strip_prefix(&self.prefix)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    }
+}
+
+/// One instance of a route reserved for a request.
+pub struct Lease<'a> {
+    route: &'a Route,
+    index: usize,
+}
+
+impl Lease<'_> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn target(&self) -> &str {
+        &self.route.targets[self.index]
+    }
+
+    pub fn succeeded(&self) {
+        self.backend().failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Counts a failed request and ejects the instance once it failed `ejection.failures`
+    /// times in a row.
+    pub fn failed(&self) {
+        let ejection = self.route.ejection;
+        let failures = self.backend().failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if ejection.failures == 0 || failures < ejection.failures {
+            return;
+        }
+        self.backend().failures.store(0, Ordering::Relaxed);
+        *self
+            .backend()
+            .ejected_until
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + ejection.duration);
+        println!(
+            "Ejecting {} from {} for {}s after {} failed requests",
+            self.target(),
+            self.route.upstream,
+            ejection.duration.as_secs(),
+            failures
+        );
+    }
+
+    fn backend(&self) -> &Backend {
+        &self.route.backends[self.index]
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        self.backend().outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Prefix routes, matched longest prefix first.
+#[derive(Debug)]
+pub struct RoutingTable {
+    routes: Vec<Route>,
+}
+
+impl RoutingTable {
+    pub fn new(mut routes: Vec<Route>) -> Result<Self, String> {
+        if let Some(route) = routes.iter().find(|route| route.targets.is_empty()) {
+            return Err(format!("route {} has no upstream URLs", route.prefix));
+        }
+        routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+        Ok(Self { routes })
+    }
+
+    /// Route serving `path` and the path to request upstream.
+    pub fn resolve<'a>(&self, path: &'a str) -> Option<(&Route, &'a str)> {
+        self.routes
+            .iter()
+            .find_map(|route| route.strip(path).map(|rest| (route, rest)))
+    }
+
+    pub fn get(&self, upstream: &str) -> Option<&Route> {
+        self.routes.iter().find(|route| route.upstream == upstream)
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+}
+
+/// Middleware: answers `400 Bad Request` for paths with `.` or `..` segments, plain or
+/// percent-encoded. Prefixes are matched on the raw path, so `/api/unfold/jobs/../x` would
+/// pass the permission of `/api/unfold/jobs` and reach `/x` after the upstream resolves it.
+pub async fn reject_dot_segments(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if !has_dot_segments(req.path()) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    let request_id = TraceContext::of(req.request()).request_id;
+    println!(
+        "[{}] Rejected {} {}: dot segment in the path",
+        request_id,
+        req.method(),
+        req.path()
+    );
+    let response = HttpResponse::BadRequest().json(json!({
+        "error": "Path segments . and .. are not allowed",
+        "code": "INVALID_PATH",
+        "request_id": request_id
+    }));
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+// Декодируем сегмент целиком: %2e%2e и ..%2f тоже превращаются в переход на уровень выше
+pub fn has_dot_segments(path: &str) -> bool {
+    path.split('/').any(|segment| {
+        percent_decode(segment)
+            .split(|&byte| byte == b'/' || byte == b'\\')
+            .any(|part| part == b"." || part == b"..")
+    })
+}
+
+fn percent_decode(segment: &str) -> Vec<u8> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(balance: Balance, ejection: Ejection) -> Route {
+        let targets = ["http://a", "http://b", "http://c"]
+            .map(String::from)
+            .to_vec();
+        Route::new(
+            "/api/unfold",
+            UNFOLDING_CORE,
+            targets,
+            Duration::from_secs(1),
+        )
+        .with_balancing(balance, 1, ejection)
+    }
+
+    fn picks(route: &Route, tried: &[usize], count: usize) -> Vec<usize> {
+        (0..count).map(|_| route.acquire(tried).index()).collect()
+    }
+
+    #[test]
+    fn test_round_robin_cycles_through_instances() {
+        let route = route(Balance::RoundRobin, Ejection::default());
+        assert_eq!(picks(&route, &[], 4), [0, 1, 2, 0]);
+        // Повтор не возвращается на уже опробованный экземпляр
+        assert!(picks(&route, &[1], 4).iter().all(|&index| index != 1));
+        assert_eq!(picks(&route, &[0, 1, 2], 1).len(), 1);
+    }
+
+    #[test]
+    fn test_least_outstanding_prefers_idle_instances() {
+        let route = route(Balance::LeastOutstanding, Ejection::default());
+        let first = route.acquire(&[]);
+        let second = route.acquire(&[]);
+        assert_eq!([first.index(), second.index()], [0, 1]);
+        assert_eq!(route.acquire(&[]).index(), 2);
+
+        let third = route.acquire(&[]);
+        assert_eq!(third.index(), 2);
+        drop(second);
+        assert_eq!(route.acquire(&[]).index(), 1);
+        assert_eq!(route.backends()[0].outstanding, 1);
+    }
+
+    #[test]
+    fn test_failing_instance_is_ejected() {
+        let ejection = Ejection {
+            failures: 2,
+            duration: Duration::from_secs(30),
+        };
+        let route = route(Balance::RoundRobin, ejection);
+        for _ in 0..2 {
+            route.acquire(&[1, 2]).failed();
+        }
+        assert!(route.backends()[0].ejected);
+        assert!(picks(&route, &[], 6).iter().all(|&index| index != 0));
+
+        // Когда исключены все, запрос всё равно уходит на какой-нибудь экземпляр
+        for index in [1, 2] {
+            let tried: Vec<usize> = (0..3).filter(|&other| other != index).collect();
+            route.acquire(&tried).failed();
+            route.acquire(&tried).failed();
+        }
+        assert!(route.backends().iter().all(|backend| backend.ejected));
+        assert_eq!(picks(&route, &[], 3).len(), 3);
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let ejection = Ejection {
+            failures: 2,
+            duration: Duration::from_secs(30),
+        };
+        let route = route(Balance::RoundRobin, ejection);
+        route.acquire(&[1, 2]).failed();
+        route.acquire(&[1, 2]).succeeded();
+        route.acquire(&[1, 2]).failed();
+        assert!(!route.backends()[0].ejected);
+        assert_eq!(route.backends()[0].failures, 1);
+    }
+
+    #[test]
+    fn test_resolve_matches_longest_prefix_on_segment_boundary() {
+        let table = RoutingTable::new(vec![
+            Route::new(
+                "/api",
+                AI_ENGINE,
+                vec!["http://ai".into()],
+                Duration::from_secs(1),
+            ),
+            route(Balance::RoundRobin, Ejection::default()),
+        ])
+        .unwrap();
+        let resolve = |path| {
+            table
+                .resolve(path)
+                .map(|(route, rest)| (route.upstream.as_str(), rest))
+        };
+
+        assert_eq!(resolve("/api/unfold/jobs"), Some((UNFOLDING_CORE, "/jobs")));
+        assert_eq!(resolve("/api/unfolding"), Some((AI_ENGINE, "/unfolding")));
+        assert_eq!(resolve("/health"), None);
+    }
+
+    #[test]
+    fn test_dot_segments_are_detected() {
+        for path in [
+            "/api/unfold/jobs/../../unfold",
+            "/api/unfold/jobs/%2e%2e/%2E%2E/unfold",
+            "/api/unfold/jobs/.%2e/unfold",
+            "/api/unfold/jobs/..%2f..%2funfold",
+            "/api/unfold/./unfold",
+            "/api/unfold/..",
+        ] {
+            assert!(has_dot_segments(path), "{}", path);
+        }
+        for path in [
+            "/api/unfold/jobs/1",
+            "/model.v2.obj",
+            "/api/unfold/...",
+            "/%2e%2e%2e",
+            "/",
+        ] {
+            assert!(!has_dot_segments(path), "{}", path);
+        }
+    }
+}
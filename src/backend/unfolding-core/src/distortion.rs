@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use crate::net::{distance_2d, distance_3d, signed_area_2d};
+use crate::Mesh;
+
+/// How much a flattened face differs from its 3D original.
+///
+/// Paper-thickness insets are deliberate changes and show up here as well.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FaceDistortion {
+    pub face_index: usize,
+    /// Flattened area divided by 3D area.
+    pub area_ratio: f64,
+    /// Largest absolute edge length difference, in model units.
+    pub max_length_error: f64,
+    /// Largest relative edge length change, `|l_2d / l_3d - 1|`.
+    pub max_length_strain: f64,
+}
+
+/// Aggregated distortion over a set of faces (one island or the whole result).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DistortionSummary {
+    pub face_count: usize,
+    pub max_length_error: f64,
+    pub max_length_strain: f64,
+    pub mean_length_strain: f64,
+    pub min_area_ratio: f64,
+    pub max_area_ratio: f64,
+    /// Mean of `|area_ratio - 1|`.
+    pub mean_area_deviation: f64,
+}
+
+impl Default for DistortionSummary {
+    fn default() -> Self {
+        Self {
+            face_count: 0,
+            max_length_error: 0.0,
+            max_length_strain: 0.0,
+            mean_length_strain: 0.0,
+            min_area_ratio: 1.0,
+            max_area_ratio: 1.0,
+            mean_area_deviation: 0.0,
+        }
+    }
+}
+
+impl DistortionSummary {
+    pub fn from_faces<'a>(faces: impl IntoIterator<Item = &'a FaceDistortion>) -> Self {
+        let mut summary = Self::default();
+        let (mut strain_sum, mut deviation_sum) = (0.0, 0.0);
+        let (mut min_area_ratio, mut max_area_ratio) = (f64::INFINITY, f64::NEG_INFINITY);
+
+        for face in faces {
+            summary.face_count += 1;
+            summary.max_length_error = summary.max_length_error.max(face.max_length_error);
+            summary.max_length_strain = summary.max_length_strain.max(face.max_length_strain);
+            min_area_ratio = min_area_ratio.min(face.area_ratio);
+            max_area_ratio = max_area_ratio.max(face.area_ratio);
+            strain_sum += face.max_length_strain;
+            deviation_sum += (face.area_ratio - 1.0).abs();
+        }
+
+        if summary.face_count > 0 {
+            let count = summary.face_count as f64;
+            summary.mean_length_strain = strain_sum / count;
+            summary.mean_area_deviation = deviation_sum / count;
+            summary.min_area_ratio = min_area_ratio;
+            summary.max_area_ratio = max_area_ratio;
+        }
+        summary
+    }
+}
+
+pub(crate) fn face_distortion(mesh: &Mesh, face_index: usize, points: &[[f64; 2]]) -> FaceDistortion {
+    let face = &mesh.faces[face_index];
+    let mut max_length_error: f64 = 0.0;
+    let mut max_length_strain: f64 = 0.0;
+
+    for i in 0..face.len() {
+        let j = (i + 1) % face.len();
+        let original = distance_3d(&mesh.vertices[face[i]], &mesh.vertices[face[j]]);
+        let flattened = distance_2d(&points[i], &points[j]);
+        max_length_error = max_length_error.max((flattened - original).abs());
+        if original > 0.0 {
+            max_length_strain = max_length_strain.max((flattened / original - 1.0).abs());
+        }
+    }
+
+    let original_area = mesh.face_area(face_index);
+    let area_ratio = if original_area > 0.0 {
+        signed_area_2d(points).abs() / original_area
+    } else {
+        1.0
+    };
+
+    FaceDistortion {
+        face_index,
+        area_ratio,
+        max_length_error,
+        max_length_strain,
+    }
+}
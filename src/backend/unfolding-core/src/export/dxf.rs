@@ -0,0 +1,97 @@
+use std::fmt::Write;
+
+use super::{number, SheetLayout};
+use crate::FoldKind;
+
+// Листы в DXF раскладываются слева направо с таким промежутком
+const SHEET_GAP: f64 = 10.0;
+
+const LAYERS: [(&str, u8, &str); 4] = [
+    // имя, цвет ACI, тип линии
+    ("SHEET", 8, "CONTINUOUS"),
+    ("CUT", 7, "CONTINUOUS"),
+    ("MOUNTAIN", 1, "DASHDOT"),
+    ("VALLEY", 5, "DASHED"),
+];
+
+/// Renders sheets as an ASCII DXF (R12) in millimetres, placed side by side.
+///
+/// Every segment is a `LINE` on the `CUT`, `MOUNTAIN` or `VALLEY` layer; sheet borders go
+/// to `SHEET` so cutters can hide them.
+pub fn render(sheets: &[SheetLayout]) -> String {
+    let mut dxf = String::new();
+    pair(&mut dxf, 0, "SECTION");
+    pair(&mut dxf, 2, "HEADER");
+    pair(&mut dxf, 9, "$INSUNITS");
+    pair(&mut dxf, 70, "4");
+    pair(&mut dxf, 0, "ENDSEC");
+
+    pair(&mut dxf, 0, "SECTION");
+    pair(&mut dxf, 2, "TABLES");
+    pair(&mut dxf, 0, "TABLE");
+    pair(&mut dxf, 2, "LTYPE");
+    for (name, pattern) in [("CONTINUOUS", &[][..]), ("DASHED", &[2.0, -1.0][..]), ("DASHDOT", &[4.0, -1.0, 0.0, -1.0][..])] {
+        pair(&mut dxf, 0, "LTYPE");
+        pair(&mut dxf, 2, name);
+        pair(&mut dxf, 70, "0");
+        pair(&mut dxf, 3, "");
+        pair(&mut dxf, 72, "65");
+        pair(&mut dxf, 73, &pattern.len().to_string());
+        pair(&mut dxf, 40, &number(pattern.iter().map(|dash: &f64| dash.abs()).sum()));
+        for dash in pattern {
+            pair(&mut dxf, 49, &number(*dash));
+        }
+    }
+    pair(&mut dxf, 0, "ENDTAB");
+    pair(&mut dxf, 0, "TABLE");
+    pair(&mut dxf, 2, "LAYER");
+    for (name, color, line_type) in LAYERS {
+        pair(&mut dxf, 0, "LAYER");
+        pair(&mut dxf, 2, name);
+        pair(&mut dxf, 70, "0");
+        pair(&mut dxf, 62, &color.to_string());
+        pair(&mut dxf, 6, line_type);
+    }
+    pair(&mut dxf, 0, "ENDTAB");
+    pair(&mut dxf, 0, "ENDSEC");
+
+    pair(&mut dxf, 0, "SECTION");
+    pair(&mut dxf, 2, "ENTITIES");
+    let mut left = 0.0;
+    for sheet in sheets {
+        let (w, h) = (sheet.width, sheet.height);
+        for [a, b] in [[[0.0, 0.0], [w, 0.0]], [[w, 0.0], [w, h]], [[w, h], [0.0, h]], [[0.0, h], [0.0, 0.0]]] {
+            line(&mut dxf, "SHEET", left, a, b);
+        }
+        for [a, b] in &sheet.cuts {
+            line(&mut dxf, "CUT", left, *a, *b);
+        }
+        for (kind, [a, b]) in &sheet.folds {
+            let layer = match kind {
+                FoldKind::Mountain => "MOUNTAIN",
+                FoldKind::Valley => "VALLEY",
+                FoldKind::Flat => continue,
+            };
+            line(&mut dxf, layer, left, *a, *b);
+        }
+        left += sheet.width + SHEET_GAP;
+    }
+    pair(&mut dxf, 0, "ENDSEC");
+    pair(&mut dxf, 0, "EOF");
+    dxf
+}
+
+fn line(dxf: &mut String, layer: &str, left: f64, a: [f64; 2], b: [f64; 2]) {
+    pair(dxf, 0, "LINE");
+    pair(dxf, 8, layer);
+    pair(dxf, 10, &number(a[0] + left));
+    pair(dxf, 20, &number(a[1]));
+    pair(dxf, 30, "0");
+    pair(dxf, 11, &number(b[0] + left));
+    pair(dxf, 21, &number(b[1]));
+    pair(dxf, 31, "0");
+}
+
+fn pair(dxf: &mut String, code: u16, value: &str) {
+    let _ = write!(dxf, "{:>3}\n{}\n", code, value);
+}
@@ -0,0 +1,189 @@
+// Вывод развёрток в файлы для печати и резки
+pub mod dxf;
+pub mod pdf;
+pub mod svg;
+mod zip;
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::topology::{edge_key, EdgeKey};
+use crate::{FoldKind, IslandInfo, Tab};
+
+/// Empty border around the net on every sheet, in millimetres.
+const SHEET_MARGIN: f64 = 10.0;
+
+/// Printable output formats. Net coordinates are treated as millimetres.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Svg,
+    Pdf,
+    Dxf,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Svg, ExportFormat::Pdf, ExportFormat::Dxf];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "svg" => Some(ExportFormat::Svg),
+            "pdf" => Some(ExportFormat::Pdf),
+            "dxf" => Some(ExportFormat::Dxf),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Dxf => "dxf",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "image/svg+xml",
+            ExportFormat::Pdf => "application/pdf",
+            ExportFormat::Dxf => "image/vnd.dxf",
+        }
+    }
+
+    fn render(self, sheets: &[SheetLayout]) -> Vec<u8> {
+        match self {
+            ExportFormat::Svg => svg::render(sheets).into_bytes(),
+            ExportFormat::Pdf => pdf::render(sheets),
+            ExportFormat::Dxf => dxf::render(sheets).into_bytes(),
+        }
+    }
+}
+
+/// Line segments of one printed sheet, in millimetres with the origin at the bottom-left corner.
+#[derive(Debug, Clone)]
+pub struct SheetLayout {
+    pub width: f64,
+    pub height: f64,
+    /// Outlines to cut along: face edges that are not folds and the free edges of tabs.
+    pub cuts: Vec<[[f64; 2]; 2]>,
+    /// Folds inside islands and the bases of tabs (always valley folds).
+    pub folds: Vec<(FoldKind, [[f64; 2]; 2])>,
+}
+
+/// Lays out every island on its own sheet, in sheet order.
+///
+/// Sheets are `sheet_size` (turned to landscape when that fits better) and grow when an
+/// island does not fit, so nothing is clipped.
+pub fn layout_sheets(islands: &[IslandInfo], sheet_size: [f64; 2]) -> Vec<SheetLayout> {
+    let mut ordered: Vec<&IslandInfo> = islands.iter().filter(|island| !island.sheet_indices.is_empty()).collect();
+    ordered.sort_by_key(|island| island.sheet_indices[0]);
+    ordered.into_iter().map(|island| layout_island(island, sheet_size)).collect()
+}
+
+/// All sheets in one document: pages of a PDF, stacked groups of an SVG, side-by-side blocks of a DXF.
+pub fn export_sheets(islands: &[IslandInfo], sheet_size: [f64; 2], format: ExportFormat) -> Vec<u8> {
+    format.render(&layout_sheets(islands, sheet_size))
+}
+
+/// ZIP archive with one `sheet-NN.<ext>` file per sheet.
+pub fn export_sheet_archive(islands: &[IslandInfo], sheet_size: [f64; 2], format: ExportFormat) -> Vec<u8> {
+    let files: Vec<(String, Vec<u8>)> = layout_sheets(islands, sheet_size)
+        .iter()
+        .enumerate()
+        .map(|(index, sheet)| {
+            (
+                format!("sheet-{:02}.{}", index + 1, format.extension()),
+                format.render(std::slice::from_ref(sheet)),
+            )
+        })
+        .collect();
+    zip::write_stored(&files)
+}
+
+fn layout_island(island: &IslandInfo, sheet_size: [f64; 2]) -> SheetLayout {
+    let net = &island.net;
+    let fold_edges: HashSet<(usize, EdgeKey)> = net
+        .folds
+        .iter()
+        .flat_map(|fold| {
+            let key = edge_key(fold.vertices[0], fold.vertices[1]);
+            fold.faces.map(|face| (face, key))
+        })
+        .collect();
+    let tabs: HashMap<(usize, EdgeKey), &Tab> = net
+        .tabs
+        .iter()
+        .map(|tab| ((tab.face_index, edge_key(tab.vertices[0], tab.vertices[1])), tab))
+        .collect();
+
+    let mut cuts = Vec::new();
+    let mut folds: Vec<(FoldKind, [[f64; 2]; 2])> = net
+        .folds
+        .iter()
+        .filter(|fold| fold.kind != FoldKind::Flat)
+        .map(|fold| (fold.kind, fold.points))
+        .collect();
+
+    for face in &net.faces {
+        let n = face.points.len();
+        for i in 0..n {
+            let segment = [face.points[i], face.points[(i + 1) % n]];
+            let key = edge_key(face.vertices[i], face.vertices[(i + 1) % n]);
+            if fold_edges.contains(&(face.face_index, key)) {
+                continue;
+            }
+            match tabs.get(&(face.face_index, key)) {
+                // Основание клапана сгибается внутрь, остальной контур режется
+                Some(tab) => {
+                    folds.push((FoldKind::Valley, segment));
+                    for j in 1..tab.points.len() {
+                        cuts.push([tab.points[j], tab.points[(j + 1) % tab.points.len()]]);
+                    }
+                }
+                None => cuts.push(segment),
+            }
+        }
+    }
+
+    let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+    for point in cuts.iter().chain(folds.iter().map(|(_, segment)| segment)).flatten() {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(point[axis]);
+            max[axis] = max[axis].max(point[axis]);
+        }
+    }
+    if cuts.is_empty() && folds.is_empty() {
+        (min, max) = ([0.0; 2], [0.0; 2]);
+    }
+
+    let needed = [max[0] - min[0] + 2.0 * SHEET_MARGIN, max[1] - min[1] + 2.0 * SHEET_MARGIN];
+    let (short, long) = (sheet_size[0].min(sheet_size[1]), sheet_size[0].max(sheet_size[1]));
+    let [width, height] = if needed[0] > needed[1] { [long, short] } else { [short, long] };
+    let (width, height) = (width.max(needed[0]), height.max(needed[1]));
+
+    // Центрируем остров на листе
+    let shift = [
+        (width - (max[0] - min[0])) / 2.0 - min[0],
+        (height - (max[1] - min[1])) / 2.0 - min[1],
+    ];
+    let moved = |[a, b]: [[f64; 2]; 2]| [[a[0] + shift[0], a[1] + shift[1]], [b[0] + shift[0], b[1] + shift[1]]];
+
+    SheetLayout {
+        width,
+        height,
+        cuts: cuts.into_iter().map(moved).collect(),
+        folds: folds.into_iter().map(|(kind, segment)| (kind, moved(segment))).collect(),
+    }
+}
+
+/// Formats a coordinate with a fixed precision and without a trailing `-0`.
+pub(crate) fn number(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
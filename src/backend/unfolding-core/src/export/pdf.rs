@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use super::{number, SheetLayout};
+use crate::FoldKind;
+
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+
+/// Renders sheets as an uncompressed PDF 1.4 with one page per sheet.
+///
+/// Cuts are solid black, mountain folds red dash-dot, valley folds blue dashed.
+pub fn render(sheets: &[SheetLayout]) -> Vec<u8> {
+    // Объекты: 1 — каталог, 2 — дерево страниц, далее по два на лист (страница и её содержимое)
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        Vec::new(),
+    ];
+    let mut kids = Vec::new();
+
+    for sheet in sheets {
+        let page_id = objects.len() + 1;
+        let content_id = page_id + 1;
+        kids.push(format!("{} 0 R", page_id));
+
+        let content = page_content(sheet);
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R >>",
+                number(sheet.width * POINTS_PER_MM),
+                number(sheet.height * POINTS_PER_MM),
+                content_id
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend_from_slice(content.as_bytes());
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+    objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), sheets.len()).into_bytes();
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    pdf.extend_from_slice(trailer.as_bytes());
+    pdf
+}
+
+fn page_content(sheet: &SheetLayout) -> String {
+    let mut content = String::new();
+    // Рисуем в миллиметрах, масштаб задаём один раз
+    let _ = writeln!(content, "{} 0 0 {} 0 0 cm", number(POINTS_PER_MM), number(POINTS_PER_MM));
+    content.push_str("0.3 w 1 J\n");
+
+    content.push_str("0 0 0 RG [] 0 d\n");
+    stroke(&mut content, sheet.cuts.iter());
+    content.push_str("0.85 0 0 RG [4 1 1 1] 0 d\n");
+    stroke(&mut content, folds_of(sheet, FoldKind::Mountain));
+    content.push_str("0 0 0.85 RG [2 1] 0 d\n");
+    stroke(&mut content, folds_of(sheet, FoldKind::Valley));
+    content
+}
+
+fn stroke<'a>(content: &mut String, segments: impl Iterator<Item = &'a [[f64; 2]; 2]>) {
+    for [a, b] in segments {
+        let _ = writeln!(
+            content,
+            "{} {} m {} {} l",
+            number(a[0]),
+            number(a[1]),
+            number(b[0]),
+            number(b[1])
+        );
+    }
+    content.push_str("S\n");
+}
+
+fn folds_of(sheet: &SheetLayout, kind: FoldKind) -> impl Iterator<Item = &[[f64; 2]; 2]> {
+    sheet.folds.iter().filter(move |(fold, _)| *fold == kind).map(|(_, segment)| segment)
+}
@@ -0,0 +1,69 @@
+use std::fmt::Write;
+
+use super::{number, SheetLayout};
+use crate::FoldKind;
+
+// Промежуток между листами, когда их несколько в одном SVG
+const SHEET_GAP: f64 = 10.0;
+
+const STYLE: &str = ".sheet{fill:none;stroke:#ccc;stroke-width:0.2}\
+.cut{fill:none;stroke:#000;stroke-width:0.3}\
+.mountain{stroke:#d00;stroke-width:0.3;stroke-dasharray:4 1 1 1}\
+.valley{stroke:#00d;stroke-width:0.3;stroke-dasharray:2 1}";
+
+/// Renders sheets as one SVG in millimetres; several sheets are stacked top to bottom,
+/// each in its own `<g id="sheet-N">` with a light outline of the sheet border.
+pub fn render(sheets: &[SheetLayout]) -> String {
+    let width = sheets.iter().map(|sheet| sheet.width).fold(0.0, f64::max);
+    let height = sheets.iter().map(|sheet| sheet.height).sum::<f64>() + SHEET_GAP * sheets.len().saturating_sub(1) as f64;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <style>{STYLE}</style>\n",
+        w = number(width),
+        h = number(height),
+    );
+
+    let mut top = 0.0;
+    for (index, sheet) in sheets.iter().enumerate() {
+        let _ = writeln!(svg, "<g id=\"sheet-{}\" transform=\"translate(0 {})\">", index + 1, number(top));
+        let _ = writeln!(
+            svg,
+            "<rect class=\"sheet\" width=\"{}\" height=\"{}\"/>",
+            number(sheet.width),
+            number(sheet.height)
+        );
+
+        // В SVG ось Y направлена вниз, переворачиваем, чтобы не получить зеркальную развёртку
+        let flip = |point: [f64; 2]| (number(point[0]), number(sheet.height - point[1]));
+        let mut cut_path = String::new();
+        for [a, b] in &sheet.cuts {
+            let (a, b) = (flip(*a), flip(*b));
+            let _ = write!(cut_path, "M{} {}L{} {}", a.0, a.1, b.0, b.1);
+        }
+        if !cut_path.is_empty() {
+            let _ = writeln!(svg, "<path class=\"cut\" d=\"{}\"/>", cut_path);
+        }
+        for (kind, [a, b]) in &sheet.folds {
+            let class = match kind {
+                FoldKind::Mountain => "mountain",
+                FoldKind::Valley => "valley",
+                FoldKind::Flat => continue,
+            };
+            let (a, b) = (flip(*a), flip(*b));
+            let _ = writeln!(
+                svg,
+                "<line class=\"{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+                class, a.0, a.1, b.0, b.1
+            );
+        }
+        svg.push_str("</g>\n");
+        top += sheet.height + SHEET_GAP;
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
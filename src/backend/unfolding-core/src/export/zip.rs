@@ -0,0 +1,69 @@
+// Минимальный ZIP без сжатия (метод 0): листов немного, а SVG/DXF и так хорошо жмутся транспортом
+
+// 1980-01-01 00:00 в формате DOS
+const DOS_DATE: u16 = 0x21;
+const DOS_TIME: u16 = 0;
+
+pub(crate) fn write_stored(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut central = Vec::new();
+
+    for (name, data) in files {
+        let offset = archive.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header_fields(&mut archive, crc, size, name);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // создано версией 2.0
+        header_fields(&mut central, crc, size, name);
+        central.extend_from_slice(&0u16.to_le_bytes()); // комментарий
+        central.extend_from_slice(&0u16.to_le_bytes()); // номер диска
+        central.extend_from_slice(&0u16.to_le_bytes()); // внутренние атрибуты
+        central.extend_from_slice(&0u32.to_le_bytes()); // внешние атрибуты
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = archive.len() as u32;
+    let central_size = central.len() as u32;
+    archive.extend_from_slice(&central);
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&central_size.to_le_bytes());
+    archive.extend_from_slice(&central_offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    archive
+}
+
+// Общая часть локального и центрального заголовков, начиная с "version needed"
+fn header_fields(out: &mut Vec<u8>, crc: u32, size: u32, name: &str) {
+    out.extend_from_slice(&20u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // флаги
+    out.extend_from_slice(&0u16.to_le_bytes()); // метод: без сжатия
+    out.extend_from_slice(&DOS_TIME.to_le_bytes());
+    out.extend_from_slice(&DOS_DATE.to_le_bytes());
+    out.extend_from_slice(&crc.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // дополнительное поле
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,382 @@
+use serde_json::Value;
+
+use super::{FaceCollector, MeshLimits, VertexWelder};
+use crate::{Mesh, Result, UnfoldingError, Vector3};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+const MODE_TRIANGLES: u64 = 4;
+const MODE_TRIANGLE_STRIP: u64 = 5;
+const MODE_TRIANGLE_FAN: u64 = 6;
+
+/// Mesh placements a scene may have; each copies the mesh's faces.
+const MAX_MESH_INSTANCES: usize = 10_000;
+
+// Матрица 4x4 по столбцам, как в glTF
+type Matrix = [f64; 16];
+
+const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0, //
+    0.0, 1.0, 0.0, 0.0, //
+    0.0, 0.0, 1.0, 0.0, //
+    0.0, 0.0, 0.0, 1.0,
+];
+
+/// Parses glTF 2.0, either as `.gltf` JSON with embedded `data:` buffers or as binary `.glb`.
+///
+/// Triangle primitives of every mesh in the default scene are imported with their node
+/// transforms applied; each glTF mesh becomes a part. External buffer files are not supported.
+pub fn parse_gltf(data: &[u8], limits: MeshLimits) -> Result<Mesh> {
+    let (document, binary) = if data.starts_with(GLB_MAGIC) {
+        split_glb(data)?
+    } else {
+        let document: Value =
+            serde_json::from_slice(data).map_err(|e| gltf_error(&format!("invalid JSON: {}", e)))?;
+        (document, None)
+    };
+
+    let buffers = load_buffers(&document, binary)?;
+    let gltf = Document { json: &document, buffers };
+
+    let mut welder = VertexWelder::new(limits);
+    let mut faces = FaceCollector::new(limits);
+    let mut mesh_parts = vec![None; array(&document, "meshes").len()];
+
+    for (mesh_index, transform) in gltf.mesh_instances()? {
+        let mesh = array(&document, "meshes")
+            .get(mesh_index)
+            .ok_or_else(|| gltf_error(&format!("mesh {} does not exist", mesh_index)))?;
+        let part = *mesh_parts[mesh_index].get_or_insert_with(|| {
+            let name = mesh["name"].as_str().map_or_else(|| format!("mesh {}", mesh_index), str::to_string);
+            faces.add_part(name)
+        });
+
+        for primitive in array(mesh, "primitives") {
+            let mode = primitive["mode"].as_u64().unwrap_or(MODE_TRIANGLES);
+            if !matches!(mode, MODE_TRIANGLES | MODE_TRIANGLE_STRIP | MODE_TRIANGLE_FAN) {
+                // Точки и линии развернуть нельзя
+                continue;
+            }
+            let position = primitive["attributes"]["POSITION"]
+                .as_u64()
+                .ok_or_else(|| gltf_error("primitive has no POSITION attribute"))?;
+            let positions = gltf.read_accessor(position as usize, 3)?;
+            let vertex_ids: Vec<usize> = positions
+                .chunks_exact(3)
+                .map(|p| welder.insert(transform_point(&transform, [p[0], p[1], p[2]])))
+                .collect::<Result<_>>()?;
+
+            let indices: Vec<usize> = match primitive["indices"].as_u64() {
+                Some(accessor) => gltf.read_accessor(accessor as usize, 1)?.into_iter().map(|i| i as usize).collect(),
+                None => (0..vertex_ids.len()).collect(),
+            };
+            for triangle in triangles(&indices, mode) {
+                let face = triangle
+                    .iter()
+                    .map(|&i| vertex_ids.get(i).copied())
+                    .collect::<Option<Vec<usize>>>()
+                    .ok_or_else(|| gltf_error("index out of range of POSITION accessor"))?;
+                faces.push(face, part)?;
+            }
+        }
+    }
+
+    Ok(faces.into_mesh(welder.into_vertices()))
+}
+
+fn split_glb(data: &[u8]) -> Result<(Value, Option<&[u8]>)> {
+    let read_u32 = |offset: usize| -> Result<u32> {
+        let bytes = data.get(offset..offset + 4).ok_or_else(|| gltf_error("truncated GLB"))?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let mut document = None;
+    let mut binary = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let length = read_u32(offset)? as usize;
+        let kind = read_u32(offset + 4)?;
+        let chunk = data
+            .get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| gltf_error("truncated GLB chunk"))?;
+        match kind {
+            CHUNK_JSON => {
+                document = Some(
+                    serde_json::from_slice(chunk).map_err(|e| gltf_error(&format!("invalid JSON chunk: {}", e)))?,
+                )
+            }
+            CHUNK_BIN if binary.is_none() => binary = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+
+    Ok((document.ok_or_else(|| gltf_error("GLB has no JSON chunk"))?, binary))
+}
+
+fn load_buffers(document: &Value, binary: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+    array(document, "buffers")
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| match buffer["uri"].as_str() {
+            Some(uri) => {
+                let (_, payload) = uri
+                    .strip_prefix("data:")
+                    .and_then(|rest| rest.split_once(";base64,"))
+                    .ok_or_else(|| gltf_error(&format!("buffer {} references an external file", index)))?;
+                decode_base64(payload).ok_or_else(|| gltf_error(&format!("buffer {} has invalid base64", index)))
+            }
+            // Буфер без uri в GLB — это BIN-чанк
+            None if index == 0 => binary
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| gltf_error("buffer 0 has no uri and there is no GLB binary chunk")),
+            None => Err(gltf_error(&format!("buffer {} has no uri", index))),
+        })
+        .collect()
+}
+
+struct Document<'a> {
+    json: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Document<'_> {
+    /// Every mesh referenced from the default scene together with its world transform.
+    fn mesh_instances(&self) -> Result<Vec<(usize, Matrix)>> {
+        let nodes = array(self.json, "nodes");
+        if nodes.is_empty() {
+            // Без узлов берём все сетки как есть
+            return Ok((0..array(self.json, "meshes").len()).map(|mesh| (mesh, IDENTITY)).collect());
+        }
+
+        let scene_index = self.json["scene"].as_u64().unwrap_or(0) as usize;
+        let roots: Vec<usize> = match array(self.json, "scenes").get(scene_index) {
+            Some(scene) => indices(&scene["nodes"]),
+            None => {
+                let children: Vec<usize> = nodes.iter().flat_map(|node| indices(&node["children"])).collect();
+                (0..nodes.len()).filter(|node| !children.contains(node)).collect()
+            }
+        };
+
+        let mut instances = Vec::new();
+        let mut visited = vec![false; nodes.len()];
+        let mut stack: Vec<(usize, Matrix)> = roots.into_iter().map(|node| (node, IDENTITY)).collect();
+        while let Some((node_index, parent)) = stack.pop() {
+            let node = nodes
+                .get(node_index)
+                .ok_or_else(|| gltf_error(&format!("node {} does not exist", node_index)))?;
+            // По спецификации иерархия — дерево. Повторный заход означает цикл или общего потомка,
+            // а обход с повторами (children: [n, n] на каждом уровне) размножал бы узлы экспоненциально
+            if std::mem::replace(&mut visited[node_index], true) {
+                return Err(gltf_error(&format!(
+                    "node {} has more than one parent or is part of a cycle",
+                    node_index
+                )));
+            }
+            let world = multiply(&parent, &local_transform(node));
+            if let Some(mesh) = node["mesh"].as_u64() {
+                if instances.len() == MAX_MESH_INSTANCES {
+                    return Err(gltf_error(&format!("scene places more than {} meshes", MAX_MESH_INSTANCES)));
+                }
+                instances.push((mesh as usize, world));
+            }
+            stack.extend(indices(&node["children"]).into_iter().map(|child| (child, world)));
+        }
+        Ok(instances)
+    }
+
+    /// Reads an accessor as floats; `components` is 1 for SCALAR and 3 for VEC3.
+    fn read_accessor(&self, index: usize, components: usize) -> Result<Vec<f64>> {
+        let accessor = array(self.json, "accessors")
+            .get(index)
+            .ok_or_else(|| gltf_error(&format!("accessor {} does not exist", index)))?;
+        let expected_type = if components == 3 { "VEC3" } else { "SCALAR" };
+        if accessor["type"].as_str() != Some(expected_type) {
+            return Err(gltf_error(&format!("accessor {} must be {}", index, expected_type)));
+        }
+        if !accessor["sparse"].is_null() {
+            return Err(gltf_error(&format!("sparse accessor {} is not supported", index)));
+        }
+
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(gltf_error(&format!("accessor {} has unknown componentType {}", index, component_type))),
+        };
+        let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+        let view_index = accessor["bufferView"]
+            .as_u64()
+            .ok_or_else(|| gltf_error(&format!("accessor {} has no bufferView", index)))?;
+        let view = array(self.json, "bufferViews")
+            .get(view_index as usize)
+            .ok_or_else(|| gltf_error(&format!("bufferView {} does not exist", view_index)))?;
+        let buffer = self
+            .buffers
+            .get(view["buffer"].as_u64().unwrap_or(0) as usize)
+            .ok_or_else(|| gltf_error(&format!("bufferView {} references a missing buffer", view_index)))?;
+
+        let view_offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let view_length = view["byteLength"].as_u64().unwrap_or(0) as usize;
+        let view_data = buffer
+            .get(view_offset..view_offset.saturating_add(view_length))
+            .ok_or_else(|| gltf_error(&format!("bufferView {} is out of range", view_index)))?;
+        let element_size = component_size * components;
+        let stride = view["byteStride"].as_u64().map_or(element_size, |stride| stride as usize);
+        let start = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        // Шаг меньше элемента (в том числе 0) читал бы одни и те же байты сколько угодно раз,
+        // поэтому до цикла проверяем и шаг, и то, что последний элемент помещается в view
+        if stride < element_size {
+            return Err(gltf_error(&format!(
+                "bufferView {} has byteStride {} but accessor {} elements take {} bytes",
+                view_index, stride, index, element_size
+            )));
+        }
+        let end = match count.checked_sub(1) {
+            None => start,
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|offset| offset.checked_add(start))
+                .and_then(|offset| offset.checked_add(element_size))
+                .unwrap_or(usize::MAX),
+        };
+        if end > view_data.len() {
+            return Err(gltf_error(&format!("accessor {} is out of range", index)));
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            let offset = start + element * stride;
+            let bytes = &view_data[offset..offset + element_size];
+            for component in bytes.chunks_exact(component_size) {
+                values.push(match component_type {
+                    5120 => component[0] as i8 as f64,
+                    5121 => component[0] as f64,
+                    5122 => i16::from_le_bytes([component[0], component[1]]) as f64,
+                    5123 => u16::from_le_bytes([component[0], component[1]]) as f64,
+                    5125 => u32::from_le_bytes([component[0], component[1], component[2], component[3]]) as f64,
+                    _ => f32::from_le_bytes([component[0], component[1], component[2], component[3]]) as f64,
+                });
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn triangles(indices: &[usize], mode: u64) -> Vec<[usize; 3]> {
+    match mode {
+        MODE_TRIANGLE_STRIP => (2..indices.len())
+            .map(|i| {
+                // Чётные треугольники полосы сохраняют ориентацию, нечётные переворачиваем
+                if i % 2 == 0 {
+                    [indices[i - 2], indices[i - 1], indices[i]]
+                } else {
+                    [indices[i - 1], indices[i - 2], indices[i]]
+                }
+            })
+            .collect(),
+        MODE_TRIANGLE_FAN => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+        _ => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+    }
+}
+
+fn local_transform(node: &Value) -> Matrix {
+    if let Some(matrix) = node["matrix"].as_array().filter(|m| m.len() == 16) {
+        let mut result = IDENTITY;
+        for (value, slot) in matrix.iter().zip(result.iter_mut()) {
+            *slot = value.as_f64().unwrap_or(0.0);
+        }
+        return result;
+    }
+
+    let vector = |key: &str, default: &[f64]| -> Vec<f64> {
+        node[key]
+            .as_array()
+            .map(|values| values.iter().map(|v| v.as_f64().unwrap_or(0.0)).collect())
+            .filter(|values: &Vec<f64>| values.len() == default.len())
+            .unwrap_or_else(|| default.to_vec())
+    };
+    let t = vector("translation", &[0.0, 0.0, 0.0]);
+    let [x, y, z, w] = <[f64; 4]>::try_from(vector("rotation", &[0.0, 0.0, 0.0, 1.0])).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let s = vector("scale", &[1.0, 1.0, 1.0]);
+
+    // T * R * S, по столбцам
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * s[0],
+        (2.0 * (x * y + z * w)) * s[0],
+        (2.0 * (x * z - y * w)) * s[0],
+        0.0,
+        (2.0 * (x * y - z * w)) * s[1],
+        (1.0 - 2.0 * (x * x + z * z)) * s[1],
+        (2.0 * (y * z + x * w)) * s[1],
+        0.0,
+        (2.0 * (x * z + y * w)) * s[2],
+        (2.0 * (y * z - x * w)) * s[2],
+        (1.0 - 2.0 * (x * x + y * y)) * s[2],
+        0.0,
+        t[0],
+        t[1],
+        t[2],
+        1.0,
+    ]
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [0.0; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            result[column * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[column * 4 + k]).sum();
+        }
+    }
+    result
+}
+
+fn transform_point(m: &Matrix, p: [f64; 3]) -> Vector3 {
+    Vector3 {
+        x: m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12],
+        y: m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13],
+        z: m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14],
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value[key].as_array().map_or(&[], Vec::as_slice)
+}
+
+fn indices(value: &Value) -> Vec<usize> {
+    value
+        .as_array()
+        .map(|values| values.iter().filter_map(Value::as_u64).map(|i| i as usize).collect())
+        .unwrap_or_default()
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    for byte in input.bytes().filter(|b| !b.is_ascii_whitespace()) {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        accumulator = ((accumulator << 6) | value as u32) & 0xFFFF;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((accumulator >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+fn gltf_error(message: &str) -> UnfoldingError {
+    UnfoldingError::InvalidMesh(format!("glTF: {}", message))
+}
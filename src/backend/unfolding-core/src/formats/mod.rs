@@ -0,0 +1,177 @@
+// Загрузчики сеток из файловых форматов
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Mesh, Result, UnfoldingError, Vector3};
+
+pub use obj::ObjGrouping;
+
+/// Mesh file formats that can be imported. `Gltf` covers both `.gltf` and `.glb`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MeshFormat {
+    Obj,
+    Stl,
+    Ply,
+    Gltf,
+}
+
+impl MeshFormat {
+    pub const ALL: [MeshFormat; 4] = [MeshFormat::Obj, MeshFormat::Stl, MeshFormat::Ply, MeshFormat::Gltf];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "obj",
+            MeshFormat::Stl => "stl",
+            MeshFormat::Ply => "ply",
+            MeshFormat::Gltf => "gltf",
+        }
+    }
+
+    /// Format named by a short name or file extension (`"stl"`, `"glb"`, ...), case-insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "obj" => Some(MeshFormat::Obj),
+            "stl" => Some(MeshFormat::Stl),
+            "ply" => Some(MeshFormat::Ply),
+            "gltf" | "glb" => Some(MeshFormat::Gltf),
+            _ => None,
+        }
+    }
+
+    /// Format implied by the extension of `file_name`.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let (_, extension) = file_name.rsplit_once('.')?;
+        Self::from_name(extension)
+    }
+
+    /// Format implied by a MIME type; parameters such as `; charset=utf-8` are ignored.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "model/obj" | "text/x-obj" | "application/x-tgif" => Some(MeshFormat::Obj),
+            "model/stl" | "model/x.stl-ascii" | "model/x.stl-binary" | "application/sla"
+            | "application/vnd.ms-pki.stl" => Some(MeshFormat::Stl),
+            "model/ply" | "application/ply" | "text/plain+ply" => Some(MeshFormat::Ply),
+            "model/gltf+json" | "model/gltf-binary" => Some(MeshFormat::Gltf),
+            _ => None,
+        }
+    }
+
+    /// Parses file contents. `grouping` only matters for OBJ; the other formats
+    /// take their parts from STL solids and glTF meshes.
+    pub fn parse(self, data: &[u8], grouping: ObjGrouping, limits: MeshLimits) -> Result<Mesh> {
+        match self {
+            MeshFormat::Obj => {
+                let source = std::str::from_utf8(data)
+                    .map_err(|e| UnfoldingError::InvalidMesh(format!("OBJ is not valid UTF-8: {}", e)))?;
+                obj::parse_obj(source, grouping, limits)
+            }
+            MeshFormat::Stl => stl::parse_stl(data, limits),
+            MeshFormat::Ply => ply::parse_ply(data, limits),
+            MeshFormat::Gltf => gltf::parse_gltf(data, limits),
+        }
+    }
+}
+
+/// Largest mesh a parser will build; loaders stop with `MeshTooLarge` as soon as a file
+/// goes over either count instead of reading it to the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshLimits {
+    pub max_vertices: usize,
+    pub max_faces: usize,
+}
+
+impl MeshLimits {
+    pub const NONE: MeshLimits = MeshLimits { max_vertices: usize::MAX, max_faces: usize::MAX };
+
+    pub(crate) fn check_vertices(self, count: usize) -> Result<()> {
+        check_count("vertices", count, self.max_vertices)
+    }
+
+    pub(crate) fn check_faces(self, count: usize) -> Result<()> {
+        check_count("faces", count, self.max_faces)
+    }
+}
+
+fn check_count(what: &str, count: usize, limit: usize) -> Result<()> {
+    if count > limit {
+        return Err(UnfoldingError::MeshTooLarge(format!("more than {} {}", limit, what)));
+    }
+    Ok(())
+}
+
+// STL и glTF хранят вершины отдельно для каждого треугольника или шва UV,
+// поэтому совпадающие позиции склеиваем, иначе у сетки не будет общих рёбер
+pub(crate) struct VertexWelder {
+    vertices: Vec<Vector3>,
+    index: HashMap<[u64; 3], usize>,
+    limits: MeshLimits,
+}
+
+impl VertexWelder {
+    pub fn new(limits: MeshLimits) -> Self {
+        Self { vertices: Vec::new(), index: HashMap::new(), limits }
+    }
+
+    pub fn insert(&mut self, vertex: Vector3) -> Result<usize> {
+        // -0.0 и 0.0 должны давать одну вершину
+        let key = [vertex.x + 0.0, vertex.y + 0.0, vertex.z + 0.0].map(f64::to_bits);
+        if let Some(&index) = self.index.get(&key) {
+            return Ok(index);
+        }
+        self.limits.check_vertices(self.vertices.len() + 1)?;
+        self.vertices.push(vertex);
+        self.index.insert(key, self.vertices.len() - 1);
+        Ok(self.vertices.len() - 1)
+    }
+
+    pub fn into_vertices(self) -> Vec<Vector3> {
+        self.vertices
+    }
+}
+
+/// Collects faces with their parts, dropping faces that collapse after welding.
+pub(crate) struct FaceCollector {
+    pub faces: Vec<Vec<usize>>,
+    pub face_parts: Vec<usize>,
+    pub part_names: Vec<String>,
+    limits: MeshLimits,
+}
+
+impl FaceCollector {
+    pub fn new(limits: MeshLimits) -> Self {
+        Self { faces: Vec::new(), face_parts: Vec::new(), part_names: Vec::new(), limits }
+    }
+
+    pub fn add_part(&mut self, name: String) -> usize {
+        self.part_names.push(name);
+        self.part_names.len() - 1
+    }
+
+    pub fn push(&mut self, face: Vec<usize>, part: usize) -> Result<()> {
+        let distinct = face.iter().enumerate().all(|(i, a)| !face[..i].contains(a));
+        if distinct {
+            self.limits.check_faces(self.faces.len() + 1)?;
+            self.faces.push(face);
+            self.face_parts.push(part);
+        }
+        Ok(())
+    }
+
+    /// Builds the mesh; part IDs are only attached when there is more than one part.
+    pub fn into_mesh(self, vertices: Vec<Vector3>) -> Mesh {
+        let mut mesh = Mesh::new(vertices, self.faces);
+        if self.part_names.len() > 1 {
+            mesh.face_parts = Some(self.face_parts);
+            mesh.part_names = self.part_names;
+        }
+        mesh
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::MeshLimits;
+use crate::{Mesh, Result, UnfoldingError, Vector3};
+
+/// Which OBJ statement defines the part a face belongs to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ObjGrouping {
+    /// Ignore groups and materials, every face ends up in a single part.
+    None,
+    /// `g` and `o` statements.
+    #[default]
+    Groups,
+    /// `usemtl` statements.
+    Materials,
+}
+
+/// Parses Wavefront OBJ text into a `Mesh`, assigning part IDs according to `grouping`.
+///
+/// Only `v`, `f`, `g`, `o` and `usemtl` are interpreted; texture coordinates,
+/// normals and other statements are skipped.
+pub fn parse_obj(source: &str, grouping: ObjGrouping, limits: MeshLimits) -> Result<Mesh> {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    let mut face_parts = Vec::new();
+    let mut part_names: Vec<String> = Vec::new();
+    let mut part_ids: HashMap<String, usize> = HashMap::new();
+    let mut current_part = "default".to_string();
+
+    for (line_number, raw_line) in source.lines().enumerate() {
+        let line = raw_line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => {
+                let coords: Vec<f64> = tokens
+                    .take(3)
+                    .map(|token| token.parse::<f64>())
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|e| obj_error(line_number, &format!("bad vertex coordinate: {}", e)))?;
+                if coords.len() != 3 {
+                    return Err(obj_error(line_number, "vertex needs 3 coordinates"));
+                }
+                limits.check_vertices(vertices.len() + 1)?;
+                vertices.push(Vector3 { x: coords[0], y: coords[1], z: coords[2] });
+            }
+            "f" => {
+                let face = tokens
+                    .map(|token| resolve_index(token, vertices.len(), line_number))
+                    .collect::<Result<Vec<usize>>>()?;
+                limits.check_faces(faces.len() + 1)?;
+                faces.push(face);
+
+                let part = *part_ids.entry(current_part.clone()).or_insert_with(|| {
+                    part_names.push(current_part.clone());
+                    part_names.len() - 1
+                });
+                face_parts.push(part);
+            }
+            "g" | "o" if grouping == ObjGrouping::Groups => {
+                current_part = part_name(tokens);
+            }
+            "usemtl" if grouping == ObjGrouping::Materials => {
+                current_part = part_name(tokens);
+            }
+            _ => {}
+        }
+    }
+
+    let mut mesh = Mesh::new(vertices, faces);
+    if grouping != ObjGrouping::None {
+        mesh.face_parts = Some(face_parts);
+        mesh.part_names = part_names;
+    }
+    Ok(mesh)
+}
+
+fn part_name<'a>(tokens: impl Iterator<Item = &'a str>) -> String {
+    let name = tokens.collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        "default".to_string()
+    } else {
+        name
+    }
+}
+
+// OBJ индексы начинаются с 1, отрицательные отсчитываются от конца списка вершин
+fn resolve_index(token: &str, vertex_count: usize, line_number: usize) -> Result<usize> {
+    let index_part = token.split('/').next().unwrap_or("");
+    let index: i64 = index_part
+        .parse()
+        .map_err(|_| obj_error(line_number, &format!("bad face index '{}'", token)))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        -1
+    };
+
+    if resolved < 0 || resolved as usize >= vertex_count {
+        return Err(obj_error(line_number, &format!("face index {} out of range", index)));
+    }
+    Ok(resolved as usize)
+}
+
+fn obj_error(line_number: usize, message: &str) -> UnfoldingError {
+    UnfoldingError::InvalidMesh(format!("OBJ line {}: {}", line_number + 1, message))
+}
@@ -0,0 +1,272 @@
+use super::MeshLimits;
+use crate::{Mesh, Result, UnfoldingError, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return Err(ply_error(&format!("unknown property type '{}'", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar { name: String, ty: ScalarType },
+    List { name: String, count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Parses ASCII and binary (little or big endian) PLY.
+///
+/// Only `x`/`y`/`z` of the `vertex` element and the `vertex_indices` (or `vertex_index`)
+/// list of the `face` element are used; other elements and properties are skipped.
+pub fn parse_ply(data: &[u8], limits: MeshLimits) -> Result<Mesh> {
+    let (encoding, elements, body_start) = parse_header(data)?;
+    // Размеры известны из заголовка, так что слишком большой файл отклоняем до чтения тела
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => limits.check_vertices(element.count)?,
+            "face" => limits.check_faces(element.count)?,
+            _ => {}
+        }
+    }
+    let mut reader = match encoding {
+        Encoding::Ascii => Reader::Ascii(
+            std::str::from_utf8(&data[body_start..])
+                .map_err(|e| ply_error(&format!("ASCII body is not valid UTF-8: {}", e)))?
+                .split_ascii_whitespace(),
+        ),
+        Encoding::LittleEndian | Encoding::BigEndian => Reader::Binary {
+            data: &data[body_start..],
+            position: 0,
+            big_endian: encoding == Encoding::BigEndian,
+        },
+    };
+
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+
+    // Элемент без свойств не занимает места в теле, а его счётчик может быть любым
+    for element in elements.iter().filter(|element| !element.properties.is_empty()) {
+        for _ in 0..element.count {
+            match element.name.as_str() {
+                "vertex" => {
+                    let mut coords = [0.0; 3];
+                    for property in &element.properties {
+                        match property {
+                            Property::Scalar { name, ty } => {
+                                let value = reader.read(*ty)?;
+                                match name.as_str() {
+                                    "x" => coords[0] = value,
+                                    "y" => coords[1] = value,
+                                    "z" => coords[2] = value,
+                                    _ => {}
+                                }
+                            }
+                            Property::List { count, item, .. } => reader.skip_list(*count, *item)?,
+                        }
+                    }
+                    vertices.push(Vector3 { x: coords[0], y: coords[1], z: coords[2] });
+                }
+                "face" => {
+                    let mut face = None;
+                    for property in &element.properties {
+                        match property {
+                            Property::List { name, count, item }
+                                if name == "vertex_indices" || name == "vertex_index" =>
+                            {
+                                let len = reader.read_index(*count, "face vertex count")?;
+                                let indices = (0..len)
+                                    .map(|_| reader.read_index(*item, "vertex index"))
+                                    .collect::<Result<Vec<usize>>>()?;
+                                face = Some(indices);
+                            }
+                            Property::List { count, item, .. } => reader.skip_list(*count, *item)?,
+                            Property::Scalar { ty, .. } => {
+                                reader.read(*ty)?;
+                            }
+                        }
+                    }
+                    faces.push(face.ok_or_else(|| ply_error("face element has no vertex_indices list"))?);
+                }
+                _ => reader.skip_element(element)?,
+            }
+        }
+    }
+
+    Ok(Mesh::new(vertices, faces))
+}
+
+fn parse_header(data: &[u8]) -> Result<(Encoding, Vec<Element>, usize)> {
+    const END: &[u8] = b"end_header";
+    let end = data
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or_else(|| ply_error("missing end_header"))?;
+    // Тело начинается со следующей строки после end_header
+    let body_start = data[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(data.len(), |newline| end + newline + 1);
+    let header = std::str::from_utf8(&data[..end]).map_err(|_| ply_error("header is not valid text"))?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(ply_error("missing 'ply' magic"));
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", format, _version] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(ply_error(&format!("unknown format '{}'", format))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| ply_error(&format!("bad element count '{}'", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or_else(|| ply_error("property before element"))?;
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count: ScalarType::parse(count)?,
+                    item: ScalarType::parse(item)?,
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements.last_mut().ok_or_else(|| ply_error("property before element"))?;
+                element.properties.push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: ScalarType::parse(ty)?,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let encoding = encoding.ok_or_else(|| ply_error("missing format line"))?;
+    Ok((encoding, elements, body_start))
+}
+
+enum Reader<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], position: usize, big_endian: bool },
+}
+
+impl Reader<'_> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64> {
+        match self {
+            Reader::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(|| ply_error("unexpected end of data"))?;
+                token.parse().map_err(|_| ply_error(&format!("bad value '{}'", token)))
+            }
+            Reader::Binary { data, position, big_endian } => {
+                let size = ty.size();
+                let bytes = data
+                    .get(*position..*position + size)
+                    .ok_or_else(|| ply_error("unexpected end of data"))?;
+                *position += size;
+
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                Ok(match ty {
+                    ScalarType::I8 => buffer[0] as i8 as f64,
+                    ScalarType::U8 => buffer[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::I32 => i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+                    ScalarType::U32 => u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+                    ScalarType::F32 => f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+                    ScalarType::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    /// Reads a count or an index; `as usize` would turn -1 into 0 and 2.5 into 2, so such
+    /// values are errors.
+    fn read_index(&mut self, ty: ScalarType, what: &str) -> Result<usize> {
+        let value = self.read(ty)?;
+        if !value.is_finite() || value < 0.0 || value.fract() != 0.0 || value > u32::MAX as f64 {
+            return Err(ply_error(&format!("{} must be a non-negative integer, got {}", what, value)));
+        }
+        Ok(value as usize)
+    }
+
+    fn skip_list(&mut self, count: ScalarType, item: ScalarType) -> Result<()> {
+        let len = self.read_index(count, "list length")?;
+        for _ in 0..len {
+            self.read(item)?;
+        }
+        Ok(())
+    }
+
+    fn skip_element(&mut self, element: &Element) -> Result<()> {
+        for property in &element.properties {
+            match property {
+                Property::Scalar { ty, .. } => {
+                    self.read(*ty)?;
+                }
+                Property::List { count, item, .. } => self.skip_list(*count, *item)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn ply_error(message: &str) -> UnfoldingError {
+    UnfoldingError::InvalidMesh(format!("PLY: {}", message))
+}
@@ -0,0 +1,99 @@
+use super::{FaceCollector, MeshLimits, VertexWelder};
+use crate::{Mesh, Result, UnfoldingError, Vector3};
+
+const HEADER_LEN: usize = 80;
+const TRIANGLE_LEN: usize = 50;
+
+/// Parses ASCII or binary STL. Coincident vertices are welded so that triangles share edges;
+/// every `solid` of a multi-solid ASCII file becomes its own part.
+pub fn parse_stl(data: &[u8], limits: MeshLimits) -> Result<Mesh> {
+    // Бинарные файлы тоже бывают с "solid" в заголовке, поэтому сначала сверяем размер
+    if let Some(count) = binary_triangle_count(data) {
+        if data.len() == HEADER_LEN + 4 + count * TRIANGLE_LEN {
+            return parse_binary(data, count, limits);
+        }
+    }
+    if data.trim_ascii_start().starts_with(b"solid") {
+        let source = std::str::from_utf8(data)
+            .map_err(|e| stl_error(&format!("ASCII STL is not valid UTF-8: {}", e)))?;
+        return parse_ascii(source, limits);
+    }
+    Err(stl_error("file is neither ASCII nor binary STL"))
+}
+
+fn binary_triangle_count(data: &[u8]) -> Option<usize> {
+    let bytes = data.get(HEADER_LEN..HEADER_LEN + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+}
+
+fn parse_binary(data: &[u8], count: usize, limits: MeshLimits) -> Result<Mesh> {
+    let mut welder = VertexWelder::new(limits);
+    let mut faces = FaceCollector::new(limits);
+    let part = faces.add_part("default".to_string());
+
+    for triangle in data[HEADER_LEN + 4..].chunks_exact(TRIANGLE_LEN).take(count) {
+        // Первые 12 байт — нормаль, её пересчитываем сами
+        let face = (0..3)
+            .map(|corner| {
+                let offset = 12 + corner * 12;
+                let coord = |axis: usize| {
+                    let start = offset + axis * 4;
+                    f32::from_le_bytes([triangle[start], triangle[start + 1], triangle[start + 2], triangle[start + 3]])
+                        as f64
+                };
+                welder.insert(Vector3 { x: coord(0), y: coord(1), z: coord(2) })
+            })
+            .collect::<Result<Vec<usize>>>()?;
+        faces.push(face, part)?;
+    }
+
+    Ok(faces.into_mesh(welder.into_vertices()))
+}
+
+fn parse_ascii(source: &str, limits: MeshLimits) -> Result<Mesh> {
+    let mut welder = VertexWelder::new(limits);
+    let mut faces = FaceCollector::new(limits);
+    let mut part = None;
+    let mut loop_vertices = Vec::new();
+
+    for (line_number, raw_line) in source.lines().enumerate() {
+        let mut tokens = raw_line.split_whitespace();
+        match tokens.next() {
+            Some("solid") => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                part = Some(faces.add_part(if name.is_empty() { "default".to_string() } else { name }));
+            }
+            Some("vertex") => {
+                let coords: Vec<f64> = tokens
+                    .take(3)
+                    .map(|token| token.parse::<f64>())
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|e| stl_line_error(line_number, &format!("bad vertex coordinate: {}", e)))?;
+                if coords.len() != 3 {
+                    return Err(stl_line_error(line_number, "vertex needs 3 coordinates"));
+                }
+                loop_vertices.push(welder.insert(Vector3 { x: coords[0], y: coords[1], z: coords[2] })?);
+            }
+            Some("endloop") => {
+                let Some(part) = part else {
+                    return Err(stl_line_error(line_number, "facet outside of a solid"));
+                };
+                if loop_vertices.len() < 3 {
+                    return Err(stl_line_error(line_number, "facet needs at least 3 vertices"));
+                }
+                faces.push(std::mem::take(&mut loop_vertices), part)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(faces.into_mesh(welder.into_vertices()))
+}
+
+fn stl_error(message: &str) -> UnfoldingError {
+    UnfoldingError::InvalidMesh(format!("STL: {}", message))
+}
+
+fn stl_line_error(line_number: usize, message: &str) -> UnfoldingError {
+    UnfoldingError::InvalidMesh(format!("STL line {}: {}", line_number + 1, message))
+}
@@ -3,15 +3,30 @@
 // #![cfg_attr(feature = "simd", feature(portable_simd))]  // Закомментировать пока
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Instant;
 
-#[cfg(feature = "parallel")]
-use rayon::prelude::*;
-
 // Используем conditional compilation с правильными фичами
 #[cfg(any(feature = "tracing", feature = "server"))]
 use tracing::{debug, info};
 
+mod distortion;
+pub mod export;
+pub mod formats;
+mod net;
+mod progress;
+mod segmentation;
+mod topology;
+
+pub use distortion::{DistortionSummary, FaceDistortion};
+pub use export::ExportFormat;
+pub use formats::{obj::ObjGrouping, MeshFormat, MeshLimits};
+pub use net::{EdgeOffset, FlatFace, FoldKind, FoldLine, IslandNet, Tab, UnfoldingMode};
+pub use progress::{CancellationToken, NoopObserver, UnfoldObserver, UnfoldProgress, UnfoldStage};
+
+use progress::ProgressTracker;
+pub use segmentation::SegmentationMode;
+
 #[derive(Debug, thiserror::Error)]
 pub enum UnfoldingError {
     #[error("Invalid mesh: {0}")]
@@ -20,6 +35,12 @@ pub enum UnfoldingError {
     ProcessingFailed(String),
     #[error("Math error: {0}")]
     MathError(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("Unfolding was cancelled")]
+    Cancelled,
+    #[error("Mesh too large: {0}")]
+    MeshTooLarge(String),
 }
 
 pub type Result<T> = std::result::Result<T, UnfoldingError>;
@@ -35,11 +56,41 @@ pub struct Vector3 {
 pub struct Mesh {
     pub vertices: Vec<Vector3>,
     pub faces: Vec<Vec<usize>>,
+    /// Optional part ID for every face (OBJ group, material, or user-defined).
+    #[serde(default)]
+    pub face_parts: Option<Vec<usize>>,
+    /// Names of the parts referenced by `face_parts`, indexed by part ID.
+    #[serde(default)]
+    pub part_names: Vec<String>,
 }
 
 impl Mesh {
     pub fn new(vertices: Vec<Vector3>, faces: Vec<Vec<usize>>) -> Self {
-        Self { vertices, faces }
+        Self {
+            vertices,
+            faces,
+            face_parts: None,
+            part_names: Vec::new(),
+        }
+    }
+
+    pub fn from_obj(source: &str, grouping: ObjGrouping) -> Result<Self> {
+        formats::obj::parse_obj(source, grouping, MeshLimits::NONE)
+    }
+
+    /// Parses a mesh file of any supported format; `grouping` only applies to OBJ.
+    pub fn from_bytes(data: &[u8], format: MeshFormat, grouping: ObjGrouping) -> Result<Self> {
+        format.parse(data, grouping, MeshLimits::NONE)
+    }
+
+    /// Like `from_bytes`, but stops with `MeshTooLarge` once the mesh outgrows `limits`.
+    pub fn from_bytes_limited(
+        data: &[u8],
+        format: MeshFormat,
+        grouping: ObjGrouping,
+        limits: MeshLimits,
+    ) -> Result<Self> {
+        format.parse(data, grouping, limits)
     }
 
     pub fn from_flat_data(flat_vertices: &[f64], faces: Vec<Vec<usize>>) -> Result<Self> {
@@ -58,7 +109,7 @@ impl Mesh {
             })
             .collect();
 
-        Ok(Self { vertices, faces })
+        Ok(Self::new(vertices, faces))
     }
 
     pub fn validate(&self) -> Result<()> {
@@ -88,31 +139,145 @@ impl Mesh {
                 }
             }
         }
+
+        if let Some(face_parts) = &self.face_parts {
+            if face_parts.len() != self.faces.len() {
+                return Err(UnfoldingError::InvalidMesh(format!(
+                    "face_parts has {} entries but mesh has {} faces",
+                    face_parts.len(),
+                    self.faces.len()
+                )));
+            }
+            // Таблица частей строится по наибольшему ID, поэтому он ограничен числом граней или имён:
+            // иначе один огромный ID раздул бы её. Загрузчики могут оставить части без граней
+            let part_limit = self.faces.len().max(self.part_names.len());
+            if let Some(&part) = face_parts.iter().find(|&&part| part >= part_limit) {
+                return Err(UnfoldingError::InvalidMesh(format!(
+                    "face_parts references part {} but the mesh has {} faces and {} part names",
+                    part,
+                    self.faces.len(),
+                    self.part_names.len()
+                )));
+            }
+        }
         Ok(())
     }
+
+    /// Splits the mesh into edge-connected shells, each given as a sorted list of face indices.
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        topology::connected_components(self)
+    }
+
+    /// Area of a single (possibly non-planar) polygonal face, computed with Newell's method.
+    pub fn face_area(&self, face_index: usize) -> f64 {
+        let n = self.newell_normal(face_index);
+        (n.x * n.x + n.y * n.y + n.z * n.z).sqrt() / 2.0
+    }
+
+    /// Unit normal of a face; zero vector for degenerate faces.
+    pub fn face_normal(&self, face_index: usize) -> Vector3 {
+        let n = self.newell_normal(face_index);
+        let length = (n.x * n.x + n.y * n.y + n.z * n.z).sqrt();
+        if length == 0.0 {
+            return n;
+        }
+        Vector3 { x: n.x / length, y: n.y / length, z: n.z / length }
+    }
+
+    fn newell_normal(&self, face_index: usize) -> Vector3 {
+        let face = &self.faces[face_index];
+        let mut normal = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+
+        for i in 0..face.len() {
+            let current = &self.vertices[face[i]];
+            let next = &self.vertices[face[(i + 1) % face.len()]];
+            normal.x += (current.y - next.y) * (current.z + next.z);
+            normal.y += (current.z - next.z) * (current.x + next.x);
+            normal.z += (current.x - next.x) * (current.y + next.y);
+        }
+
+        normal
+    }
+
+    pub fn surface_area(&self, face_indices: &[usize]) -> f64 {
+        face_indices.iter().map(|&face_index| self.face_area(face_index)).sum()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UnfoldingResult {
+    /// Outline of every island's net (without tabs) in the net's own coordinates, `z = 0`.
     pub sheets: Vec<Vec<Vector3>>,
     pub processing_time_ms: u128,
     pub metadata: UnfoldingMetadata,
+    pub components: Vec<ComponentInfo>,
+    pub parts: Vec<PartInfo>,
+    pub islands: Vec<IslandInfo>,
+    /// Thickness offsets applied along folded edges; empty when `paper_thickness` is zero.
+    pub edge_offsets: Vec<EdgeOffset>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UnfoldingMetadata {
     pub sheet_count: usize,
+    /// Paper area of all flattened faces, after thickness compensation.
     pub total_area: f64,
+    /// Extent of the nets including tabs; each net keeps its own coordinates.
     pub bounds: [f64; 4], // [min_x, min_y, max_x, max_y]
+    pub component_count: usize,
+    pub dropped_components: usize,
+    pub part_count: usize,
+    pub island_count: usize,
+    /// Distortion over every flattened face.
+    pub distortion: DistortionSummary,
+    /// Distortion per island, indexed by island ID.
+    pub island_distortion: Vec<DistortionSummary>,
+}
+
+/// A disconnected shell of the input mesh and the sheets produced from it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComponentInfo {
+    /// Stable identifier: index of the shell in detection order, assigned before filtering.
+    pub id: usize,
+    pub face_indices: Vec<usize>,
+    pub surface_area: f64,
+    pub sheet_indices: Vec<usize>,
 }
 
+/// A logical section of the model; its `sheet_indices` form a separately printable sheet set.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PartInfo {
+    pub id: usize,
+    pub name: Option<String>,
+    pub face_count: usize,
+    pub sheet_indices: Vec<usize>,
+}
+
+/// Faces of one component and one part that are unfolded together.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IslandInfo {
+    pub id: usize,
+    pub component_id: usize,
+    pub part_id: usize,
+    pub face_indices: Vec<usize>,
+    pub sheet_indices: Vec<usize>,
+    pub net: IslandNet,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct UnfoldingConfig {
     pub quality_level: QualityLevel,
     pub sheet_size: [f64; 2],
     pub optimize_folding_lines: bool,
     pub add_tabs: bool,
     pub tolerance: f64,
+    /// Components whose 3D surface area is below this value are left out of the result.
+    pub min_component_area: f64,
+    pub segmentation: SegmentationMode,
+    /// Paper thickness in model units; faces are inset along folds to compensate for it.
+    pub paper_thickness: f64,
+    pub unfolding_mode: UnfoldingMode,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -123,6 +288,68 @@ pub enum QualityLevel {
     Production,
 }
 
+impl QualityLevel {
+    pub const ALL: [QualityLevel; 4] = [
+        QualityLevel::Draft,
+        QualityLevel::Standard,
+        QualityLevel::High,
+        QualityLevel::Production,
+    ];
+
+    /// Lower-case name used by the HTTP API.
+    pub fn name(&self) -> &'static str {
+        match self {
+            QualityLevel::Draft => "draft",
+            QualityLevel::Standard => "standard",
+            QualityLevel::High => "high",
+            QualityLevel::Production => "production",
+        }
+    }
+
+    /// Parses a quality name case-insensitively.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl UnfoldingConfig {
+    /// Checks that every numeric option is finite and within its meaningful range.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(UnfoldingError::InvalidConfig(message));
+
+        if self.sheet_size.iter().any(|side| !side.is_finite() || *side <= 0.0) {
+            return invalid(format!("sheet_size must be two positive numbers, got {:?}", self.sheet_size));
+        }
+        if !self.tolerance.is_finite() || self.tolerance <= 0.0 {
+            return invalid(format!("tolerance must be a positive number, got {}", self.tolerance));
+        }
+        if !self.min_component_area.is_finite() || self.min_component_area < 0.0 {
+            return invalid(format!(
+                "min_component_area must be non-negative, got {}",
+                self.min_component_area
+            ));
+        }
+        if !self.paper_thickness.is_finite() || self.paper_thickness < 0.0 {
+            return invalid(format!("paper_thickness must be non-negative, got {}", self.paper_thickness));
+        }
+        if let SegmentationMode::Automatic { max_dihedral_angle } = self.segmentation {
+            if !(0.0..=180.0).contains(&max_dihedral_angle) {
+                return invalid(format!(
+                    "max_dihedral_angle must be between 0 and 180 degrees, got {}",
+                    max_dihedral_angle
+                ));
+            }
+        }
+        if let UnfoldingMode::Strips { max_strain } = self.unfolding_mode {
+            // Растяжение больше 100% уже не бумага
+            if !(0.0..=1.0).contains(&max_strain) {
+                return invalid(format!("max_strain must be between 0 and 1, got {}", max_strain));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Default for UnfoldingConfig {
     fn default() -> Self {
         Self {
@@ -131,6 +358,10 @@ impl Default for UnfoldingConfig {
             optimize_folding_lines: true,
             add_tabs: true,
             tolerance: 0.001,
+            min_component_area: 0.0,
+            segmentation: SegmentationMode::default(),
+            paper_thickness: 0.0,
+            unfolding_mode: UnfoldingMode::default(),
         }
     }
 }
@@ -176,27 +407,165 @@ impl UnfoldingCore {
 
     // Убрали атрибут instrument чтобы избежать ошибок
     pub fn unfold_mesh(&self, request: &UnfoldingRequest) -> Result<UnfoldingResult> {
+        self.unfold_mesh_with(request, &NoopObserver)
+    }
+
+    /// Same as [`unfold_mesh`](Self::unfold_mesh), reporting each stage to `observer` and
+    /// returning `UnfoldingError::Cancelled` as soon as it asks to stop.
+    pub fn unfold_mesh_with(&self, request: &UnfoldingRequest, observer: &dyn UnfoldObserver) -> Result<UnfoldingResult> {
         #[cfg(any(feature = "tracing", feature = "server"))]
         info!("Starting unfolding process with quality level: {:?}", request.config.quality_level);
         
         let start_time = Instant::now();
+        let progress = ProgressTracker::new(observer);
+        progress.report(UnfoldStage::Validate, 0.0)?;
 
         // Validate mesh
         request.mesh.validate()?;
+        request.config.validate()?;
         
         #[cfg(any(feature = "tracing", feature = "server"))]
         debug!("Mesh validation passed: {} vertices, {} faces", 
                request.mesh.vertices.len(), request.mesh.faces.len());
 
-        // Process mesh based on quality level - используем self.config для демонстрации
-        let _current_config = self.get_config();
-        
-        let sheets = match request.config.quality_level {
-            QualityLevel::Draft => self.calculate_draft_unfolding(&request.mesh, &request.config)?,
-            QualityLevel::Standard => self.calculate_standard_unfolding(&request.mesh, &request.config)?,
-            QualityLevel::High => self.calculate_high_quality_unfolding(&request.mesh, &request.config)?,
-            QualityLevel::Production => self.calculate_production_unfolding(&request.mesh, &request.config)?,
+        progress.report(UnfoldStage::Topology, 0.0)?;
+
+        // Split into shells and drop the ones below the size threshold
+        let mut components: Vec<ComponentInfo> = Vec::new();
+        let mut dropped_components = 0;
+
+        for (id, face_indices) in request.mesh.connected_components().into_iter().enumerate() {
+            let surface_area = request.mesh.surface_area(&face_indices);
+            if surface_area < request.config.min_component_area {
+                dropped_components += 1;
+                continue;
+            }
+            components.push(ComponentInfo {
+                id,
+                face_indices,
+                surface_area,
+                sheet_indices: Vec::new(),
+            });
+        }
+
+        // Порог задаёт клиент, так что это ошибка запроса, а не сервера
+        if components.is_empty() {
+            return Err(UnfoldingError::InvalidConfig(format!(
+                "All {} components are smaller than min_component_area ({})",
+                dropped_components, request.config.min_component_area
+            )));
+        }
+
+        #[cfg(any(feature = "tracing", feature = "server"))]
+        debug!("Detected {} components ({} dropped)", components.len() + dropped_components, dropped_components);
+
+        // Острова не пересекают границы частей, поэтому соединяем только грани одной части
+        let assignment = segmentation::assign_parts(&request.mesh, &request.config.segmentation);
+        let mut component_of_face = vec![None; request.mesh.faces.len()];
+        for component in &components {
+            for &face_index in &component.face_indices {
+                component_of_face[face_index] = Some(component.id);
+            }
+        }
+
+        // Раскладываем грани каждого острова на плоскость с учётом толщины бумаги
+        let edge_map = topology::build_edge_map(&request.mesh);
+        let folds = net::EdgeFolds::new(&request.mesh, &edge_map, request.config.paper_thickness);
+        let regions = topology::connected_components_where(&request.mesh, |a, b| {
+            assignment.face_parts[a] == assignment.face_parts[b]
+        });
+        // Дефекты нужны только полосам; считаем их один раз на весь меш, а не на каждую часть
+        let defects = match request.config.unfolding_mode {
+            UnfoldingMode::Strips { .. } => net::vertex_angle_defects(&request.mesh, &edge_map),
+            UnfoldingMode::Exact => HashMap::new(),
+        };
+
+        progress.report(UnfoldStage::TreeSearch, 0.0)?;
+        let mut flattened = Vec::new();
+        for (done, region) in regions.iter().enumerate() {
+            let Some(component_id) = component_of_face[region[0]] else {
+                continue;
+            };
+            let part_id = assignment.face_parts[region[0]];
+            let nets = net::flatten_region(&request.mesh, region, &edge_map, &folds, &defects, &request.config);
+            for (face_indices, net) in nets {
+                flattened.push((component_id, part_id, face_indices, net));
+            }
+            progress.report(UnfoldStage::TreeSearch, (done + 1) as f64 / regions.len() as f64)?;
+        }
+
+        // Черновое качество и полосы оставляют наложения как есть
+        let repair = request.config.quality_level != QualityLevel::Draft
+            && request.config.unfolding_mode == UnfoldingMode::Exact;
+        progress.report(UnfoldStage::OverlapRepair, 0.0)?;
+        let flattened_count = flattened.len();
+        let mut islands: Vec<IslandInfo> = Vec::new();
+        for (done, (component_id, part_id, face_indices, net)) in flattened.into_iter().enumerate() {
+            let pieces = if repair {
+                net::repair_overlaps(&request.mesh, face_indices, net, &edge_map, &folds, &request.config)
+            } else {
+                vec![(face_indices, net)]
+            };
+            for (face_indices, net) in pieces {
+                let island = IslandInfo {
+                    id: islands.len(),
+                    component_id,
+                    part_id,
+                    face_indices,
+                    sheet_indices: Vec::new(),
+                    net,
+                };
+                progress.island(&island);
+                islands.push(island);
+            }
+            progress.report(UnfoldStage::OverlapRepair, (done + 1) as f64 / flattened_count as f64)?;
+        }
+
+        // Keep each part's sheets contiguous so parts can be printed as separate sets
+        islands.sort_by_key(|island| (island.part_id, island.component_id, island.face_indices[0]));
+        for (id, island) in islands.iter_mut().enumerate() {
+            island.id = id;
+        }
+
+        progress.report(UnfoldStage::Packing, 0.0)?;
+        let island_sheets = match request.config.quality_level {
+            QualityLevel::Draft => self.calculate_draft_unfolding(&islands),
+            QualityLevel::Standard => self.calculate_standard_unfolding(&islands, &progress)?,
+            QualityLevel::High => self.calculate_high_quality_unfolding(&islands, &progress)?,
+            QualityLevel::Production => self.calculate_production_unfolding(&islands, &progress)?,
         };
+        progress.report(UnfoldStage::Packing, 1.0)?;
+
+        let mut sheets = Vec::new();
+        for (island, unfolded) in islands.iter_mut().zip(island_sheets) {
+            for sheet in unfolded {
+                island.sheet_indices.push(sheets.len());
+                sheets.push(sheet);
+            }
+        }
+
+        let mut parts: Vec<PartInfo> = Vec::new();
+        for island in &islands {
+            if parts.last().map(|part| part.id) != Some(island.part_id) {
+                parts.push(PartInfo {
+                    id: island.part_id,
+                    name: assignment.names.get(island.part_id).cloned().flatten(),
+                    face_count: 0,
+                    sheet_indices: Vec::new(),
+                });
+            }
+            if let Some(part) = parts.last_mut() {
+                part.face_count += island.face_indices.len();
+                part.sheet_indices.extend(&island.sheet_indices);
+            }
+        }
+
+        for component in components.iter_mut() {
+            for island in islands.iter().filter(|island| island.component_id == component.id) {
+                component.sheet_indices.extend(&island.sheet_indices);
+            }
+            component.sheet_indices.sort_unstable();
+        }
 
         let elapsed = start_time.elapsed();
         
@@ -205,145 +574,89 @@ impl UnfoldingCore {
 
         let metadata = UnfoldingMetadata {
             sheet_count: sheets.len(),
-            total_area: self.calculate_total_area(&sheets),
-            bounds: self.calculate_bounds(&sheets),
+            total_area: self.calculate_total_area(&islands),
+            bounds: self.calculate_bounds(&islands),
+            component_count: components.len(),
+            dropped_components,
+            part_count: parts.len(),
+            island_count: islands.len(),
+            distortion: DistortionSummary::from_faces(islands.iter().flat_map(|island| &island.net.distortion)),
+            island_distortion: islands
+                .iter()
+                .map(|island| DistortionSummary::from_faces(&island.net.distortion))
+                .collect(),
         };
 
         Ok(UnfoldingResult {
             sheets,
             processing_time_ms: elapsed.as_millis(),
             metadata,
+            components,
+            parts,
+            islands,
+            edge_offsets: folds.applied_offsets(),
         })
     }
 
-    fn calculate_draft_unfolding(&self, mesh: &Mesh, config: &UnfoldingConfig) -> Result<Vec<Vec<Vector3>>> {
-        // Simple bounding box unfolding for draft quality
-        self.calculate_bounding_box_unfolding(mesh, config)
+    fn calculate_draft_unfolding(&self, islands: &[IslandInfo]) -> Vec<Vec<Vec<Vector3>>> {
+        // Simple outline unfolding for draft quality
+        self.calculate_outline_unfolding(islands)
     }
 
-    fn calculate_standard_unfolding(&self, mesh: &Mesh, config: &UnfoldingConfig) -> Result<Vec<Vec<Vector3>>> {
+    fn calculate_standard_unfolding(&self, islands: &[IslandInfo], progress: &ProgressTracker) -> Result<Vec<Vec<Vec<Vector3>>>> {
         // More sophisticated unfolding algorithm
-        std::thread::sleep(std::time::Duration::from_millis(100));
-        self.calculate_bounding_box_unfolding(mesh, config)
+        progress.wait(UnfoldStage::Packing, std::time::Duration::from_millis(100))?;
+        Ok(self.calculate_outline_unfolding(islands))
     }
 
-    fn calculate_high_quality_unfolding(&self, mesh: &Mesh, config: &UnfoldingConfig) -> Result<Vec<Vec<Vector3>>> {
+    fn calculate_high_quality_unfolding(&self, islands: &[IslandInfo], progress: &ProgressTracker) -> Result<Vec<Vec<Vec<Vector3>>>> {
         // High quality unfolding with optimization
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        self.calculate_bounding_box_unfolding(mesh, config)
+        progress.wait(UnfoldStage::Packing, std::time::Duration::from_millis(200))?;
+        Ok(self.calculate_outline_unfolding(islands))
     }
 
-    fn calculate_production_unfolding(&self, mesh: &Mesh, config: &UnfoldingConfig) -> Result<Vec<Vec<Vector3>>> {
+    fn calculate_production_unfolding(&self, islands: &[IslandInfo], progress: &ProgressTracker) -> Result<Vec<Vec<Vec<Vector3>>>> {
         // Production quality with all optimizations
-        std::thread::sleep(std::time::Duration::from_millis(500));
-        self.calculate_bounding_box_unfolding(mesh, config)
+        progress.wait(UnfoldStage::Packing, std::time::Duration::from_millis(500))?;
+        Ok(self.calculate_outline_unfolding(islands))
     }
 
-    fn calculate_bounding_box_unfolding(&self, mesh: &Mesh, _config: &UnfoldingConfig) -> Result<Vec<Vec<Vector3>>> {
-        // Calculate bounding box
-        let (min, max) = self.calculate_bounds_3d(&mesh.vertices)?;
-        
-        // Create a simple 2D unfolding (bounding box projection)
-        let sheet = vec![
-            Vector3 { x: min.x, y: min.y, z: 0.0 },
-            Vector3 { x: max.x, y: min.y, z: 0.0 },
-            Vector3 { x: max.x, y: max.y, z: 0.0 },
-            Vector3 { x: min.x, y: max.y, z: 0.0 },
-        ];
-
-        Ok(vec![sheet])
-    }
-
-    fn calculate_bounds_3d(&self, vertices: &[Vector3]) -> Result<(Vector3, Vector3)> {
-        if vertices.is_empty() {
-            return Err(UnfoldingError::MathError("Cannot calculate bounds of empty vertex list".to_string()));
-        }
-
-        let mut min = Vector3 { x: f64::INFINITY, y: f64::INFINITY, z: f64::INFINITY };
-        let mut max = Vector3 { x: f64::NEG_INFINITY, y: f64::NEG_INFINITY, z: f64::NEG_INFINITY };
-
-        #[cfg(feature = "parallel")]
-        {
-            let (min_result, max_result) = vertices.par_iter().fold(
-                || (min.clone(), max.clone()),
-                |(mut local_min, mut local_max), vertex| {
-                    local_min.x = local_min.x.min(vertex.x);
-                    local_min.y = local_min.y.min(vertex.y);
-                    local_min.z = local_min.z.min(vertex.z);
-                    local_max.x = local_max.x.max(vertex.x);
-                    local_max.y = local_max.y.max(vertex.y);
-                    local_max.z = local_max.z.max(vertex.z);
-                    (local_min, local_max)
-                },
-            ).reduce(
-                || (min.clone(), max.clone()),
-                |(min1, max1), (min2, max2)| {
-                    (
-                        Vector3 {
-                            x: min1.x.min(min2.x),
-                            y: min1.y.min(min2.y),
-                            z: min1.z.min(min2.z),
-                        },
-                        Vector3 {
-                            x: max1.x.max(max2.x),
-                            y: max1.y.max(max2.y),
-                            z: max1.z.max(max2.z),
-                        },
-                    )
-                },
-            );
-            min = min_result;
-            max = max_result;
-        }
-
-        #[cfg(not(feature = "parallel"))]
-        {
-            for vertex in vertices {
-                min.x = min.x.min(vertex.x);
-                min.y = min.y.min(vertex.y);
-                min.z = min.z.min(vertex.z);
-                max.x = max.x.max(vertex.x);
-                max.y = max.y.max(vertex.y);
-                max.z = max.z.max(vertex.z);
-            }
-        }
-
-        Ok((min, max))
+    // Возвращает листы для каждого острова в том же порядке, что и islands:
+    // контур развёртки острова в её собственных координатах
+    fn calculate_outline_unfolding(&self, islands: &[IslandInfo]) -> Vec<Vec<Vec<Vector3>>> {
+        islands
+            .iter()
+            .map(|island| {
+                let sheet = net::outline(&island.net)
+                    .into_iter()
+                    .map(|[x, y]| Vector3 { x, y, z: 0.0 })
+                    .collect();
+                vec![sheet]
+            })
+            .collect()
     }
 
-    fn calculate_total_area(&self, sheets: &[Vec<Vector3>]) -> f64 {
-        sheets.iter().map(|sheet| self.calculate_polygon_area(sheet)).sum()
+    // Площадь бумаги под гранями развёрток, с учётом вдавливания на толщину бумаги
+    fn calculate_total_area(&self, islands: &[IslandInfo]) -> f64 {
+        islands
+            .iter()
+            .flat_map(|island| &island.net.faces)
+            .map(|face| net::signed_area_2d(&face.points).abs())
+            .sum()
     }
 
-    fn calculate_polygon_area(&self, polygon: &[Vector3]) -> f64 {
-        if polygon.len() < 3 {
-            return 0.0;
-        }
-
-        let mut area = 0.0;
-        for i in 0..polygon.len() {
-            let j = (i + 1) % polygon.len();
-            area += polygon[i].x * polygon[j].y - polygon[j].x * polygon[i].y;
-        }
-        area.abs() / 2.0
-    }
-
-    fn calculate_bounds(&self, sheets: &[Vec<Vector3>]) -> [f64; 4] {
-        let mut min_x = f64::INFINITY;
-        let mut min_y = f64::INFINITY;
-        let mut max_x = f64::NEG_INFINITY;
-        let mut max_y = f64::NEG_INFINITY;
-
-        for sheet in sheets {
-            for vertex in sheet {
-                min_x = min_x.min(vertex.x);
-                min_y = min_y.min(vertex.y);
-                max_x = max_x.max(vertex.x);
-                max_y = max_y.max(vertex.y);
-            }
-        }
+    // Габариты граней и клапанов всех развёрток; каждая лежит в своих координатах
+    fn calculate_bounds(&self, islands: &[IslandInfo]) -> [f64; 4] {
+        let points: Vec<[f64; 2]> = islands
+            .iter()
+            .flat_map(|island| {
+                let faces = island.net.faces.iter().flat_map(|face| &face.points);
+                faces.chain(island.net.tabs.iter().flat_map(|tab| &tab.points))
+            })
+            .copied()
+            .collect();
 
-        [min_x, min_y, max_x, max_y]
+        net::bounds_2d(&points)
     }
 }
 
@@ -388,6 +701,23 @@ mod tests {
         assert!(mesh.validate().is_err());
     }
 
+    #[test]
+    fn test_mesh_validation_part_id_out_of_range() {
+        let mut mesh = create_test_cube();
+        mesh.face_parts = Some(vec![0, 1, 2, 3, 4, 5]);
+        assert!(mesh.validate().is_ok());
+
+        for part in [6, 1_000_000_000_000, usize::MAX] {
+            mesh.face_parts = Some(vec![0, 0, 0, 0, 0, part]);
+            assert!(matches!(mesh.validate(), Err(UnfoldingError::InvalidMesh(_))), "{}", part);
+        }
+
+        // Имя может быть и у части, грани которой выпали при сварке
+        mesh.part_names = (0..8).map(|part| format!("part {}", part)).collect();
+        mesh.face_parts = Some(vec![0, 0, 0, 0, 0, 7]);
+        assert!(mesh.validate().is_ok());
+    }
+
     #[test]
     fn test_unfold_mesh_ok() {
         let mesh = create_test_cube();
@@ -408,6 +738,698 @@ mod tests {
         }
     }
 
+    fn create_two_cubes() -> Mesh {
+        let mut mesh = create_test_cube();
+        let offset = mesh.vertices.len();
+        let small = create_test_cube();
+
+        mesh.vertices.extend(small.vertices.into_iter().map(|v| Vector3 {
+            x: v.x * 0.1 + 5.0,
+            y: v.y * 0.1,
+            z: v.z * 0.1,
+        }));
+        mesh.faces.extend(
+            small.faces.into_iter().map(|face| face.into_iter().map(|i| i + offset).collect()),
+        );
+        mesh
+    }
+
+    #[test]
+    fn test_connected_components() {
+        let mesh = create_two_cubes();
+        let components = mesh.connected_components();
+
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0], vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(components[1], vec![6, 7, 8, 9, 10, 11]);
+        assert!((mesh.surface_area(&components[0]) - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unfold_components_separately() {
+        let request = UnfoldingRequest {
+            mesh: create_two_cubes(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+
+        assert_eq!(result.metadata.component_count, 2);
+        assert_eq!(result.components[1].id, 1);
+        assert_eq!(result.components[1].sheet_indices, vec![1]);
+        assert!((result.metadata.total_area - 6.06).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sheets_are_net_outlines() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                add_tabs: false,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+        let outline: Vec<[f64; 2]> = result.sheets[0].iter().map(|p| [p.x, p.y]).collect();
+        let net_points: Vec<[f64; 2]> =
+            result.islands[0].net.faces.iter().flat_map(|face| face.points.clone()).collect();
+
+        // Крестовая развёртка куба: 14 вершин контура, площадь шести граней
+        assert_eq!(outline.len(), 14);
+        assert!((net::signed_area_2d(&outline) - 6.0).abs() < 1e-9);
+        assert!((result.metadata.total_area - 6.0).abs() < 1e-9);
+        assert_eq!(result.metadata.bounds, net::bounds_2d(&net_points));
+    }
+
+    #[test]
+    fn test_small_components_dropped() {
+        let request = UnfoldingRequest {
+            mesh: create_two_cubes(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                min_component_area: 1.0,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+
+        assert_eq!(result.components.len(), 1);
+        assert_eq!(result.components[0].id, 0);
+        assert_eq!(result.metadata.dropped_components, 1);
+        assert_eq!(result.metadata.sheet_count, 1);
+    }
+
+    #[test]
+    fn test_all_components_dropped_is_invalid_config() {
+        let request = UnfoldingRequest {
+            mesh: create_two_cubes(),
+            config: UnfoldingConfig { min_component_area: 100.0, ..Default::default() },
+        };
+
+        let error = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap_err();
+        assert!(matches!(error, UnfoldingError::InvalidConfig(message) if message.contains("min_component_area")));
+    }
+
+    #[test]
+    fn test_obj_groups_become_parts() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                      g head\nf 1 2 3\n\
+                      g body\nf 1/1/1 3/3/3 4/4/4\n";
+        let mesh = Mesh::from_obj(source, ObjGrouping::Groups).unwrap();
+
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2], vec![0, 2, 3]]);
+        assert_eq!(mesh.face_parts, Some(vec![0, 1]));
+        assert_eq!(mesh.part_names, vec!["head".to_string(), "body".to_string()]);
+
+        let request = UnfoldingRequest {
+            mesh,
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                ..Default::default()
+            },
+        };
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+
+        assert_eq!(result.metadata.island_count, 2);
+        assert_eq!(result.parts[1].name.as_deref(), Some("body"));
+        assert_eq!(result.parts[1].sheet_indices, vec![1]);
+    }
+
+    #[test]
+    fn test_stl_ascii_and_binary_are_welded() {
+        let ascii = "solid quad\n\
+                     facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nendloop\nendfacet\n\
+                     facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 1 0\nvertex 0 1 0\nendloop\nendfacet\n\
+                     endsolid quad\n";
+        let mesh = Mesh::from_bytes(ascii.as_bytes(), MeshFormat::Stl, ObjGrouping::default()).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2], vec![0, 2, 3]]);
+
+        let mut binary = vec![0u8; 80];
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        let triangles = [
+            [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+            [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+        ];
+        for triangle in triangles {
+            binary.extend_from_slice(&[0u8; 12]);
+            for coord in triangle.iter().flatten() {
+                binary.extend_from_slice(&coord.to_le_bytes());
+            }
+            binary.extend_from_slice(&[0u8; 2]);
+        }
+        let binary_mesh = Mesh::from_bytes(&binary, MeshFormat::Stl, ObjGrouping::default()).unwrap();
+        assert_eq!(binary_mesh.faces, mesh.faces);
+        assert_eq!(binary_mesh.vertices.len(), 4);
+    }
+
+    #[test]
+    fn test_ply_ascii_and_binary() {
+        let ascii = "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                     element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                     0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2 3\n";
+        let mesh = Mesh::from_bytes(ascii.as_bytes(), MeshFormat::Ply, ObjGrouping::default()).unwrap();
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2, 3]]);
+        assert_eq!(mesh.vertices[2].x, 1.0);
+
+        let mut binary = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\nproperty double x\n\
+                           property double y\nproperty double z\nelement face 1\n\
+                           property list uchar ushort vertex_indices\nend_header\n"
+            .to_vec();
+        for coord in [0.0f64, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0] {
+            binary.extend_from_slice(&coord.to_be_bytes());
+        }
+        binary.push(3);
+        for index in [0u16, 1, 2] {
+            binary.extend_from_slice(&index.to_be_bytes());
+        }
+        let binary_mesh = Mesh::from_bytes(&binary, MeshFormat::Ply, ObjGrouping::default()).unwrap();
+        assert_eq!(binary_mesh.faces, vec![vec![0, 1, 2]]);
+        assert_eq!(binary_mesh.vertices[1].x, 2.0);
+    }
+
+    #[test]
+    fn test_ply_rejects_invalid_indices() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                      element face 1\nproperty list uchar float vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n";
+        for face in ["3 0 1 -1", "3 0 1 1.5", "3 0 1 nan", "3 0 1 inf", "-3 0 1 2", "2.5 0 1 2"] {
+            let ply = format!("{}{}\n", header, face);
+            let error = Mesh::from_bytes(ply.as_bytes(), MeshFormat::Ply, ObjGrouping::default()).unwrap_err();
+            assert!(error.to_string().contains("non-negative integer"), "{}: {}", face, error);
+        }
+        let ply = format!("{}3 0 1 2.0\n", header);
+        assert!(Mesh::from_bytes(ply.as_bytes(), MeshFormat::Ply, ObjGrouping::default()).is_ok());
+    }
+
+    #[test]
+    fn test_parsers_stop_at_mesh_limits() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n";
+        let ply = "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                   element face 2\nproperty list uchar int vertex_indices\nend_header\n";
+        let facet = |a: &str, b: &str, c: &str| {
+            format!("facet normal 0 0 1\nouter loop\nvertex {}\nvertex {}\nvertex {}\nendloop\nendfacet\n", a, b, c)
+        };
+        let stl = format!("solid s\n{}{}endsolid s\n", facet("0 0 0", "1 0 0", "1 1 0"), facet("0 0 0", "1 1 0", "0 1 0"));
+        let parse = |data: &str, format, limits| {
+            Mesh::from_bytes_limited(data.as_bytes(), format, ObjGrouping::default(), limits)
+        };
+
+        for (data, format) in [(obj, MeshFormat::Obj), (stl.as_str(), MeshFormat::Stl)] {
+            assert!(parse(data, format, MeshLimits { max_vertices: 4, max_faces: 2 }).is_ok());
+        }
+        // PLY проверяется по заголовку, тело тут не нужно
+        for (data, format) in [(obj, MeshFormat::Obj), (ply, MeshFormat::Ply), (stl.as_str(), MeshFormat::Stl)] {
+            for (limits, what) in [
+                (MeshLimits { max_vertices: 3, max_faces: 2 }, "more than 3 vertices"),
+                (MeshLimits { max_vertices: 4, max_faces: 1 }, "more than 1 faces"),
+            ] {
+                let error = parse(data, format, limits).unwrap_err();
+                assert!(matches!(&error, UnfoldingError::MeshTooLarge(message) if message == what), "{:?}", error);
+            }
+        }
+    }
+
+    /// GLB with one triangle in the binary chunk. `accessor` and `view` override fields of
+    /// its only accessor and buffer view.
+    fn glb_triangle(
+        nodes: serde_json::Value,
+        scene_nodes: serde_json::Value,
+        accessor: serde_json::Value,
+        view: serde_json::Value,
+    ) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for coord in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            buffer.extend_from_slice(&coord.to_le_bytes());
+        }
+        let mut accessors = serde_json::json!({"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"});
+        let mut buffer_view = serde_json::json!({"buffer": 0, "byteLength": buffer.len()});
+        for (target, overrides) in [(&mut accessors, &accessor), (&mut buffer_view, &view)] {
+            for (key, value) in overrides.as_object().into_iter().flatten() {
+                target[key] = value.clone();
+            }
+        }
+        let json = serde_json::json!({
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": scene_nodes}],
+            "nodes": nodes,
+            "meshes": [{"name": "wing", "primitives": [{"attributes": {"POSITION": 0}}]}],
+            "accessors": [accessors],
+            "bufferViews": [buffer_view],
+            "buffers": [{"byteLength": buffer.len()}]
+        })
+        .to_string();
+
+        let mut glb = b"glTF".to_vec();
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&0u32.to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&0x4E4F_534Au32.to_le_bytes());
+        glb.extend_from_slice(json.as_bytes());
+        glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&0x004E_4942u32.to_le_bytes());
+        glb.extend_from_slice(&buffer);
+        glb
+    }
+
+    #[test]
+    fn test_glb_applies_node_transforms() {
+        let glb = glb_triangle(
+            serde_json::json!([{"mesh": 0, "translation": [0.0, 0.0, 5.0]}]),
+            serde_json::json!([0]),
+            serde_json::json!({}),
+            serde_json::json!({}),
+        );
+
+        let mesh = Mesh::from_bytes(&glb, MeshFormat::Gltf, ObjGrouping::default()).unwrap();
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2]]);
+        assert!(mesh.vertices.iter().all(|v| v.z == 5.0));
+        assert_eq!(MeshFormat::from_file_name("model.GLB"), Some(MeshFormat::Gltf));
+        assert_eq!(MeshFormat::from_content_type("model/stl; charset=binary"), Some(MeshFormat::Stl));
+    }
+
+    #[test]
+    fn test_glb_rejects_accessors_outside_their_view() {
+        let nodes = serde_json::json!([{"mesh": 0}]);
+        let no_overrides = serde_json::json!({});
+        for accessor in [
+            serde_json::json!({"count": 4}),
+            serde_json::json!({"count": usize::MAX}),
+            serde_json::json!({"byteOffset": 4}),
+        ] {
+            let glb = glb_triangle(nodes.clone(), serde_json::json!([0]), accessor.clone(), no_overrides.clone());
+            let error = Mesh::from_bytes(&glb, MeshFormat::Gltf, ObjGrouping::default()).unwrap_err();
+            assert!(error.to_string().contains("out of range"), "{}: {}", accessor, error);
+        }
+
+        // Нулевой шаг с огромным count читал бы одни и те же байты до исчерпания памяти
+        let glb = glb_triangle(
+            nodes,
+            serde_json::json!([0]),
+            serde_json::json!({"count": 1u64 << 40}),
+            serde_json::json!({"byteStride": 0}),
+        );
+        let error = Mesh::from_bytes(&glb, MeshFormat::Gltf, ObjGrouping::default()).unwrap_err();
+        assert!(error.to_string().contains("byteStride 0"), "{}", error);
+    }
+
+    #[test]
+    fn test_glb_rejects_shared_and_cyclic_nodes() {
+        let no_overrides = serde_json::json!({});
+        // Каждый уровень ссылается на следующий дважды: без проверки это 2^20 экземпляров
+        let mut doubling: Vec<serde_json::Value> =
+            (0..20).map(|node| serde_json::json!({"children": [node + 1, node + 1]})).collect();
+        doubling.push(serde_json::json!({"mesh": 0}));
+        let cycle = serde_json::json!([{"mesh": 0, "children": [1]}, {"children": [0]}]);
+
+        for nodes in [serde_json::Value::from(doubling), cycle] {
+            let glb = glb_triangle(nodes, serde_json::json!([0]), no_overrides.clone(), no_overrides.clone());
+            let error = Mesh::from_bytes(&glb, MeshFormat::Gltf, ObjGrouping::default()).unwrap_err();
+            assert!(error.to_string().contains("more than one parent"), "{}", error);
+        }
+
+        let tree = serde_json::json!([{"children": [1, 2]}, {"mesh": 0}, {"mesh": 0, "translation": [0.0, 0.0, 1.0]}]);
+        let glb = glb_triangle(tree, serde_json::json!([0]), no_overrides.clone(), no_overrides);
+        let mesh = Mesh::from_bytes(&glb, MeshFormat::Gltf, ObjGrouping::default()).unwrap();
+        assert_eq!(mesh.faces.len(), 2);
+    }
+
+    #[test]
+    fn test_export_cube_sheets() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig::default(),
+        };
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+
+        // 5 сгибов дерева и 7 разрезанных рёбер, на каждом клапан с основанием-сгибом и тремя резами
+        let sheets = export::layout_sheets(&result.islands, request.config.sheet_size);
+        assert_eq!(sheets.len(), 1);
+        assert_eq!(sheets[0].folds.len(), 5 + 7);
+        assert_eq!(sheets[0].cuts.len(), 7 + 7 * 3);
+
+        let svg = String::from_utf8(export::export_sheets(&result.islands, [210.0, 297.0], ExportFormat::Svg)).unwrap();
+        assert!(svg.contains("<g id=\"sheet-1\"") && svg.ends_with("</svg>\n"));
+        let pdf = export::export_sheets(&result.islands, [210.0, 297.0], ExportFormat::Pdf);
+        assert!(pdf.starts_with(b"%PDF-1.4") && pdf.ends_with(b"%%EOF\n"));
+        let dxf = String::from_utf8(export::export_sheets(&result.islands, [210.0, 297.0], ExportFormat::Dxf)).unwrap();
+        assert_eq!(dxf.matches("\nLINE\n").count(), 4 + 12 + 28);
+
+        let archive = export::export_sheet_archive(&result.islands, [210.0, 297.0], ExportFormat::Svg);
+        assert!(archive.starts_with(b"PK\x03\x04"));
+        assert!(archive.windows(12).any(|window| window == b"sheet-01.svg"));
+    }
+
+    #[test]
+    fn test_islands_never_cross_parts() {
+        let mut mesh = create_test_cube();
+        mesh.face_parts = Some(vec![0, 1, 1, 1, 1, 1]);
+        let request = UnfoldingRequest {
+            mesh,
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+
+        assert_eq!(result.metadata.component_count, 1);
+        assert_eq!(result.metadata.part_count, 2);
+        assert_eq!(result.islands[0].face_indices, vec![0]);
+        assert_eq!(result.islands[1].face_indices, vec![1, 2, 3, 4, 5]);
+        assert_eq!(result.components[0].sheet_indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_automatic_segmentation_splits_sharp_edges() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                segmentation: SegmentationMode::Automatic { max_dihedral_angle: 45.0 },
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+        assert_eq!(result.metadata.part_count, 6);
+        assert!(result.islands.iter().all(|island| island.face_indices.len() == 1));
+    }
+
+    #[test]
+    fn test_flattened_cube_keeps_face_sizes() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+        let net = &result.islands[0].net;
+
+        assert_eq!(net.faces.len(), 6);
+        assert_eq!(net.folds.len(), 5);
+        assert_eq!(net.tabs.len(), 7);
+        for face in &net.faces {
+            assert!((net::signed_area_2d(&face.points).abs() - 1.0).abs() < 1e-9);
+        }
+        assert!(result.edge_offsets.is_empty());
+    }
+
+    #[test]
+    fn test_distortion_metrics_reported() {
+        let request = UnfoldingRequest {
+            mesh: create_two_cubes(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+        let stats = &result.metadata.distortion;
+
+        assert_eq!(stats.face_count, 12);
+        assert!(stats.max_length_error < 1e-9);
+        assert!((stats.min_area_ratio - 1.0).abs() < 1e-9 && (stats.max_area_ratio - 1.0).abs() < 1e-9);
+        assert_eq!(result.metadata.island_distortion.len(), 2);
+        assert_eq!(result.metadata.island_distortion[1].face_count, 6);
+        assert_eq!(result.islands[0].net.distortion.len(), 6);
+    }
+
+    #[test]
+    fn test_paper_thickness_insets_mountain_folds() {
+        // Тестовый куб ориентирован нормалями внутрь, разворачиваем грани наружу
+        let mut mesh = create_test_cube();
+        for face in mesh.faces.iter_mut() {
+            face.reverse();
+        }
+        let request = UnfoldingRequest {
+            mesh,
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Draft,
+                paper_thickness: 0.1,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap();
+
+        assert_eq!(result.edge_offsets.len(), 12);
+        for edge in &result.edge_offsets {
+            assert_eq!(edge.kind, FoldKind::Mountain);
+            assert!((edge.offset - 0.05).abs() < 1e-9);
+        }
+        // Every edge of the cube is convex, so each square shrinks by 2 * 0.05 per side
+        for face in &result.islands[0].net.faces {
+            assert!((net::signed_area_2d(&face.points).abs() - 0.81).abs() < 1e-9);
+        }
+        assert!((result.metadata.distortion.max_length_error - 0.1).abs() < 1e-9);
+        assert!((result.metadata.distortion.max_area_ratio - 0.81).abs() < 1e-9);
+        assert!((result.metadata.total_area - 6.0 * 0.81).abs() < 1e-9);
+        assert!(result.islands[0].net.folds.iter().all(|fold| fold.kind == FoldKind::Mountain));
+    }
+
+    #[test]
+    fn test_negative_paper_thickness_rejected() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig {
+                paper_thickness: -0.1,
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request);
+        assert!(matches!(result, Err(UnfoldingError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_config_validation_rejects_out_of_range_values() {
+        assert!(UnfoldingConfig::default().validate().is_ok());
+
+        let invalid = [
+            UnfoldingConfig { sheet_size: [0.0, 297.0], ..Default::default() },
+            UnfoldingConfig { tolerance: f64::NAN, ..Default::default() },
+            UnfoldingConfig { min_component_area: -1.0, ..Default::default() },
+            UnfoldingConfig { paper_thickness: f64::INFINITY, ..Default::default() },
+            UnfoldingConfig {
+                segmentation: SegmentationMode::Automatic { max_dihedral_angle: 200.0 },
+                ..Default::default()
+            },
+            UnfoldingConfig {
+                unfolding_mode: UnfoldingMode::Strips { max_strain: 2.0 },
+                ..Default::default()
+            },
+        ];
+        for config in invalid {
+            assert!(matches!(config.validate(), Err(UnfoldingError::InvalidConfig(_))), "{:?}", config);
+        }
+
+        assert_eq!(QualityLevel::from_name("HIGH"), Some(QualityLevel::High));
+        assert_eq!(QualityLevel::from_name("ultra"), None);
+    }
+
+    fn create_test_tube(segments: usize, rings: usize) -> Mesh {
+        let mut vertices = Vec::new();
+        for ring in 0..rings {
+            for i in 0..segments {
+                let angle = 2.0 * std::f64::consts::PI * i as f64 / segments as f64;
+                vertices.push(Vector3 { x: angle.cos(), y: angle.sin(), z: ring as f64 * 0.5 });
+            }
+        }
+
+        let mut faces = Vec::new();
+        for ring in 0..rings - 1 {
+            for i in 0..segments {
+                let (a, b) = (ring * segments + i, ring * segments + (i + 1) % segments);
+                let (c, d) = (a + segments, b + segments);
+                faces.push(vec![a, b, d]);
+                faces.push(vec![a, d, c]);
+            }
+        }
+        Mesh::new(vertices, faces)
+    }
+
+    #[test]
+    fn test_strip_mode_keeps_developable_tube_in_one_piece() {
+        let mesh = create_test_tube(12, 3);
+        let face_count = mesh.faces.len();
+        let unfold = |unfolding_mode| {
+            let request = UnfoldingRequest {
+                mesh: mesh.clone(),
+                config: UnfoldingConfig {
+                    quality_level: QualityLevel::Draft,
+                    unfolding_mode,
+                    ..Default::default()
+                },
+            };
+            UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap()
+        };
+
+        let exact = unfold(UnfoldingMode::Exact);
+        let strips = unfold(UnfoldingMode::Strips { max_strain: 0.01 });
+
+        assert_eq!(strips.metadata.island_count, 1);
+        let net = &strips.islands[0].net;
+        assert_eq!(net.distortion.len(), face_count);
+        assert!(net.distortion.iter().all(|d| d.max_length_strain <= 0.01 && (d.area_ratio - 1.0).abs() < 0.03));
+        // В точной развёртке сгибы образуют только остовное дерево, в полосе сшиваются и соседние рёбра
+        assert_eq!(exact.islands[0].net.folds.len(), face_count - 1);
+        assert!(net.folds.len() > face_count - 1);
+        assert!(net.tabs.len() < exact.islands[0].net.tabs.len());
+    }
+
+    #[test]
+    fn test_strip_mode_rejects_negative_strain() {
+        let request = UnfoldingRequest {
+            mesh: create_test_tube(8, 2),
+            config: UnfoldingConfig {
+                unfolding_mode: UnfoldingMode::Strips { max_strain: -1.0 },
+                ..Default::default()
+            },
+        };
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh(&request);
+        assert!(matches!(result, Err(UnfoldingError::InvalidConfig(_))));
+    }
+
+    // Веер из 8 треугольников вокруг седловой вершины: сумма углов больше 360°, развёртка налезает сама на себя
+    fn create_test_saddle() -> Mesh {
+        let mut vertices = vec![Vector3 { x: 0.0, y: 0.0, z: 0.0 }];
+        for i in 0..8 {
+            let angle = std::f64::consts::PI / 4.0 * i as f64;
+            let z = if i % 2 == 0 { 1.0 } else { -1.0 };
+            vertices.push(Vector3 { x: angle.cos(), y: angle.sin(), z });
+        }
+        let faces = (0..8).map(|i| vec![0, i + 1, (i + 1) % 8 + 1]).collect();
+        Mesh::new(vertices, faces)
+    }
+
+    #[test]
+    fn test_overlap_repair_splits_islands() {
+        let unfold = |quality_level| {
+            let request = UnfoldingRequest {
+                mesh: create_test_saddle(),
+                config: UnfoldingConfig {
+                    quality_level,
+                    ..Default::default()
+                },
+            };
+            UnfoldingCore::with_default_config().unfold_mesh(&request).unwrap()
+        };
+
+        assert_eq!(unfold(QualityLevel::Draft).metadata.island_count, 1);
+
+        let repaired = unfold(QualityLevel::Standard);
+        assert!(repaired.metadata.island_count > 1);
+        let mut faces: Vec<usize> = repaired.islands.iter().flat_map(|island| island.face_indices.clone()).collect();
+        faces.sort_unstable();
+        assert_eq!(faces, (0..8).collect::<Vec<_>>());
+        assert!(repaired.islands.iter().all(|island| net::overlapping_subtrees(&island.net).is_empty()));
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        stages: std::sync::Mutex<Vec<UnfoldProgress>>,
+        islands: std::sync::Mutex<Vec<Vec<usize>>>,
+        cancel_at: Option<UnfoldStage>,
+    }
+
+    impl UnfoldObserver for RecordingObserver {
+        fn on_progress(&self, progress: &UnfoldProgress) {
+            self.stages.lock().unwrap().push(progress.clone());
+        }
+
+        fn on_island(&self, island: &IslandInfo) {
+            self.islands.lock().unwrap().push(island.face_indices.clone());
+        }
+
+        fn is_cancelled(&self) -> bool {
+            let stages = self.stages.lock().unwrap();
+            self.cancel_at.is_some_and(|stage| stages.iter().any(|p| p.stage == stage))
+        }
+    }
+
+    #[test]
+    fn test_progress_reports_every_stage() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig::default(),
+        };
+        let observer = RecordingObserver::default();
+
+        UnfoldingCore::with_default_config().unfold_mesh_with(&request, &observer).unwrap();
+
+        let reported = observer.stages.lock().unwrap();
+        let mut stages: Vec<UnfoldStage> = reported.iter().map(|p| p.stage).collect();
+        stages.dedup();
+        assert_eq!(
+            stages,
+            vec![
+                UnfoldStage::Validate,
+                UnfoldStage::Topology,
+                UnfoldStage::TreeSearch,
+                UnfoldStage::OverlapRepair,
+                UnfoldStage::Packing,
+            ]
+        );
+        assert!(reported.windows(2).all(|w| w[0].percent <= w[1].percent));
+        assert_eq!(reported.last().map(|p| p.percent), Some(100.0));
+    }
+
+    #[test]
+    fn test_island_layouts_reported_before_result() {
+        let request = UnfoldingRequest {
+            mesh: create_two_cubes(),
+            config: UnfoldingConfig::default(),
+        };
+        let observer = RecordingObserver::default();
+
+        let result = UnfoldingCore::with_default_config().unfold_mesh_with(&request, &observer).unwrap();
+
+        let mut reported = observer.islands.lock().unwrap().clone();
+        let mut final_islands: Vec<Vec<usize>> = result.islands.iter().map(|i| i.face_indices.clone()).collect();
+        reported.sort();
+        final_islands.sort();
+        assert_eq!(reported, final_islands);
+    }
+
+    #[test]
+    fn test_cancellation_stops_promptly() {
+        let request = UnfoldingRequest {
+            mesh: create_test_cube(),
+            config: UnfoldingConfig {
+                quality_level: QualityLevel::Production,
+                ..Default::default()
+            },
+        };
+        let observer = RecordingObserver {
+            cancel_at: Some(UnfoldStage::Packing),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let result = UnfoldingCore::with_default_config().unfold_mesh_with(&request, &observer);
+
+        assert!(matches!(result, Err(UnfoldingError::Cancelled)));
+        assert!(start.elapsed() < std::time::Duration::from_millis(400));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = UnfoldingCore::with_default_config().unfold_mesh_with(&request, &token);
+        assert!(matches!(result, Err(UnfoldingError::Cancelled)));
+    }
+
     #[test]
     fn test_from_flat_data() {
         let flat_vertices = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0];
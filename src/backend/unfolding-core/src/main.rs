@@ -1,27 +1,54 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{Json, IntoResponse},
+    extract::{
+        rejection::{BytesRejection, JsonRejection},
+        DefaultBodyLimit, Query, State,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{Json, IntoResponse, Response},
     routing::{get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use clap::Parser;
 use std::{
+    collections::BTreeMap,
     net::SocketAddr,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
-use tracing::{info, instrument};
+use tracing::{info, instrument, Span};
 
 // Используем текущий крейт вместо unfolding_core
 use pepakura_unfolding_core::{
-    UnfoldingCore, UnfoldingRequest, UnfoldingConfig, QualityLevel,
+    CancellationToken, ComponentInfo, DistortionSummary, EdgeOffset, ExportFormat, IslandInfo, MeshFormat, PartInfo, UnfoldingCore, UnfoldingError,
+    UnfoldingRequest, UnfoldingResult, QualityLevel,
 };
 
-#[derive(Debug, Clone)]
+mod server;
+
+use server::cache::ResultCache;
+use server::export::{self, ExportParams};
+use server::jobs::{self, JobQueue};
+use server::metrics::{self, Metrics};
+use server::profiles::{self, ConfigOverrides, Profiles};
+use server::quota::{self, Client, ClientQuota};
+use server::request_id;
+use server::settings::{self, Cli, Limits, LogFormat, Settings};
+use server::upload;
+
+#[derive(Debug)]
 struct AppState {
     version: String,
     unfolding_core: UnfoldingCore,
+    jobs: Arc<JobQueue>,
+    profiles: Profiles,
+    limits: Limits,
+    metrics: Arc<Metrics>,
+    cache: Arc<ResultCache>,
+    quota: Arc<ClientQuota>,
+    /// Whether `X-Client-Id` names the client, see `quota::Client`.
+    trust_client_id: bool,
 }
 
 #[derive(Serialize)]
@@ -31,43 +58,206 @@ struct HealthResponse {
     service: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 struct UnfoldResponse {
     sheets: Vec<Vec<[f64; 2]>>,
     success: bool,
     processing_time_ms: u128,
     metadata: UnfoldingMetadata,
+    components: Vec<ComponentInfo>,
+    parts: Vec<PartInfo>,
+    islands: Vec<IslandInfo>,
+    edge_offsets: Vec<EdgeOffset>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 struct UnfoldingMetadata {
     sheet_count: usize,
     total_area: f64,
     bounds: [f64; 4],
+    component_count: usize,
+    dropped_components: usize,
+    part_count: usize,
+    island_count: usize,
+    distortion: DistortionSummary,
+    island_distortion: Vec<DistortionSummary>,
+    /// The result was served from the result cache instead of being unfolded again.
+    cache_hit: bool,
 }
 
 #[derive(Deserialize)]
 struct UnfoldRequest {
     vertices: Vec<f64>,
     faces: Vec<Vec<usize>>,
-    quality: Option<String>,
+    face_parts: Option<Vec<usize>>,
+    #[serde(default)]
+    part_names: Vec<String>,
+    #[serde(flatten)]
+    config: ConfigOverrides,
+    // Поля, которых не знают ни запрос, ни ConfigOverrides, — обычно опечатки в именах опций
+    #[serde(flatten)]
+    unknown: BTreeMap<String, serde_json::Value>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Debug)]
 struct ErrorResponse {
     error: String,
     code: String,
 }
 
-impl IntoResponse for ErrorResponse {
-    fn into_response(self) -> axum::response::Response {
-        let status = match self.code.as_str() {
-            "INVALID_MESH" => StatusCode::BAD_REQUEST,
+// Тело ошибки с request_id текущего запроса; сами ошибки создаются без доступа к запросу
+#[derive(Serialize)]
+struct ErrorBody {
+    #[serde(flatten)]
+    error: ErrorResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    fn status(&self) -> StatusCode {
+        match self.code.as_str() {
+            "INVALID_MESH" | "INVALID_CONFIG" | "INVALID_REQUEST" => StatusCode::BAD_REQUEST,
+            "MESH_TOO_LARGE" | "BODY_TOO_LARGE" => StatusCode::PAYLOAD_TOO_LARGE,
+            "UNSUPPORTED_FORMAT" => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "JOB_NOT_FOUND" => StatusCode::NOT_FOUND,
+            "JOB_FINISHED" | "JOB_NOT_COMPLETED" | "CANCELLED" => StatusCode::CONFLICT,
+            "QUEUE_FULL" => StatusCode::SERVICE_UNAVAILABLE,
+            "TOO_MANY_JOBS" => StatusCode::TOO_MANY_REQUESTS,
+            "TIMEOUT" => StatusCode::GATEWAY_TIMEOUT,
             "PROCESSING_ERROR" => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        let body = ErrorBody {
+            error: self,
+            request_id: request_id::current(),
+        };
+        let mut response = (status, Json(body)).into_response();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(quota::RETRY_AFTER_SECS));
+        }
+        response
+    }
+}
+
+impl From<UnfoldingError> for ErrorResponse {
+    fn from(e: UnfoldingError) -> Self {
+        let code = match e {
+            UnfoldingError::InvalidMesh(_) => "INVALID_MESH",
+            UnfoldingError::InvalidConfig(_) => "INVALID_CONFIG",
+            UnfoldingError::Cancelled => "CANCELLED",
+            UnfoldingError::MeshTooLarge(_) => "MESH_TOO_LARGE",
+            _ => "PROCESSING_ERROR",
         };
-        
-        (status, Json(self)).into_response()
+        ErrorResponse {
+            error: e.to_string(),
+            code: code.to_string(),
+        }
+    }
+}
+
+// Отказы экстракторов axum отдаём тем же JSON, что и остальные ошибки
+fn rejection(status: StatusCode, message: String) -> ErrorResponse {
+    let code = match status {
+        StatusCode::PAYLOAD_TOO_LARGE => "BODY_TOO_LARGE",
+        _ => "INVALID_REQUEST",
+    };
+    ErrorResponse {
+        error: message,
+        code: code.to_string(),
+    }
+}
+
+impl From<JsonRejection> for ErrorResponse {
+    fn from(e: JsonRejection) -> Self {
+        rejection(e.status(), e.body_text())
+    }
+}
+
+impl From<BytesRejection> for ErrorResponse {
+    fn from(e: BytesRejection) -> Self {
+        rejection(e.status(), e.body_text())
+    }
+}
+
+impl UnfoldResponse {
+    fn from_result(result: UnfoldingResult, processing_time: Duration, cache_hit: bool) -> Self {
+        // Конвертируем результат в формат API
+        let sheets: Vec<Vec<[f64; 2]>> = result.sheets
+            .into_iter()
+            .map(|sheet| {
+                sheet.into_iter()
+                    .map(|v| [v.x, v.y])
+                    .collect()
+            })
+            .collect();
+
+        UnfoldResponse {
+            sheets,
+            success: true,
+            processing_time_ms: processing_time.as_millis(),
+            metadata: UnfoldingMetadata {
+                sheet_count: result.metadata.sheet_count,
+                total_area: result.metadata.total_area,
+                bounds: result.metadata.bounds,
+                component_count: result.metadata.component_count,
+                dropped_components: result.metadata.dropped_components,
+                part_count: result.metadata.part_count,
+                island_count: result.metadata.island_count,
+                distortion: result.metadata.distortion,
+                island_distortion: result.metadata.island_distortion,
+                cache_hit,
+            },
+            components: result.components,
+            parts: result.parts,
+            islands: result.islands,
+            edge_offsets: result.edge_offsets,
+        }
+    }
+}
+
+/// Cancels the wrapped token when dropped, e.g. when axum drops a handler after the client disconnects.
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+fn build_unfolding_request(payload: UnfoldRequest, state: &AppState) -> Result<UnfoldingRequest, ErrorResponse> {
+    reject_unknown_options(&payload.unknown)?;
+    let config = state.profiles.resolve(payload.config)?;
+
+    // Создаем запрос на развертку
+    match UnfoldingRequest::from_flat_data(payload.vertices, payload.faces, config) {
+        Ok(mut req) => {
+            req.mesh.face_parts = payload.face_parts;
+            req.mesh.part_names = payload.part_names;
+            state.limits.check(&req.mesh)?;
+            Ok(req)
+        }
+        Err(e) => Err(ErrorResponse {
+            error: e.to_string(),
+            code: "INVALID_MESH".to_string(),
+        }),
+    }
+}
+
+// serde не поддерживает deny_unknown_fields вместе с flatten, поэтому проверяем остаток сами
+fn reject_unknown_options(unknown: &BTreeMap<String, serde_json::Value>) -> Result<(), ErrorResponse> {
+    match unknown.keys().next() {
+        Some(key) => Err(ErrorResponse {
+            error: format!("unknown config option '{}'", key),
+            code: "INVALID_CONFIG".to_string(),
+        }),
+        None => Ok(()),
     }
 }
 
@@ -101,85 +291,92 @@ async fn test_cube() -> Json<UnfoldResponse> {
             sheet_count: 1,
             total_area: 10000.0,
             bounds: [0.0, 0.0, 100.0, 100.0],
+            component_count: 1,
+            dropped_components: 0,
+            part_count: 1,
+            island_count: 1,
+            distortion: DistortionSummary::default(),
+            island_distortion: Vec::new(),
+            cache_hit: false,
         },
+        components: vec![ComponentInfo {
+            id: 0,
+            face_indices: (0..6).collect(),
+            surface_area: 60000.0,
+            sheet_indices: vec![0],
+        }],
+        parts: vec![PartInfo {
+            id: 0,
+            name: None,
+            face_count: 6,
+            sheet_indices: vec![0],
+        }],
+        islands: Vec::new(),
+        edge_offsets: Vec::new(),
     })
 }
 
 #[instrument(skip_all)]
 async fn unfold_mesh(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<UnfoldRequest>,
-) -> Result<Json<UnfoldResponse>, ErrorResponse> {
+    Query(params): Query<ExportParams>,
+    client: Client,
+    headers: HeaderMap,
+    payload: Result<Json<UnfoldRequest>, JsonRejection>,
+) -> Result<Response, ErrorResponse> {
+    let Json(payload) = payload?;
     info!("Unfolding mesh with {} vertices and {} faces", payload.vertices.len(), payload.faces.len());
 
-    let start_time = Instant::now();
-
-    // Определяем уровень качества
-    let quality_level = match payload.quality.as_deref() {
-        Some("draft") => QualityLevel::Draft,
-        Some("high") => QualityLevel::High,
-        Some("production") => QualityLevel::Production,
-        _ => QualityLevel::Standard,
-    };
-
-    // Создаем конфигурацию
-    let config = UnfoldingConfig {
-        quality_level,
-        ..Default::default()
-    };
-
-    // Создаем запрос на развертку
-    let request = match UnfoldingRequest::from_flat_data(payload.vertices, payload.faces, config) {
-        Ok(req) => req,
-        Err(e) => {
-            return Err(ErrorResponse {
-                error: e.to_string(),
-                code: "INVALID_MESH".to_string(),
-            });
-        }
-    };
+    let export = export::requested_export(&params, &headers)?;
+    let request = build_unfolding_request(payload, &state)?;
+    let sheet_size = request.config.sheet_size;
+    let _slot = state.quota.acquire(&client)?;
+    let response = run_unfold(&state, request).await?;
 
-    // Выполняем развертку
-    let result = match state.unfolding_core.unfold_mesh(&request) {
-        Ok(res) => res,
-        Err(e) => {
-            return Err(ErrorResponse {
-                error: e.to_string(),
-                code: "PROCESSING_ERROR".to_string(),
-            });
-        }
-    };
+    info!("Unfolding completed in {}ms", response.processing_time_ms);
+    match export {
+        Some(export) => export::export_response(export, response.islands, sheet_size, "unfold").await,
+        None => Ok(Json(response).into_response()),
+    }
+}
 
-    let processing_time = start_time.elapsed();
+/// Runs an unfold on a blocking thread; the work is cancelled if the caller's future is dropped
+/// or the request timeout runs out.
+async fn run_unfold(state: &AppState, request: UnfoldingRequest) -> Result<UnfoldResponse, ErrorResponse> {
+    let start_time = Instant::now();
 
-    // Конвертируем результат в формат API
-    let sheets: Vec<Vec<[f64; 2]>> = result.sheets
-        .into_iter()
-        .map(|sheet| {
-            sheet.into_iter()
-                .map(|v| [v.x, v.y])
-                .collect()
+    // Развертка занимает процессор надолго, поэтому выполняем её вне асинхронного рантайма
+    let core = state.unfolding_core.clone();
+    let cancel = CancellationToken::new();
+    let _guard = CancelOnDrop(cancel.clone());
+    let metrics = Arc::clone(&state.metrics);
+    let cache = Arc::clone(&state.cache);
+    // Блокирующий поток не наследует span запроса, переносим его вручную
+    let span = Span::current();
+    let worker = tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        cache.get_or_unfold(&request, &cancel, || {
+            metrics.observe_unfold(&request, || core.unfold_mesh_with(&request, &cancel))
         })
-        .collect();
-
-    let response = UnfoldResponse {
-        sheets,
-        success: true,
-        processing_time_ms: processing_time.as_millis(),
-        metadata: UnfoldingMetadata {
-            sheet_count: result.metadata.sheet_count,
-            total_area: result.metadata.total_area,
-            bounds: result.metadata.bounds,
-        },
+    });
+    let outcome = match state.limits.request_timeout() {
+        // По истечении времени _guard отменит развёртку
+        Some(limit) => tokio::time::timeout(limit, worker).await.map_err(|_| settings::timed_out(limit))?,
+        None => worker.await,
     };
+    let (result, cache_hit) = outcome
+        .map_err(|e| ErrorResponse {
+            error: format!("Unfolding worker stopped unexpectedly: {}", e),
+            code: "PROCESSING_ERROR".to_string(),
+        })??;
 
-    info!("Unfolding completed in {}ms", response.processing_time_ms);
-    Ok(Json(response))
+    Ok(UnfoldResponse::from_result(result, start_time.elapsed(), cache_hit))
 }
 
 #[instrument]
 async fn server_info(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     info!("Server info requested");
+    let (queued, running) = state.jobs.counts();
 
     Json(serde_json::json!({
         "service": "Pepakura Unfolding API",
@@ -188,58 +385,175 @@ async fn server_info(State(state): State<Arc<AppState>>) -> Json<serde_json::Val
             "health": "/health",
             "test_cube": "/test-cube", 
             "unfold": "/unfold",
-            "info": "/info"
+            "upload": "/upload",
+            "jobs": "/jobs",
+            "job": "/jobs/{id}",
+            "job_events": "/jobs/{id}/events",
+            "job_export": "/jobs/{id}/export",
+            "profiles": "/profiles",
+            "info": "/info",
+            "metrics": "/metrics"
+        },
+        "jobs": {
+            "max_concurrent_jobs": state.jobs.config().max_concurrent_jobs,
+            "max_queued_jobs": state.jobs.config().max_queued_jobs,
+            "max_jobs_per_client": state.quota.limit(),
+            "queued": queued,
+            "running": running
         },
+        "limits": state.limits,
         "features": {
-            "max_vertices": 100000,
-            "supported_formats": ["obj", "stl", "ply"],
-            "quality_levels": ["draft", "standard", "high", "production"]
+            "supported_formats": MeshFormat::ALL.map(MeshFormat::name),
+            "export_formats": ExportFormat::ALL.map(ExportFormat::extension),
+            "quality_levels": QualityLevel::ALL.map(|level| level.name()),
+            "profiles": state.profiles.names().collect::<Vec<_>>()
         }
     }))
 }
 
-async fn run_server() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+async fn run_server(settings: Settings) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Инициализируем логгирование
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
+    let filter = tracing_subscriber::EnvFilter::try_new(&settings.log_level)?;
+    match settings.log_format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        LogFormat::Json => tracing_subscriber::fmt().json().with_env_filter(filter).init(),
+    }
 
     info!("Starting Pepakura Unfolding Server...");
     
     // Создаем состояние приложения
+    let metrics = Arc::new(Metrics::default());
+    let cache = Arc::new(settings.cache());
     let state = Arc::new(AppState {
         version: env!("CARGO_PKG_VERSION").to_string(),
         unfolding_core: UnfoldingCore::with_default_config(),
+        jobs: Arc::new(JobQueue::new(settings.job_queue(), Arc::clone(&metrics), Arc::clone(&cache))),
+        profiles: settings.profiles()?,
+        limits: settings.limits(),
+        metrics,
+        cache,
+        quota: Arc::new(settings.quota()),
+        trust_client_id: settings.trust_client_id,
     });
+    state.jobs.spawn_pruning();
     
     // Создаем маршруты
     let app = Router::new()
         .route("/health", get(health))
         .route("/test-cube", get(test_cube))
         .route("/unfold", post(unfold_mesh))
+        .route("/upload", post(upload::upload_mesh).layer(DefaultBodyLimit::max(settings.max_upload_bytes)))
+        .route("/jobs", post(jobs::create_job))
+        .route("/jobs/:id", get(jobs::get_job).delete(jobs::cancel_job))
+        .route("/jobs/:id/events", get(jobs::job_events))
+        .route("/jobs/:id/export", get(export::export_job))
+        .route("/profiles", get(profiles::list_profiles))
         .route("/info", get(server_info))
+        .route("/metrics", get(metrics::metrics))
+        .layer(DefaultBodyLimit::max(settings.max_body_bytes))
+        .layer(middleware::from_fn_with_state(Arc::clone(&state), metrics::track_requests))
+        .layer(middleware::from_fn(request_id::propagate))
         .with_state(state);
     
     // Настраиваем адрес
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    let addr = settings.addr();
     info!("Server listening on {}", addr);
     info!("Available endpoints:");
     info!("  GET  /health     - Health check");
     info!("  GET  /test-cube  - Test cube unfolding");
     info!("  POST /unfold     - Unfold custom mesh");
+    info!("  POST /upload     - Unfold an OBJ/STL/PLY/glTF file");
+    info!("  POST /jobs       - Queue an unfold job");
+    info!("  GET  /jobs/:id   - Job status and result");
+    info!("  GET  /jobs/:id/events - Job progress stream (SSE)");
+    info!("  GET  /jobs/:id/export - Job result as SVG/PDF/DXF/ZIP");
+    info!("  DELETE /jobs/:id - Cancel a job");
+    info!("  GET  /profiles   - Server-side config profiles");
     info!("  GET  /info       - Server information");
+    info!("  GET  /metrics    - Prometheus metrics");
     
     // Запускаем сервер
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Адрес клиента нужен для квоты, когда запрос пришёл не через шлюз
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     
     Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    if let Err(e) = run_server().await {
+fn main() {
+    let settings = match Settings::load(Cli::parse()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Рантайм собираем вручную, чтобы число потоков задавалось настройками
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if let Some(workers) = settings.workers {
+        runtime.worker_threads(workers);
+    }
+    let result = match runtime.build() {
+        Ok(runtime) => runtime.block_on(run_server(settings)),
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = result {
         eprintln!("Server error: {}", e);
         std::process::exit(1);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_map_to_statuses() {
+        let status = |code: &str| {
+            ErrorResponse {
+                error: String::new(),
+                code: code.to_string(),
+            }
+            .status()
+        };
+
+        assert_eq!(settings::timed_out(Duration::from_secs(5)).status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(status("MESH_TOO_LARGE"), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(status("INVALID_CONFIG"), StatusCode::BAD_REQUEST);
+        assert_eq!(status("QUEUE_FULL"), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status("TOO_MANY_JOBS"), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(status("CANCELLED"), StatusCode::CONFLICT);
+        assert_eq!(status("SOMETHING_NEW"), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(ErrorResponse::from(UnfoldingError::Cancelled).status(), StatusCode::CONFLICT);
+
+        let response = status_response("TOO_MANY_JOBS");
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        assert_eq!(status_response("TIMEOUT").status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    fn status_response(code: &str) -> Response {
+        ErrorResponse {
+            error: String::new(),
+            code: code.to_string(),
+        }
+        .into_response()
+    }
+
+    #[test]
+    fn test_misspelled_config_option_rejected() {
+        let body = r#"{"vertices": [], "faces": [], "paper_thickness": 0.2, "paper_thicknes": 0.3}"#;
+        let payload: UnfoldRequest = serde_json::from_str(body).unwrap();
+
+        assert_eq!(payload.config.paper_thickness, Some(0.2));
+        assert_eq!(payload.unknown.keys().collect::<Vec<_>>(), vec!["paper_thicknes"]);
+        let error = reject_unknown_options(&payload.unknown).unwrap_err();
+        assert_eq!(error.code, "INVALID_CONFIG");
+        assert!(error.error.contains("paper_thicknes"));
+
+        let payload: UnfoldRequest = serde_json::from_str(r#"{"vertices": [], "faces": [], "quality": "high"}"#).unwrap();
+        assert!(reject_unknown_options(&payload.unknown).is_ok());
+    }
+}
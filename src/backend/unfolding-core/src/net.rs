@@ -0,0 +1,932 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::distortion::{face_distortion, FaceDistortion};
+use crate::topology::{self, edge_key, EdgeKey};
+use crate::{Mesh, UnfoldingConfig, Vector3};
+
+// Высота клапана относительно длины ребра и сужение его боковых сторон
+const TAB_HEIGHT_RATIO: f64 = 0.2;
+const TAB_TAPER_RATIO: f64 = 0.25;
+// Выше этого угла tan(θ/2) растёт слишком быстро, смещение ограничиваем
+const MAX_COMPENSATED_FOLD_ANGLE: f64 = 170.0;
+
+/// How faces of an island are laid out in the plane.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum UnfoldingMode {
+    /// Every face keeps its exact shape; faces are joined along a spanning tree of hinge edges.
+    #[default]
+    Exact,
+    /// Near-developable regions are flattened as continuous strips (gores) whose edges may
+    /// stretch by at most `max_strain` (0.02 = 2%). Paper thickness is not compensated in strips.
+    Strips { max_strain: f64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum FoldKind {
+    /// Convex edge: the crease points towards the viewer on the printed side.
+    Mountain,
+    /// Concave edge: the crease points away from the printed side.
+    Valley,
+    /// Coplanar faces, nothing to fold.
+    Flat,
+}
+
+/// A face laid out in the plane, vertices in the same order as `Mesh::faces`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlatFace {
+    pub face_index: usize,
+    /// Mesh vertex indices, parallel to `points`.
+    pub vertices: Vec<usize>,
+    pub points: Vec<[f64; 2]>,
+}
+
+/// An edge kept inside an island that has to be folded during assembly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FoldLine {
+    pub faces: [usize; 2],
+    pub vertices: [usize; 2],
+    pub points: [[f64; 2]; 2],
+    pub kind: FoldKind,
+    /// Angle between the face normals in degrees, 0 for a flat edge.
+    pub angle: f64,
+}
+
+/// Glue flap attached to `face_index` along a cut edge, glued under `neighbor_face`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tab {
+    pub face_index: usize,
+    pub neighbor_face: usize,
+    pub vertices: [usize; 2],
+    pub points: Vec<[f64; 2]>,
+}
+
+/// Paper-thickness offset applied to both faces adjacent to a mesh edge.
+///
+/// `offset` is the distance each face is moved inwards from the edge; it is
+/// negative (the face grows) for valley folds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EdgeOffset {
+    pub vertices: [usize; 2],
+    pub faces: [usize; 2],
+    pub kind: FoldKind,
+    pub angle: f64,
+    pub offset: f64,
+}
+
+/// Flattened geometry of one island.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IslandNet {
+    pub faces: Vec<FlatFace>,
+    pub folds: Vec<FoldLine>,
+    pub tabs: Vec<Tab>,
+    /// Distortion of every face, in the same order as `faces`.
+    pub distortion: Vec<FaceDistortion>,
+}
+
+/// Fold classification and thickness offset for every edge shared by two faces.
+pub(crate) struct EdgeFolds {
+    edges: HashMap<EdgeKey, EdgeOffset>,
+}
+
+impl EdgeFolds {
+    pub fn new(mesh: &Mesh, edge_map: &HashMap<EdgeKey, Vec<usize>>, paper_thickness: f64) -> Self {
+        let mut edges = HashMap::new();
+
+        for (&(a, b), faces) in edge_map {
+            // Non-manifold and boundary edges cannot be folded
+            if faces.len() != 2 {
+                continue;
+            }
+            let (first, second) = (faces[0], faces[1]);
+            let normal = mesh.face_normal(first);
+            let other = mesh.face_normal(second);
+            let cos = (dot(&normal, &other)).clamp(-1.0, 1.0);
+            let angle = cos.acos().to_degrees();
+
+            let midpoint = midpoint_3d(&mesh.vertices[a], &mesh.vertices[b]);
+            let towards_second = sub(&face_centroid(mesh, second), &midpoint);
+            let side = dot(&normal, &towards_second);
+
+            let kind = if angle < 1e-6 {
+                FoldKind::Flat
+            } else if side < 0.0 {
+                FoldKind::Mountain
+            } else {
+                FoldKind::Valley
+            };
+
+            // Нейтральный слой бумаги лежит на t/2 под печатной стороной
+            let half_angle = angle.min(MAX_COMPENSATED_FOLD_ANGLE).to_radians() / 2.0;
+            let magnitude = paper_thickness / 2.0 * half_angle.tan();
+            let offset = match kind {
+                FoldKind::Mountain => magnitude,
+                FoldKind::Valley => -magnitude,
+                FoldKind::Flat => 0.0,
+            };
+
+            edges.insert(
+                (a, b),
+                EdgeOffset {
+                    vertices: [a, b],
+                    faces: [first.min(second), first.max(second)],
+                    kind,
+                    angle,
+                    offset,
+                },
+            );
+        }
+
+        Self { edges }
+    }
+
+    fn get(&self, a: usize, b: usize) -> Option<&EdgeOffset> {
+        self.edges.get(&edge_key(a, b))
+    }
+
+    fn offset(&self, a: usize, b: usize) -> f64 {
+        self.get(a, b).map_or(0.0, |edge| edge.offset)
+    }
+
+    /// Offsets sorted by edge, only those that actually move geometry.
+    pub fn applied_offsets(&self) -> Vec<EdgeOffset> {
+        let mut offsets: Vec<EdgeOffset> = self
+            .edges
+            .values()
+            .filter(|edge| edge.offset != 0.0)
+            .cloned()
+            .collect();
+        offsets.sort_by_key(|edge| edge.vertices);
+        offsets
+    }
+}
+
+/// Flattens an edge-connected group of faces according to `config.unfolding_mode`.
+///
+/// Strip mode may split the group, so every returned net comes with the faces it covers.
+/// `defects` are the [`vertex_angle_defects`] of the whole mesh, only read in strip mode.
+pub(crate) fn flatten_region(
+    mesh: &Mesh,
+    face_indices: &[usize],
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    folds: &EdgeFolds,
+    defects: &HashMap<usize, f64>,
+    config: &UnfoldingConfig,
+) -> Vec<(Vec<usize>, IslandNet)> {
+    let UnfoldingMode::Strips { max_strain } = config.unfolding_mode else {
+        let net = flatten_island(mesh, face_indices, edge_map, folds, config);
+        return vec![(face_indices.to_vec(), net)];
+    };
+
+    // Вершина почти развёртываема, если её угловой дефект можно закрыть растяжением не больше max_strain
+    let defect_limit = 2.0 * PI * max_strain;
+    let (developable, rigid): (Vec<usize>, Vec<usize>) = face_indices.iter().partition(|&&face_index| {
+        mesh.faces[face_index]
+            .iter()
+            .all(|&vertex| defects.get(&vertex).is_none_or(|defect| defect.abs() <= defect_limit))
+    });
+
+    let mut regions = Vec::new();
+    for group in topology::connected_subsets(mesh, edge_map, &developable) {
+        regions.extend(flatten_strips(mesh, &group, edge_map, folds, config, max_strain));
+    }
+    for group in topology::connected_subsets(mesh, edge_map, &rigid) {
+        let net = flatten_island(mesh, &group, edge_map, folds, config);
+        regions.push((group, net));
+    }
+    regions
+}
+
+/// Lays the faces of an island out in the plane by walking a breadth-first
+/// spanning tree of its hinge edges, starting from the first face.
+pub(crate) fn flatten_island(
+    mesh: &Mesh,
+    face_indices: &[usize],
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    folds: &EdgeFolds,
+    config: &UnfoldingConfig,
+) -> IslandNet {
+    let members: HashSet<usize> = face_indices.iter().copied().collect();
+    let mut placed: HashMap<usize, Vec<[f64; 2]>> = HashMap::new();
+    let mut order: Vec<usize> = Vec::new();
+    let mut hinges: HashSet<EdgeKey> = HashSet::new();
+    let mut net = IslandNet::default();
+
+    let root = face_indices[0];
+    placed.insert(root, compensated_polygon(mesh, root, folds));
+    order.push(root);
+
+    let mut queue = VecDeque::from([root]);
+    while let Some(parent) = queue.pop_front() {
+        let face = &mesh.faces[parent];
+        for i in 0..face.len() {
+            let (a, b) = (face[i], face[(i + 1) % face.len()]);
+            let Some(neighbours) = edge_map.get(&edge_key(a, b)) else {
+                continue;
+            };
+            if neighbours.len() != 2 {
+                continue;
+            }
+            let child = if neighbours[0] == parent { neighbours[1] } else { neighbours[0] };
+            if !members.contains(&child) || placed.contains_key(&child) {
+                continue;
+            }
+
+            let parent_points = &placed[&parent];
+            let hinge = [parent_points[i], parent_points[(i + 1) % face.len()]];
+            let child_points = attach_to_hinge(
+                mesh,
+                child,
+                compensated_polygon(mesh, child, folds),
+                (a, b),
+                hinge,
+                &centroid_2d(parent_points),
+            );
+
+            if let Some(edge) = folds.get(a, b) {
+                net.folds.push(FoldLine {
+                    faces: [parent, child],
+                    vertices: [a, b],
+                    points: hinge,
+                    kind: edge.kind,
+                    angle: edge.angle,
+                });
+            }
+
+            hinges.insert(edge_key(a, b));
+            placed.insert(child, child_points);
+            order.push(child);
+            queue.push_back(child);
+        }
+    }
+
+    if config.add_tabs {
+        for &face_index in &order {
+            net.tabs.extend(build_tabs(mesh, face_index, &placed[&face_index], edge_map, &hinges, config));
+        }
+    }
+
+    net.faces = order
+        .into_iter()
+        .map(|face_index| FlatFace {
+            face_index,
+            vertices: mesh.faces[face_index].clone(),
+            points: placed.remove(&face_index).unwrap_or_default(),
+        })
+        .collect();
+    net.distortion = net
+        .faces
+        .iter()
+        .map(|face| face_distortion(mesh, face.face_index, &face.points))
+        .collect();
+    net
+}
+
+/// Splits islands whose flattened faces overlap each other.
+///
+/// Faces are checked in placement order; a face that overlaps an already kept
+/// face is cut off together with its subtree. Both the remaining faces and the
+/// cut-off groups are flattened again and checked the same way until nothing overlaps.
+pub(crate) fn repair_overlaps(
+    mesh: &Mesh,
+    face_indices: Vec<usize>,
+    net: IslandNet,
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    folds: &EdgeFolds,
+    config: &UnfoldingConfig,
+) -> Vec<(Vec<usize>, IslandNet)> {
+    let mut pending = vec![(face_indices, net)];
+    let mut repaired = Vec::new();
+
+    while let Some((faces, net)) = pending.pop() {
+        let detached = overlapping_subtrees(&net);
+        if detached.is_empty() {
+            repaired.push((faces, net));
+            continue;
+        }
+
+        let (kept, cut): (Vec<usize>, Vec<usize>) = faces.into_iter().partition(|face| !detached.contains(face));
+        // Оставшиеся грани раскладываются заново и проверяются ещё раз, как и отрезанные
+        let kept_net = flatten_island(mesh, &kept, edge_map, folds, config);
+        pending.push((kept, kept_net));
+
+        for group in topology::connected_subsets(mesh, edge_map, &cut) {
+            let group_net = flatten_island(mesh, &group, edge_map, folds, config);
+            pending.push((group, group_net));
+        }
+    }
+
+    repaired.sort_by_key(|(faces, _)| faces[0]);
+    repaired
+}
+
+// Faces (with their descendants in the placement tree) that overlap an earlier placed face
+pub(crate) fn overlapping_subtrees(net: &IslandNet) -> HashSet<usize> {
+    let parent_of: HashMap<usize, usize> = net.folds.iter().map(|fold| (fold.faces[1], fold.faces[0])).collect();
+    let bounds: Vec<[f64; 4]> = net.faces.iter().map(|face| bounds_2d(&face.points)).collect();
+
+    let extent = bounds.iter().fold([f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY], |acc, b| {
+        [acc[0].min(b[0]), acc[1].min(b[1]), acc[2].max(b[2]), acc[3].max(b[3])]
+    });
+    let epsilon = 1e-9 * (extent[2] - extent[0]).max(extent[3] - extent[1]).max(1.0);
+    let cell_size = bounds
+        .iter()
+        .map(|b| (b[2] - b[0]).max(b[3] - b[1]))
+        .sum::<f64>()
+        .max(epsilon)
+        / bounds.len().max(1) as f64;
+    let cells_of = |b: &[f64; 4]| {
+        let (x0, y0) = ((b[0] / cell_size).floor() as i64, (b[1] / cell_size).floor() as i64);
+        let (x1, y1) = ((b[2] / cell_size).floor() as i64, (b[3] / cell_size).floor() as i64);
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    };
+
+    // Грани кладутся в равномерную сетку, чтобы не сравнивать каждую пару
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    let mut detached = HashSet::new();
+
+    for (slot, face) in net.faces.iter().enumerate() {
+        if parent_of.get(&face.face_index).is_some_and(|parent| detached.contains(parent)) {
+            detached.insert(face.face_index);
+            continue;
+        }
+
+        let overlaps = cells_of(&bounds[slot])
+            .filter_map(|cell| grid.get(&cell))
+            .flatten()
+            .any(|&other| {
+                boxes_overlap(&bounds[slot], &bounds[other], epsilon)
+                    && polygons_overlap(&face.points, &net.faces[other].points, epsilon)
+            });
+        if overlaps {
+            detached.insert(face.face_index);
+            continue;
+        }
+
+        for cell in cells_of(&bounds[slot]) {
+            grid.entry(cell).or_default().push(slot);
+        }
+    }
+
+    detached
+}
+
+/// Outer outline of a net without its tabs, counter-clockwise.
+///
+/// Cut edges are chained by walking around their end vertex through the folds until the
+/// next cut edge; along thickness-compensated folds the outline bridges the small gap
+/// between the two inset faces.
+pub(crate) fn outline(net: &IslandNet) -> Vec<[f64; 2]> {
+    // Грани с обратным обходом разворачиваем, чтобы все шли против часовой стрелки
+    let faces: HashMap<usize, (Vec<usize>, Vec<[f64; 2]>)> = net
+        .faces
+        .iter()
+        .map(|face| {
+            let (mut vertices, mut points) = (face.vertices.clone(), face.points.clone());
+            if signed_area_2d(&points) < 0.0 {
+                vertices.reverse();
+                points.reverse();
+            }
+            (face.face_index, (vertices, points))
+        })
+        .collect();
+    let folds: HashMap<(usize, EdgeKey), usize> = net
+        .folds
+        .iter()
+        .flat_map(|fold| {
+            let key = edge_key(fold.vertices[0], fold.vertices[1]);
+            [((fold.faces[0], key), fold.faces[1]), ((fold.faces[1], key), fold.faces[0])]
+        })
+        .collect();
+    let edge_of = |face: usize, i: usize| {
+        let vertices = &faces[&face].0;
+        edge_key(vertices[i], vertices[(i + 1) % vertices.len()])
+    };
+
+    let mut unvisited: Vec<(usize, usize)> = net
+        .faces
+        .iter()
+        .flat_map(|face| (0..face.vertices.len()).map(move |i| (face.face_index, i)))
+        .filter(|&(face, i)| !folds.contains_key(&(face, edge_of(face, i))))
+        .collect();
+    unvisited.reverse();
+    let mut visited: HashSet<(usize, usize)> = HashSet::new();
+    let limit = unvisited.len() + folds.len() + 1;
+    let mut best: Vec<[f64; 2]> = Vec::new();
+
+    while let Some(start) = unvisited.pop() {
+        if visited.contains(&start) {
+            continue;
+        }
+        let mut loop_points = Vec::new();
+        let (mut face, mut i) = start;
+        while visited.insert((face, i)) && loop_points.len() < limit {
+            let (vertices, points) = &faces[&face];
+            loop_points.push(points[i]);
+
+            // Обходим вершину конца ребра по сгибам, пока не встретим следующий разрез
+            let vertex = vertices[(i + 1) % vertices.len()];
+            (face, i) = (face, (i + 1) % vertices.len());
+            for _ in 0..limit {
+                let Some(&next) = folds.get(&(face, edge_of(face, i))) else {
+                    break;
+                };
+                let Some(slot) = faces.get(&next).and_then(|(v, _)| v.iter().position(|&v| v == vertex)) else {
+                    break;
+                };
+                (face, i) = (next, slot);
+            }
+        }
+        if signed_area_2d(&loop_points).abs() > signed_area_2d(&best).abs() {
+            best = loop_points;
+        }
+    }
+
+    best
+}
+
+pub(crate) fn bounds_2d(points: &[[f64; 2]]) -> [f64; 4] {
+    points.iter().fold([f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY], |b, p| {
+        [b[0].min(p[0]), b[1].min(p[1]), b[2].max(p[0]), b[3].max(p[1])]
+    })
+}
+
+fn boxes_overlap(a: &[f64; 4], b: &[f64; 4], epsilon: f64) -> bool {
+    a[0] < b[2] - epsilon && b[0] < a[2] - epsilon && a[1] < b[3] - epsilon && b[1] < a[3] - epsilon
+}
+
+// Грани разбиваются веером на треугольники и проверяются теоремой о разделяющей оси;
+// касание по ребру или вершине пересечением не считается
+fn polygons_overlap(a: &[[f64; 2]], b: &[[f64; 2]], epsilon: f64) -> bool {
+    let fan = |points: &[[f64; 2]]| -> Vec<[[f64; 2]; 3]> {
+        (1..points.len().saturating_sub(1))
+            .map(|i| [points[0], points[i], points[i + 1]])
+            .collect()
+    };
+    let (fan_a, fan_b) = (fan(a), fan(b));
+
+    fan_a.iter().any(|ta| fan_b.iter().any(|tb| triangles_overlap(ta, tb, epsilon)))
+}
+
+fn triangles_overlap(a: &[[f64; 2]; 3], b: &[[f64; 2]; 3], epsilon: f64) -> bool {
+    for triangle in [a, b] {
+        for i in 0..3 {
+            let (p, q) = (triangle[i], triangle[(i + 1) % 3]);
+            let length = distance_2d(&p, &q);
+            if length == 0.0 {
+                continue;
+            }
+            let axis = [(p[1] - q[1]) / length, (q[0] - p[0]) / length];
+            let project = |t: &[[f64; 2]; 3]| {
+                t.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                    let d = v[0] * axis[0] + v[1] * axis[1];
+                    (min.min(d), max.max(d))
+                })
+            };
+            let ((min_a, max_a), (min_b, max_b)) = (project(a), project(b));
+            if max_a <= min_b + epsilon || max_b <= min_a + epsilon {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Полоса растёт как обычная развёртка, но вершины, уже лежащие рядом на плоскости, сшиваются,
+// если растяжение рёбер грани остаётся в пределах max_strain; иначе по ребру проходит разрез
+fn flatten_strips(
+    mesh: &Mesh,
+    face_indices: &[usize],
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    folds: &EdgeFolds,
+    config: &UnfoldingConfig,
+    max_strain: f64,
+) -> Vec<(Vec<usize>, IslandNet)> {
+    let members: HashSet<usize> = face_indices.iter().copied().collect();
+    let mut strips = Vec::new();
+    let mut visited: HashSet<usize> = HashSet::new();
+
+    for &seed in face_indices {
+        if !visited.insert(seed) {
+            continue;
+        }
+
+        let mut vertex_positions: HashMap<usize, Vec<[f64; 2]>> = HashMap::new();
+        let mut placed: Vec<(usize, Vec<[f64; 2]>)> = Vec::new();
+        let mut distortion = Vec::new();
+
+        let seed_points = local_polygon(mesh, seed);
+        for (&vertex, &point) in mesh.faces[seed].iter().zip(&seed_points) {
+            vertex_positions.entry(vertex).or_default().push(point);
+        }
+        distortion.push(face_distortion(mesh, seed, &seed_points));
+        placed.push((seed, seed_points));
+
+        let mut queue = VecDeque::from([0]);
+        while let Some(slot) = queue.pop_front() {
+            let (parent, parent_points) = placed[slot].clone();
+            let face = &mesh.faces[parent];
+            for i in 0..face.len() {
+                let (a, b) = (face[i], face[(i + 1) % face.len()]);
+                let Some(neighbours) = edge_map.get(&edge_key(a, b)) else {
+                    continue;
+                };
+                if neighbours.len() != 2 {
+                    continue;
+                }
+                let child = if neighbours[0] == parent { neighbours[1] } else { neighbours[0] };
+                if !members.contains(&child) || !visited.insert(child) {
+                    continue;
+                }
+
+                let proposed = attach_to_hinge(
+                    mesh,
+                    child,
+                    local_polygon(mesh, child),
+                    (a, b),
+                    [parent_points[i], parent_points[(i + 1) % face.len()]],
+                    &centroid_2d(&parent_points),
+                );
+                let snapped: Vec<[f64; 2]> = mesh.faces[child]
+                    .iter()
+                    .zip(&proposed)
+                    .map(|(vertex, point)| nearest(vertex_positions.get(vertex), point))
+                    .collect();
+
+                let snapped_distortion = face_distortion(mesh, child, &snapped);
+                let (points, child_distortion) = if snapped_distortion.max_length_strain <= max_strain {
+                    (snapped, snapped_distortion)
+                } else {
+                    let exact = face_distortion(mesh, child, &proposed);
+                    (proposed, exact)
+                };
+
+                for (&vertex, &point) in mesh.faces[child].iter().zip(&points) {
+                    let positions = vertex_positions.entry(vertex).or_default();
+                    if !positions.iter().any(|existing| distance_2d(existing, &point) < 1e-12) {
+                        positions.push(point);
+                    }
+                }
+                distortion.push(child_distortion);
+                placed.push((child, points));
+                queue.push_back(placed.len() - 1);
+            }
+        }
+
+        strips.push(build_strip_net(mesh, placed, distortion, edge_map, folds, config));
+    }
+
+    strips
+}
+
+fn nearest(candidates: Option<&Vec<[f64; 2]>>, point: &[f64; 2]) -> [f64; 2] {
+    candidates
+        .into_iter()
+        .flatten()
+        .min_by(|x, y| distance_2d(x, point).total_cmp(&distance_2d(y, point)))
+        .copied()
+        .unwrap_or(*point)
+}
+
+// Ребро внутри полосы считается сгибом, только если обе грани кладут его концы в одни и те же точки
+fn build_strip_net(
+    mesh: &Mesh,
+    placed: Vec<(usize, Vec<[f64; 2]>)>,
+    distortion: Vec<FaceDistortion>,
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    folds: &EdgeFolds,
+    config: &UnfoldingConfig,
+) -> (Vec<usize>, IslandNet) {
+    let points_of: HashMap<usize, &Vec<[f64; 2]>> = placed.iter().map(|(face, points)| (*face, points)).collect();
+    let position = |face_index: usize, vertex: usize| -> Option<[f64; 2]> {
+        let points = points_of.get(&face_index)?;
+        let slot = mesh.faces[face_index].iter().position(|&v| v == vertex)?;
+        Some(points[slot])
+    };
+
+    let mut net = IslandNet {
+        distortion,
+        ..Default::default()
+    };
+    let mut joined: HashSet<EdgeKey> = HashSet::new();
+
+    for (face_index, points) in &placed {
+        let face = &mesh.faces[*face_index];
+        for i in 0..face.len() {
+            let (a, b) = (face[i], face[(i + 1) % face.len()]);
+            let Some(edge) = folds.get(a, b) else {
+                continue;
+            };
+            let other = if edge.faces[0] == *face_index { edge.faces[1] } else { edge.faces[0] };
+            let coincide = |vertex: usize, point: &[f64; 2]| {
+                position(other, vertex).is_some_and(|p| distance_2d(&p, point) < 1e-9)
+            };
+            if *face_index > other || !coincide(a, &points[i]) || !coincide(b, &points[(i + 1) % face.len()]) {
+                continue;
+            }
+            joined.insert(edge_key(a, b));
+            net.folds.push(FoldLine {
+                faces: [*face_index, other],
+                vertices: [a, b],
+                points: [points[i], points[(i + 1) % face.len()]],
+                kind: edge.kind,
+                angle: edge.angle,
+            });
+        }
+    }
+
+    if config.add_tabs {
+        for (face_index, points) in &placed {
+            net.tabs.extend(build_tabs(mesh, *face_index, points, edge_map, &joined, config));
+        }
+    }
+
+    let mut faces: Vec<usize> = placed.iter().map(|(face_index, _)| *face_index).collect();
+    faces.sort_unstable();
+    net.faces = placed
+        .into_iter()
+        .map(|(face_index, points)| FlatFace {
+            face_index,
+            vertices: mesh.faces[face_index].clone(),
+            points,
+        })
+        .collect();
+    (faces, net)
+}
+
+/// Angle defect `2π - Σ corner angles` of every interior vertex; boundary vertices are skipped.
+pub(crate) fn vertex_angle_defects(mesh: &Mesh, edge_map: &HashMap<EdgeKey, Vec<usize>>) -> HashMap<usize, f64> {
+    let boundary: HashSet<usize> = edge_map
+        .iter()
+        .filter(|(_, faces)| faces.len() == 1)
+        .flat_map(|(&(a, b), _)| [a, b])
+        .collect();
+
+    let mut angle_sums: HashMap<usize, f64> = HashMap::new();
+    for face in &mesh.faces {
+        for i in 0..face.len() {
+            let vertex = face[i];
+            if boundary.contains(&vertex) {
+                continue;
+            }
+            let previous = &mesh.vertices[face[(i + face.len() - 1) % face.len()]];
+            let next = &mesh.vertices[face[(i + 1) % face.len()]];
+            let to_previous = sub(previous, &mesh.vertices[vertex]);
+            let to_next = sub(next, &mesh.vertices[vertex]);
+            let lengths = dot(&to_previous, &to_previous).sqrt() * dot(&to_next, &to_next).sqrt();
+            if lengths == 0.0 {
+                continue;
+            }
+            let angle = (dot(&to_previous, &to_next) / lengths).clamp(-1.0, 1.0).acos();
+            *angle_sums.entry(vertex).or_default() += angle;
+        }
+    }
+
+    angle_sums
+        .into_iter()
+        .map(|(vertex, sum)| (vertex, 2.0 * PI - sum))
+        .collect()
+}
+
+// Клапан ставится на ребро разреза только с одной стороны: у грани с меньшим индексом
+fn build_tabs(
+    mesh: &Mesh,
+    face_index: usize,
+    points: &[[f64; 2]],
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    hinges: &HashSet<EdgeKey>,
+    config: &UnfoldingConfig,
+) -> Vec<Tab> {
+    let face = &mesh.faces[face_index];
+    let centroid = centroid_2d(points);
+    let mut tabs = Vec::new();
+
+    for i in 0..face.len() {
+        let (a, b) = (face[i], face[(i + 1) % face.len()]);
+        let Some(neighbours) = edge_map.get(&edge_key(a, b)) else {
+            continue;
+        };
+        if neighbours.len() != 2 || hinges.contains(&edge_key(a, b)) {
+            continue;
+        }
+        let neighbor_face = if neighbours[0] == face_index { neighbours[1] } else { neighbours[0] };
+        if neighbor_face < face_index {
+            continue;
+        }
+
+        let (p0, p1) = (points[i], points[(i + 1) % face.len()]);
+        let length = distance_2d(&p0, &p1);
+        // Клапан укорачиваем на толщину бумаги, чтобы он не упирался в сгиб соседней грани
+        let height = length * TAB_HEIGHT_RATIO - config.paper_thickness;
+        if length == 0.0 || height <= 0.0 {
+            continue;
+        }
+
+        let direction = [(p1[0] - p0[0]) / length, (p1[1] - p0[1]) / length];
+        let mut outward = [-direction[1], direction[0]];
+        let to_centroid = [centroid[0] - p0[0], centroid[1] - p0[1]];
+        if outward[0] * to_centroid[0] + outward[1] * to_centroid[1] > 0.0 {
+            outward = [-outward[0], -outward[1]];
+        }
+        let taper = length * TAB_TAPER_RATIO;
+
+        tabs.push(Tab {
+            face_index,
+            neighbor_face,
+            vertices: [a, b],
+            points: vec![
+                p0,
+                p1,
+                [
+                    p1[0] + outward[0] * height - direction[0] * taper,
+                    p1[1] + outward[1] * height - direction[1] * taper,
+                ],
+                [
+                    p0[0] + outward[0] * height + direction[0] * taper,
+                    p0[1] + outward[1] * height + direction[1] * taper,
+                ],
+            ],
+        });
+    }
+
+    tabs
+}
+
+// Rotates the child polygon so its hinge edge lies on the parent's one, on the opposite side
+fn attach_to_hinge(
+    mesh: &Mesh,
+    child: usize,
+    local: Vec<[f64; 2]>,
+    (a, b): (usize, usize),
+    hinge: [[f64; 2]; 2],
+    parent_centroid: &[f64; 2],
+) -> Vec<[f64; 2]> {
+    let face = &mesh.faces[child];
+    let position = |vertex: usize| face.iter().position(|&v| v == vertex).unwrap_or(0);
+    let (local_a, local_b) = (local[position(a)], local[position(b)]);
+
+    let target_angle = (hinge[1][1] - hinge[0][1]).atan2(hinge[1][0] - hinge[0][0]);
+    let source_angle = (local_b[1] - local_a[1]).atan2(local_b[0] - local_a[0]);
+    let (sin, cos) = (target_angle - source_angle).sin_cos();
+    let source_mid = [(local_a[0] + local_b[0]) / 2.0, (local_a[1] + local_b[1]) / 2.0];
+    let target_mid = [(hinge[0][0] + hinge[1][0]) / 2.0, (hinge[0][1] + hinge[1][1]) / 2.0];
+
+    let mut points: Vec<[f64; 2]> = local
+        .iter()
+        .map(|p| {
+            let (dx, dy) = (p[0] - source_mid[0], p[1] - source_mid[1]);
+            [target_mid[0] + dx * cos - dy * sin, target_mid[1] + dx * sin + dy * cos]
+        })
+        .collect();
+
+    // Несогласованная ориентация граней кладёт потомка поверх родителя: отражаем его через линию сгиба
+    let side = |p: &[f64; 2]| {
+        (hinge[1][0] - hinge[0][0]) * (p[1] - hinge[0][1]) - (hinge[1][1] - hinge[0][1]) * (p[0] - hinge[0][0])
+    };
+    if side(&centroid_2d(&points)) * side(parent_centroid) > 0.0 {
+        let (ux, uy) = hinge_direction(&hinge);
+        for p in points.iter_mut() {
+            let (dx, dy) = (p[0] - target_mid[0], p[1] - target_mid[1]);
+            let along = dx * ux + dy * uy;
+            *p = [
+                target_mid[0] + 2.0 * along * ux - dx,
+                target_mid[1] + 2.0 * along * uy - dy,
+            ];
+        }
+    }
+
+    points
+}
+
+fn hinge_direction(hinge: &[[f64; 2]; 2]) -> (f64, f64) {
+    let length = distance_2d(&hinge[0], &hinge[1]);
+    if length == 0.0 {
+        return (1.0, 0.0);
+    }
+    ((hinge[1][0] - hinge[0][0]) / length, (hinge[1][1] - hinge[0][1]) / length)
+}
+
+/// Face polygon in its own plane, shrunk or grown along each edge by the thickness offset.
+fn compensated_polygon(mesh: &Mesh, face_index: usize, folds: &EdgeFolds) -> Vec<[f64; 2]> {
+    let face = &mesh.faces[face_index];
+    let local = local_polygon(mesh, face_index);
+    let offsets: Vec<f64> = (0..face.len())
+        .map(|i| folds.offset(face[i], face[(i + 1) % face.len()]))
+        .collect();
+
+    if offsets.iter().all(|&offset| offset == 0.0) {
+        return local;
+    }
+    inset_polygon(&local, &offsets)
+}
+
+// Локальная система координат грани: начало в первой вершине, ось X вдоль первого ребра
+fn local_polygon(mesh: &Mesh, face_index: usize) -> Vec<[f64; 2]> {
+    let face = &mesh.faces[face_index];
+    let origin = &mesh.vertices[face[0]];
+    let normal = mesh.face_normal(face_index);
+
+    let mut x_axis = sub(&mesh.vertices[face[1]], origin);
+    let length = dot(&x_axis, &x_axis).sqrt();
+    if length > 0.0 {
+        x_axis = scale(&x_axis, 1.0 / length);
+    }
+    let y_axis = cross(&normal, &x_axis);
+
+    face.iter()
+        .map(|&vertex| {
+            let relative = sub(&mesh.vertices[vertex], origin);
+            [dot(&relative, &x_axis), dot(&relative, &y_axis)]
+        })
+        .collect()
+}
+
+/// Moves every edge `i` (from point `i` to `i + 1`) inwards by `offsets[i]` and
+/// rebuilds the corners from the intersections of neighbouring edge lines.
+pub(crate) fn inset_polygon(points: &[[f64; 2]], offsets: &[f64]) -> Vec<[f64; 2]> {
+    let count = points.len();
+    let orientation = if signed_area_2d(points) >= 0.0 { 1.0 } else { -1.0 };
+
+    let lines: Vec<([f64; 2], [f64; 2])> = (0..count)
+        .map(|i| {
+            let (p0, p1) = (points[i], points[(i + 1) % count]);
+            let length = distance_2d(&p0, &p1).max(f64::EPSILON);
+            let direction = [(p1[0] - p0[0]) / length, (p1[1] - p0[1]) / length];
+            let inward = [-direction[1] * orientation, direction[0] * orientation];
+            ([p0[0] + inward[0] * offsets[i], p0[1] + inward[1] * offsets[i]], direction)
+        })
+        .collect();
+
+    (0..count)
+        .map(|i| {
+            let (p, r) = lines[(i + count - 1) % count];
+            let (q, s) = lines[i];
+            let denominator = r[0] * s[1] - r[1] * s[0];
+            if denominator.abs() < 1e-12 {
+                // Соседние рёбра коллинеарны: просто сдвигаем вершину
+                return q;
+            }
+            let t = ((q[0] - p[0]) * s[1] - (q[1] - p[1]) * s[0]) / denominator;
+            [p[0] + r[0] * t, p[1] + r[1] * t]
+        })
+        .collect()
+}
+
+pub(crate) fn signed_area_2d(points: &[[f64; 2]]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let j = (i + 1) % points.len();
+        area += points[i][0] * points[j][1] - points[j][0] * points[i][1];
+    }
+    area / 2.0
+}
+
+fn centroid_2d(points: &[[f64; 2]]) -> [f64; 2] {
+    let count = points.len().max(1) as f64;
+    let (x, y) = points.iter().fold((0.0, 0.0), |(x, y), p| (x + p[0], y + p[1]));
+    [x / count, y / count]
+}
+
+pub(crate) fn distance_2d(a: &[f64; 2], b: &[f64; 2]) -> f64 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+pub(crate) fn distance_3d(a: &Vector3, b: &Vector3) -> f64 {
+    let d = sub(b, a);
+    dot(&d, &d).sqrt()
+}
+
+fn face_centroid(mesh: &Mesh, face_index: usize) -> Vector3 {
+    let face = &mesh.faces[face_index];
+    let sum = face.iter().fold(Vector3 { x: 0.0, y: 0.0, z: 0.0 }, |acc, &vertex| {
+        let v = &mesh.vertices[vertex];
+        Vector3 { x: acc.x + v.x, y: acc.y + v.y, z: acc.z + v.z }
+    });
+    scale(&sum, 1.0 / face.len() as f64)
+}
+
+fn midpoint_3d(a: &Vector3, b: &Vector3) -> Vector3 {
+    Vector3 { x: (a.x + b.x) / 2.0, y: (a.y + b.y) / 2.0, z: (a.z + b.z) / 2.0 }
+}
+
+fn sub(a: &Vector3, b: &Vector3) -> Vector3 {
+    Vector3 { x: a.x - b.x, y: a.y - b.y, z: a.z - b.z }
+}
+
+fn scale(v: &Vector3, factor: f64) -> Vector3 {
+    Vector3 { x: v.x * factor, y: v.y * factor, z: v.z * factor }
+}
+
+fn dot(a: &Vector3, b: &Vector3) -> f64 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn cross(a: &Vector3, b: &Vector3) -> Vector3 {
+    Vector3 {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    }
+}
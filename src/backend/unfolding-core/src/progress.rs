@@ -0,0 +1,126 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{IslandInfo, Result, UnfoldingError};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnfoldStage {
+    Validate,
+    Topology,
+    TreeSearch,
+    OverlapRepair,
+    Packing,
+}
+
+impl UnfoldStage {
+    // Доля общего прогресса, которую занимает каждый этап: [начало, конец)
+    fn range(self) -> (f64, f64) {
+        match self {
+            UnfoldStage::Validate => (0.0, 5.0),
+            UnfoldStage::Topology => (5.0, 15.0),
+            UnfoldStage::TreeSearch => (15.0, 60.0),
+            UnfoldStage::OverlapRepair => (60.0, 80.0),
+            UnfoldStage::Packing => (80.0, 100.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnfoldProgress {
+    pub stage: UnfoldStage,
+    /// Completion of the current stage, 0.0..=1.0.
+    pub stage_progress: f64,
+    /// Completion of the whole unfold, 0.0..=100.0.
+    pub percent: f64,
+}
+
+/// Receives progress updates from `UnfoldingCore::unfold_mesh_with` and can ask it to stop.
+///
+/// Both methods are called from the unfolding thread and should return quickly.
+pub trait UnfoldObserver: Sync {
+    fn on_progress(&self, _progress: &UnfoldProgress) {}
+
+    /// Called once per island as soon as its layout is final. `id` is provisional:
+    /// islands are renumbered when the result is assembled.
+    fn on_island(&self, _island: &IslandInfo) {}
+
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Observer that ignores progress and never cancels.
+pub struct NoopObserver;
+
+impl UnfoldObserver for NoopObserver {}
+
+/// Shared flag for cancelling an unfold from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl UnfoldObserver for CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        CancellationToken::is_cancelled(self)
+    }
+}
+
+pub(crate) struct ProgressTracker<'a> {
+    observer: &'a dyn UnfoldObserver,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub fn new(observer: &'a dyn UnfoldObserver) -> Self {
+        Self { observer }
+    }
+
+    /// Reports progress within `stage` and fails with `Cancelled` if the observer asks to stop.
+    pub fn report(&self, stage: UnfoldStage, stage_progress: f64) -> Result<()> {
+        if self.observer.is_cancelled() {
+            return Err(UnfoldingError::Cancelled);
+        }
+        let stage_progress = stage_progress.clamp(0.0, 1.0);
+        let (start, end) = stage.range();
+        self.observer.on_progress(&UnfoldProgress {
+            stage,
+            stage_progress,
+            percent: start + (end - start) * stage_progress,
+        });
+        Ok(())
+    }
+
+    pub fn island(&self, island: &IslandInfo) {
+        self.observer.on_island(island);
+    }
+
+    /// Waits for `duration` in short steps so that cancellation is noticed promptly.
+    pub fn wait(&self, stage: UnfoldStage, duration: Duration) -> Result<()> {
+        const STEP: Duration = Duration::from_millis(10);
+        let start = Instant::now();
+
+        while start.elapsed() < duration {
+            std::thread::sleep(STEP.min(duration.saturating_sub(start.elapsed())));
+            self.report(stage, start.elapsed().as_secs_f64() / duration.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
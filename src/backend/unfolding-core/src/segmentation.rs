@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{topology, Mesh};
+
+/// How faces are split into parts before unfolding. Faces of different parts
+/// never end up in the same island.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum SegmentationMode {
+    /// Treat the whole mesh as a single part.
+    None,
+    /// Use `Mesh::face_parts` (e.g. OBJ groups or materials); falls back to a single part.
+    #[default]
+    MeshParts,
+    /// Grow parts across edges whose dihedral angle (in degrees) does not exceed the limit.
+    Automatic { max_dihedral_angle: f64 },
+}
+
+/// Part assignment for every face plus optional human-readable part names.
+pub(crate) struct PartAssignment {
+    pub face_parts: Vec<usize>,
+    pub names: Vec<Option<String>>,
+}
+
+pub(crate) fn assign_parts(mesh: &Mesh, mode: &SegmentationMode) -> PartAssignment {
+    match (mode, &mesh.face_parts) {
+        (SegmentationMode::MeshParts, Some(face_parts)) => {
+            let part_count = face_parts.iter().max().map_or(0, |&max| max + 1);
+            let names = (0..part_count)
+                .map(|part| mesh.part_names.get(part).cloned())
+                .collect();
+            PartAssignment {
+                face_parts: face_parts.clone(),
+                names,
+            }
+        }
+        (SegmentationMode::Automatic { max_dihedral_angle }, _) => {
+            segment_by_dihedral_angle(mesh, *max_dihedral_angle)
+        }
+        _ => PartAssignment {
+            face_parts: vec![0; mesh.faces.len()],
+            names: vec![None],
+        },
+    }
+}
+
+// Соседние грани попадают в одну часть, если угол между их нормалями не больше порога
+fn segment_by_dihedral_angle(mesh: &Mesh, max_dihedral_angle: f64) -> PartAssignment {
+    let normals: Vec<_> = (0..mesh.faces.len()).map(|face| mesh.face_normal(face)).collect();
+    let min_cos = max_dihedral_angle.to_radians().cos();
+
+    let parts = topology::connected_components_where(mesh, |a, b| {
+        let cos = normals[a].x * normals[b].x + normals[a].y * normals[b].y + normals[a].z * normals[b].z;
+        cos >= min_cos
+    });
+
+    let mut face_parts = vec![0; mesh.faces.len()];
+    for (part, faces) in parts.iter().enumerate() {
+        for &face in faces {
+            face_parts[face] = part;
+        }
+    }
+
+    PartAssignment {
+        face_parts,
+        names: vec![None; parts.len()],
+    }
+}
@@ -0,0 +1,371 @@
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+use pepakura_unfolding_core::{Result, UnfoldObserver, UnfoldingError, UnfoldingRequest, UnfoldingResult};
+
+#[derive(Debug, Default)]
+struct Lru {
+    /// Result and the tick of its last use.
+    entries: HashMap<String, (Arc<UnfoldingResult>, u64)>,
+    /// Keys by last use, least recently used first.
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Lru {
+    fn get(&mut self, key: &str) -> Option<Arc<UnfoldingResult>> {
+        let result = Arc::clone(&self.entries.get(key)?.0);
+        self.insert(key, Arc::clone(&result));
+        Some(result)
+    }
+
+    fn insert(&mut self, key: &str, result: Arc<UnfoldingResult>) {
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.insert(key.to_string(), (result, self.tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.tick, key.to_string());
+    }
+
+    fn evict_to(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// An unfold of one key in progress; requests for the same key wait until it is over.
+#[derive(Debug, Default)]
+struct Flight {
+    done: Mutex<bool>,
+    finished: Condvar,
+}
+
+impl Flight {
+    /// Waits for the unfold to end, giving up with `Cancelled` once `cancel` asks to stop.
+    fn wait(&self, cancel: &dyn UnfoldObserver) -> Result<()> {
+        // Отмену проверяем между короткими ожиданиями, как ProgressTracker::wait
+        const STEP: Duration = Duration::from_millis(10);
+        let mut done = lock(&self.done);
+        while !*done {
+            if cancel.is_cancelled() {
+                return Err(UnfoldingError::Cancelled);
+            }
+            done = self.finished.wait_timeout(done, STEP).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        Ok(())
+    }
+}
+
+/// Unfold of a key owned by this request; waiters are released on drop, even after a panic.
+struct FlightGuard<'a> {
+    cache: &'a ResultCache,
+    key: String,
+    flight: Arc<Flight>,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        lock(&self.cache.in_flight).remove(&self.key);
+        *lock(&self.flight.done) = true;
+        self.flight.finished.notify_all();
+    }
+}
+
+/// Unfold results keyed by a hash of the mesh and config: an in-memory LRU of
+/// `capacity` entries, optionally backed by one JSON file per result in `dir`.
+///
+/// Files on disk are never evicted by the server.
+///
+/// Identical requests that miss at the same time are unfolded once: the others wait for the
+/// first one, or until they are cancelled, and take its result from the cache.
+#[derive(Debug)]
+pub struct ResultCache {
+    capacity: usize,
+    dir: Option<PathBuf>,
+    lru: Mutex<Lru>,
+    /// Keys being unfolded right now.
+    in_flight: Mutex<HashMap<String, Arc<Flight>>>,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize, dir: Option<PathBuf>) -> Self {
+        Self {
+            capacity,
+            dir,
+            lru: Mutex::new(Lru::default()),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.capacity > 0 || self.dir.is_some()
+    }
+
+    /// Stable SHA-256 over the crate version, mesh and config; the version makes results of an
+    /// older unfolder miss.
+    pub fn key(request: &UnfoldingRequest) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update([0]);
+        // Сериализация в JSON детерминирована: в Mesh и UnfoldingConfig нет хеш-таблиц
+        let _ = serde_json::to_writer(&mut hasher, &request.mesh);
+        hasher.update([0]);
+        let _ = serde_json::to_writer(&mut hasher, &request.config);
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Cached result for `request`, or the result of `unfold`, stored on success.
+    /// The flag is `true` for a cache hit. Blocks on disk I/O and on an identical unfold
+    /// already running, until `cancel` asks to stop.
+    pub fn get_or_unfold(
+        &self,
+        request: &UnfoldingRequest,
+        cancel: &dyn UnfoldObserver,
+        unfold: impl FnOnce() -> Result<UnfoldingResult>,
+    ) -> Result<(UnfoldingResult, bool)> {
+        if !self.is_enabled() {
+            return unfold().map(|result| (result, false));
+        }
+
+        let key = Self::key(request);
+        // Второй такой же запрос ждёт первый и берёт его результат из кеша; если первый
+        // завершился ошибкой, следующий считает сам
+        let _computing = loop {
+            if let Some(result) = self.get(&key) {
+                debug!("Cache hit for {}", key);
+                return Ok((result.as_ref().clone(), true));
+            }
+            let mut in_flight = lock(&self.in_flight);
+            match in_flight.get(&key) {
+                Some(flight) => {
+                    let flight = Arc::clone(flight);
+                    drop(in_flight);
+                    flight.wait(cancel)?;
+                }
+                None => {
+                    let flight = Arc::new(Flight::default());
+                    in_flight.insert(key.clone(), Arc::clone(&flight));
+                    break FlightGuard { cache: self, key: key.clone(), flight };
+                }
+            }
+        };
+
+        // Предыдущий мог закончить между проверкой кеша и захватом ключа
+        if let Some(result) = self.get(&key) {
+            debug!("Cache hit for {} after waiting for the same unfold", key);
+            return Ok((result.as_ref().clone(), true));
+        }
+        unfold().map(|result| {
+            self.put(&key, &result);
+            (result, false)
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<Arc<UnfoldingResult>> {
+        if let Some(result) = lock(&self.lru).get(key) {
+            return Some(result);
+        }
+
+        let path = self.dir.as_ref()?.join(format!("{}.json", key));
+        let data = fs::read(&path).ok()?;
+        match serde_json::from_slice::<UnfoldingResult>(&data) {
+            Ok(result) => {
+                let result = Arc::new(result);
+                self.remember(key, Arc::clone(&result));
+                Some(result)
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable cache file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, result: &UnfoldingResult) {
+        self.remember(key, Arc::new(result.clone()));
+
+        if let Some(dir) = &self.dir {
+            // Пишем во временный файл и переименовываем, чтобы соседний процесс не прочитал половину
+            let path = dir.join(format!("{}.json", key));
+            let temp = dir.join(format!("{}.json.tmp", key));
+            let written = fs::create_dir_all(dir)
+                .and_then(|_| fs::File::create(&temp))
+                .and_then(|mut file| {
+                    serde_json::to_writer(&mut file, result)?;
+                    file.flush()
+                })
+                .and_then(|_| fs::rename(&temp, &path));
+            if let Err(e) = written {
+                warn!("Failed to store cached result in {}: {}", path.display(), e);
+                let _ = fs::remove_file(&temp);
+            }
+        }
+    }
+
+    fn remember(&self, key: &str, result: Arc<UnfoldingResult>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut lru = lock(&self.lru);
+        lru.insert(key, result);
+        lru.evict_to(self.capacity);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pepakura_unfolding_core::{
+        CancellationToken, Mesh, NoopObserver, QualityLevel, UnfoldingConfig, UnfoldingCore, Vector3,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Barrier,
+        },
+        thread,
+    };
+
+    fn triangle(size: f64) -> UnfoldingRequest {
+        let vertex = |x, y| Vector3 { x, y, z: 0.0 };
+        UnfoldingRequest {
+            mesh: Mesh::new(vec![vertex(0.0, 0.0), vertex(size, 0.0), vertex(0.0, size)], vec![vec![0, 1, 2]]),
+            config: UnfoldingConfig { quality_level: QualityLevel::Draft, ..Default::default() },
+        }
+    }
+
+    fn unfold(request: &UnfoldingRequest) -> Result<UnfoldingResult> {
+        UnfoldingCore::with_default_config().unfold_mesh(request)
+    }
+
+    #[test]
+    fn test_key_depends_on_mesh_and_config() {
+        let request = triangle(1.0);
+        assert_eq!(ResultCache::key(&request), ResultCache::key(&triangle(1.0)));
+        assert_eq!(ResultCache::key(&request).len(), 64);
+
+        let mut other_config = triangle(1.0);
+        other_config.config.paper_thickness = 0.1;
+        assert_ne!(ResultCache::key(&request), ResultCache::key(&other_config));
+        assert_ne!(ResultCache::key(&request), ResultCache::key(&triangle(2.0)));
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let cache = ResultCache::new(2, None);
+        let requests: Vec<UnfoldingRequest> = (1..=3).map(|size| triangle(size as f64)).collect();
+        let cached = |request: &UnfoldingRequest| cache.get(&ResultCache::key(request)).is_some();
+
+        cache.get_or_unfold(&requests[0], &NoopObserver, || unfold(&requests[0])).unwrap();
+        cache.get_or_unfold(&requests[1], &NoopObserver, || unfold(&requests[1])).unwrap();
+        // Первый запрос использован последним, поэтому вытесняется второй
+        assert!(cache.get_or_unfold(&requests[0], &NoopObserver, || unreachable!()).unwrap().1);
+        cache.get_or_unfold(&requests[2], &NoopObserver, || unfold(&requests[2])).unwrap();
+
+        assert!(cached(&requests[0]));
+        assert!(!cached(&requests[1]));
+        assert!(cached(&requests[2]));
+        assert_eq!(lock(&cache.lru).order.len(), 2);
+    }
+
+    #[test]
+    fn test_results_survive_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = triangle(1.0);
+        let (stored, hit) =
+            ResultCache::new(0, Some(dir.path().to_path_buf())).get_or_unfold(&request, &NoopObserver, || unfold(&request)).unwrap();
+        assert!(!hit);
+        assert!(dir.path().join(format!("{}.json", ResultCache::key(&request))).exists());
+
+        let (loaded, hit) =
+            ResultCache::new(4, Some(dir.path().to_path_buf())).get_or_unfold(&request, &NoopObserver, || unreachable!()).unwrap();
+        assert!(hit);
+        assert_eq!(loaded.metadata.total_area, stored.metadata.total_area);
+        assert_eq!(loaded.islands[0].face_indices, stored.islands[0].face_indices);
+    }
+
+    #[test]
+    fn test_concurrent_misses_unfold_once() {
+        let cache = Arc::new(ResultCache::new(4, None));
+        let unfolds = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let (cache, unfolds) = (Arc::clone(&cache), Arc::clone(&unfolds));
+                thread::spawn(move || {
+                    let request = triangle(1.0);
+                    cache
+                        .get_or_unfold(&request, &NoopObserver, || {
+                            unfolds.fetch_add(1, Ordering::SeqCst);
+                            thread::sleep(Duration::from_millis(50));
+                            unfold(&request)
+                        })
+                        .unwrap()
+                        .1
+                })
+            })
+            .collect();
+        let hits = workers.into_iter().map(|worker| worker.join().unwrap()).filter(|hit| *hit).count();
+
+        assert_eq!(unfolds.load(Ordering::SeqCst), 1);
+        assert_eq!(hits, 3);
+        assert!(lock(&cache.in_flight).is_empty());
+    }
+
+    #[test]
+    fn test_failed_unfold_is_retried_by_the_next_request() {
+        let cache = ResultCache::new(4, None);
+        let request = triangle(1.0);
+
+        assert!(cache.get_or_unfold(&request, &NoopObserver, || Err(UnfoldingError::Cancelled)).is_err());
+        assert!(!cache.get_or_unfold(&request, &NoopObserver, || unfold(&request)).unwrap().1);
+    }
+
+    #[test]
+    fn test_waiting_for_the_same_unfold_stops_on_cancel() {
+        let cache = Arc::new(ResultCache::new(4, None));
+        let (started, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+        let first = {
+            let (cache, started, release) = (Arc::clone(&cache), Arc::clone(&started), Arc::clone(&release));
+            thread::spawn(move || {
+                let request = triangle(1.0);
+                cache.get_or_unfold(&request, &NoopObserver, || {
+                    started.wait();
+                    release.wait();
+                    unfold(&request)
+                })
+            })
+        };
+        started.wait();
+
+        let cancel = CancellationToken::new();
+        let waiter = {
+            let (cache, cancel) = (Arc::clone(&cache), cancel.clone());
+            thread::spawn(move || cache.get_or_unfold(&triangle(1.0), &cancel, || unreachable!()).map(|(_, hit)| hit))
+        };
+        thread::sleep(Duration::from_millis(30));
+        assert!(!waiter.is_finished());
+        cancel.cancel();
+        assert!(matches!(waiter.join().unwrap(), Err(UnfoldingError::Cancelled)));
+
+        release.wait();
+        assert!(!first.join().unwrap().unwrap().1);
+        assert!(lock(&cache.in_flight).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::instrument;
+
+use pepakura_unfolding_core::{
+    export::{export_sheet_archive, export_sheets},
+    ExportFormat, IslandInfo,
+};
+
+use crate::{AppState, ErrorResponse};
+
+/// Printable output requested by the client instead of JSON.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Export {
+    Document(ExportFormat),
+    /// ZIP of one file per sheet in the given format.
+    Archive(ExportFormat),
+}
+
+/// `?format=svg|pdf|dxf|zip|json`; `sheet_format` picks the file type inside a ZIP (SVG by default).
+#[derive(Deserialize, Default)]
+pub(crate) struct ExportParams {
+    pub format: Option<String>,
+    pub sheet_format: Option<String>,
+}
+
+/// Export chosen by the `format` parameter or, when it is absent, by an `Accept` header that
+/// names exactly one printable type. `None` means the usual JSON response.
+pub(crate) fn requested_export(params: &ExportParams, headers: &HeaderMap) -> Result<Option<Export>, ErrorResponse> {
+    let sheet_format = match params.sheet_format.as_deref() {
+        None => ExportFormat::Svg,
+        Some(name) => ExportFormat::from_name(name).ok_or_else(|| unsupported_export(name))?,
+    };
+
+    if let Some(format) = params.format.as_deref() {
+        return match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(None),
+            "zip" => Ok(Some(Export::Archive(sheet_format))),
+            name => ExportFormat::from_name(name)
+                .map(|format| Some(Export::Document(format)))
+                .ok_or_else(|| unsupported_export(name)),
+        };
+    }
+
+    // Браузеры шлют "*/*" и JSON-клиенты "application/json", их не трогаем
+    let accept = headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()).unwrap_or("");
+    let mut matches = accept.split(',').filter_map(|item| {
+        let mime = item.split(';').next().unwrap_or("").trim();
+        if mime.eq_ignore_ascii_case("application/zip") {
+            return Some(Export::Archive(sheet_format));
+        }
+        ExportFormat::ALL
+            .into_iter()
+            .find(|format| mime.eq_ignore_ascii_case(format.content_type()))
+            .map(Export::Document)
+    });
+    Ok(match (matches.next(), matches.next()) {
+        (Some(export), None) => Some(export),
+        _ => None,
+    })
+}
+
+/// Renders `islands` on a blocking thread and returns them as a download named `<file_stem>.<ext>`.
+pub(crate) async fn export_response(
+    export: Export,
+    islands: Vec<IslandInfo>,
+    sheet_size: [f64; 2],
+    file_stem: &str,
+) -> Result<Response, ErrorResponse> {
+    let body = tokio::task::spawn_blocking(move || match export {
+        Export::Document(format) => export_sheets(&islands, sheet_size, format),
+        Export::Archive(format) => export_sheet_archive(&islands, sheet_size, format),
+    })
+    .await
+    .map_err(|e| ErrorResponse {
+        error: format!("Exporter stopped unexpectedly: {}", e),
+        code: "PROCESSING_ERROR".to_string(),
+    })?;
+
+    let (content_type, extension) = match export {
+        Export::Document(format) => (format.content_type(), format.extension()),
+        Export::Archive(_) => ("application/zip", "zip"),
+    };
+    // Имя файла приходит от клиента, оставляем только безопасные символы
+    let file_stem: String = file_stem
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    let file_stem = if file_stem.is_empty() { "unfold" } else { file_stem.as_str() };
+    let disposition = format!("attachment; filename=\"{}.{}\"", file_stem, extension);
+
+    let mut response = body.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    Ok(response)
+}
+
+/// `GET /jobs/{id}/export`: the result of a completed job as SVG (default), PDF, DXF or ZIP.
+#[instrument(skip(state, headers, params))]
+pub async fn export_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<ExportParams>,
+    headers: HeaderMap,
+) -> Result<Response, ErrorResponse> {
+    let export = requested_export(&params, &headers)?.unwrap_or(Export::Document(ExportFormat::Svg));
+    let (islands, sheet_size) = state.jobs.export_source(&id)?;
+    export_response(export, islands, sheet_size, &id).await
+}
+
+fn unsupported_export(name: &str) -> ErrorResponse {
+    let supported: Vec<&str> = ExportFormat::ALL.iter().map(|format| format.extension()).collect();
+    ErrorResponse {
+        error: format!(
+            "Unsupported export format '{}' (supported: json, zip, {})",
+            name,
+            supported.join(", ")
+        ),
+        code: "INVALID_REQUEST".to_string(),
+    }
+}
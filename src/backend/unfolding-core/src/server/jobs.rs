@@ -0,0 +1,776 @@
+use axum::{
+    extract::{rejection::JsonRejection, Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json,
+    },
+};
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Notify, Semaphore,
+};
+use tracing::{info, instrument, warn, Instrument, Span};
+
+use pepakura_unfolding_core::{
+    CancellationToken, IslandInfo, UnfoldObserver, UnfoldProgress, UnfoldStage, UnfoldingCore,
+    UnfoldingError, UnfoldingRequest,
+};
+
+use crate::{
+    server::{
+        cache::ResultCache,
+        metrics::Metrics,
+        quota::{Client, ClientSlot},
+        settings,
+    },
+    AppState, ErrorResponse, UnfoldRequest, UnfoldResponse};
+
+#[derive(Debug, Clone)]
+pub struct JobQueueConfig {
+    /// Jobs unfolded at the same time, each on its own blocking thread.
+    pub max_concurrent_jobs: usize,
+    /// Jobs allowed to wait for a free slot before new submissions are rejected.
+    pub max_queued_jobs: usize,
+    /// How long finished jobs stay available for `GET /jobs/{id}`.
+    pub retention: Duration,
+    /// Running time after which a job is stopped and fails with `TIMEOUT`.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for JobQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_jobs: std::thread::available_parallelism().map_or(2, |n| n.get()),
+            max_queued_jobs: 64,
+            retention: Duration::from_secs(600),
+            timeout: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+// Сколько событий может отстать медленный подписчик, прежде чем пропустит часть прогресса
+const EVENT_BUFFER: usize = 256;
+
+// Прогресс публикуем не чаще, чем раз в процент, иначе ожидания упаковки засыпают клиентов событиями
+const PROGRESS_STEP: f64 = 0.01;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// What `GET /jobs/{id}` returns.
+#[derive(Serialize, Clone)]
+pub struct JobSnapshot {
+    id: String,
+    status: JobStatus,
+    /// Completion of the whole unfold, 0.0..=1.0.
+    progress: f64,
+    stage: Option<UnfoldStage>,
+    created_at: u64,
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    result: Option<UnfoldResponse>,
+    error: Option<ErrorResponse>,
+}
+
+#[derive(Serialize, Clone)]
+struct JobProgress {
+    status: JobStatus,
+    progress: f64,
+    stage: Option<UnfoldStage>,
+}
+
+/// Event streamed by `GET /jobs/{id}/events`.
+#[derive(Clone)]
+enum JobEvent {
+    Progress(JobProgress),
+    /// Layout of one island, sent as soon as the unfold has settled it.
+    Island(Box<IslandInfo>),
+    /// Last event of the stream, named after the final status.
+    Finished(Box<JobSnapshot>),
+}
+
+impl JobEvent {
+    fn to_sse(&self) -> Event {
+        let (name, data) = match self {
+            JobEvent::Progress(progress) => ("progress", serde_json::to_string(progress)),
+            JobEvent::Island(island) => ("island", serde_json::to_string(island)),
+            JobEvent::Finished(snapshot) => (snapshot.status.as_str(), serde_json::to_string(snapshot)),
+        };
+        Event::default().event(name).data(data.unwrap_or_default())
+    }
+}
+
+struct Job {
+    snapshot: JobSnapshot,
+    finished: Option<Instant>,
+    cancel: CancellationToken,
+    /// Wakes a queued job cancelled before it got a slot, so it releases the client's quota.
+    dequeued: Arc<Notify>,
+    events: broadcast::Sender<JobEvent>,
+    /// Islands laid out so far, replayed to subscribers that connect mid-run.
+    islands: Vec<IslandInfo>,
+    /// Kept for exporting the result to printable sheets.
+    sheet_size: [f64; 2],
+}
+
+impl Job {
+    fn progress(&self) -> JobEvent {
+        JobEvent::Progress(JobProgress {
+            status: self.snapshot.status,
+            progress: self.snapshot.progress,
+            stage: self.snapshot.stage,
+        })
+    }
+
+    // Публикуем под блокировкой очереди, чтобы подписка не пропустила событие между снимком и приёмником
+    fn publish(&self, event: JobEvent) {
+        // Ошибка означает лишь отсутствие подписчиков
+        let _ = self.events.send(event);
+    }
+
+    fn finish(&mut self, status: JobStatus) {
+        self.snapshot.status = status;
+        self.snapshot.finished_at = Some(unix_millis());
+        self.finished = Some(Instant::now());
+        self.islands = Vec::new();
+        self.publish(JobEvent::Finished(Box::new(self.snapshot.clone())));
+    }
+}
+
+// Переносит прогресс развёртки в состояние задачи и передаёт отмену из DELETE /jobs/{id}
+struct JobObserver {
+    queue: Arc<JobQueue>,
+    id: String,
+    cancel: CancellationToken,
+}
+
+impl UnfoldObserver for JobObserver {
+    fn on_progress(&self, progress: &UnfoldProgress) {
+        self.queue.update(&self.id, |job| {
+            let fraction = progress.percent / 100.0;
+            if job.snapshot.stage == Some(progress.stage) && fraction - job.snapshot.progress < PROGRESS_STEP {
+                return false;
+            }
+            job.snapshot.progress = fraction;
+            job.snapshot.stage = Some(progress.stage);
+            job.publish(job.progress());
+            true
+        });
+    }
+
+    fn on_island(&self, island: &IslandInfo) {
+        self.queue.update(&self.id, |job| {
+            job.islands.push(island.clone());
+            job.publish(JobEvent::Island(Box::new(island.clone())));
+            true
+        });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+#[derive(Serialize)]
+struct JobAccepted {
+    job_id: String,
+    status: JobStatus,
+    status_url: String,
+}
+
+pub struct JobQueue {
+    config: JobQueueConfig,
+    metrics: Arc<Metrics>,
+    cache: Arc<ResultCache>,
+    jobs: Mutex<HashMap<String, Job>>,
+    slots: Arc<Semaphore>,
+    next_id: AtomicU64,
+}
+
+impl std::fmt::Debug for JobQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobQueue").field("config", &self.config).finish_non_exhaustive()
+    }
+}
+
+impl JobQueue {
+    pub fn new(config: JobQueueConfig, metrics: Arc<Metrics>, cache: Arc<ResultCache>) -> Self {
+        Self {
+            metrics,
+            cache,
+            slots: Arc::new(Semaphore::new(config.max_concurrent_jobs)),
+            config,
+            jobs: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn config(&self) -> &JobQueueConfig {
+        &self.config
+    }
+
+    /// Number of (queued, running) jobs.
+    pub fn counts(&self) -> (usize, usize) {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.values().fold((0, 0), |(queued, running), job| match job.snapshot.status {
+            JobStatus::Queued => (queued + 1, running),
+            JobStatus::Running => (queued, running + 1),
+            _ => (queued, running),
+        })
+    }
+
+    /// Registers a job and schedules it; the unfold itself runs on a blocking thread
+    /// once one of `max_concurrent_jobs` slots is free.
+    pub fn submit(
+        self: &Arc<Self>,
+        core: UnfoldingCore,
+        request: UnfoldingRequest,
+        slot: ClientSlot,
+    ) -> Result<String, ErrorResponse> {
+        let id = {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            // Старые завершённые задачи удаляем и при каждой новой отправке
+            self.prune_locked(&mut jobs);
+
+            let queued = jobs.values().filter(|job| job.snapshot.status == JobStatus::Queued).count();
+            if queued >= self.config.max_queued_jobs {
+                return Err(ErrorResponse {
+                    error: format!("Job queue is full ({} jobs waiting)", queued),
+                    code: "QUEUE_FULL".to_string(),
+                });
+            }
+
+            let id = format!("job-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+            jobs.insert(
+                id.clone(),
+                Job {
+                    snapshot: JobSnapshot {
+                        id: id.clone(),
+                        status: JobStatus::Queued,
+                        progress: 0.0,
+                        stage: None,
+                        created_at: unix_millis(),
+                        started_at: None,
+                        finished_at: None,
+                        result: None,
+                        error: None,
+                    },
+                    finished: None,
+                    cancel: CancellationToken::new(),
+                    dequeued: Arc::new(Notify::new()),
+                    events: broadcast::channel(EVENT_BUFFER).0,
+                    islands: Vec::new(),
+                    sheet_size: request.config.sheet_size,
+                },
+            );
+            id
+        };
+
+        let queue = Arc::clone(self);
+        let job_id = id.clone();
+        // Задача наследует span запроса, так что логи задания несут его request_id
+        tokio::spawn(async move { queue.run(job_id, core, request, slot).await }.in_current_span());
+
+        Ok(id)
+    }
+
+    /// Holds the client's `slot` until the job is over.
+    async fn run(self: Arc<Self>, id: String, core: UnfoldingCore, request: UnfoldingRequest, _slot: ClientSlot) {
+        let dequeued = {
+            let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(job) = jobs.get(&id) else {
+                return;
+            };
+            Arc::clone(&job.dequeued)
+        };
+        // Отменённая в очереди задача выходит сразу и освобождает слот клиента, не дожидаясь исполнителя
+        let _permit = tokio::select! {
+            permit = Arc::clone(&self.slots).acquire_owned() => match permit {
+                Ok(permit) => permit,
+                Err(_) => return,
+            },
+            () = dequeued.notified() => return,
+        };
+        let mut cancel = None;
+        self.update(&id, |job| {
+            if job.snapshot.status != JobStatus::Queued {
+                return false;
+            }
+            job.snapshot.status = JobStatus::Running;
+            job.snapshot.started_at = Some(unix_millis());
+            cancel = Some(job.cancel.clone());
+            job.publish(job.progress());
+            true
+        });
+        let Some(cancel) = cancel else {
+            return;
+        };
+
+        info!("Job {} started", id);
+        // Таймаут отменяет развёртку тем же токеном, что и DELETE /jobs/{id}
+        let timer = self.config.timeout.map(|timeout| {
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                cancel.cancel();
+            })
+        });
+        let observer = JobObserver {
+            queue: Arc::clone(&self),
+            id: id.clone(),
+            cancel,
+        };
+        let metrics = Arc::clone(&self.metrics);
+        let cache = Arc::clone(&self.cache);
+        let span = Span::current();
+        let outcome = tokio::task::spawn_blocking(move || {
+            let _entered = span.enter();
+            let start_time = Instant::now();
+            cache
+                .get_or_unfold(&request, &observer, || {
+                    metrics.observe_unfold(&request, || core.unfold_mesh_with(&request, &observer))
+                })
+                .map(|(result, cache_hit)| UnfoldResponse::from_result(result, start_time.elapsed(), cache_hit))
+        })
+        .await;
+        if let Some(timer) = timer {
+            timer.abort();
+        }
+
+        self.update(&id, |job| {
+            // Отменённая во время выполнения задача остаётся отменённой, результат отбрасываем
+            if job.snapshot.status == JobStatus::Cancelled {
+                return false;
+            }
+            let status = match outcome {
+                Ok(Ok(response)) => {
+                    job.snapshot.progress = 1.0;
+                    job.snapshot.result = Some(response);
+                    JobStatus::Completed
+                }
+                // Отмену через DELETE отсекли выше, значит сработал таймаут
+                Ok(Err(UnfoldingError::Cancelled)) => {
+                    job.snapshot.error = Some(settings::timed_out(self.config.timeout.unwrap_or_default()));
+                    JobStatus::Failed
+                }
+                Ok(Err(e)) => {
+                    job.snapshot.error = Some(ErrorResponse::from(e));
+                    JobStatus::Failed
+                }
+                Err(e) => {
+                    warn!("Job {} worker panicked: {}", id, e);
+                    job.snapshot.error = Some(ErrorResponse {
+                        error: "Unfolding worker stopped unexpectedly".to_string(),
+                        code: "PROCESSING_ERROR".to_string(),
+                    });
+                    JobStatus::Failed
+                }
+            };
+            job.finish(status);
+            true
+        });
+        info!("Job {} finished", id);
+    }
+
+    /// Drops finished jobs older than `retention` on a timer, so that the results of an idle
+    /// server do not stay in memory. The timer stops once the queue is dropped.
+    pub fn spawn_pruning(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let queue = Arc::downgrade(self);
+        let period = (self.config.retention / 2).clamp(Duration::from_secs(1), Duration::from_secs(60));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(queue) = queue.upgrade() else {
+                    break;
+                };
+                queue.prune();
+            }
+        })
+    }
+
+    /// Removes finished jobs whose retention has run out.
+    pub fn prune(&self) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        self.prune_locked(&mut jobs);
+    }
+
+    fn prune_locked(&self, jobs: &mut HashMap<String, Job>) {
+        let retention = self.config.retention;
+        jobs.retain(|_, job| job.finished.is_none_or(|at| at.elapsed() < retention));
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobSnapshot> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.get(id).map(|job| job.snapshot.clone())
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<JobSnapshot, ErrorResponse> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs.get_mut(id).ok_or_else(|| job_not_found(id))?;
+
+        if job.snapshot.status.is_finished() {
+            return Err(ErrorResponse {
+                error: format!("Job {} is already {}", id, job.snapshot.status.as_str()),
+                code: "JOB_FINISHED".to_string(),
+            });
+        }
+
+        // Выполняющаяся развёртка заметит отмену на ближайшей проверке и освободит слот;
+        // ждущая в очереди просыпается сразу. notify_one запоминает сигнал, даже если run ещё не ждёт
+        job.cancel.cancel();
+        job.dequeued.notify_one();
+        job.finish(JobStatus::Cancelled);
+        Ok(job.snapshot.clone())
+    }
+
+    /// Islands and sheet size of a completed job, for `GET /jobs/{id}/export`.
+    pub fn export_source(&self, id: &str) -> Result<(Vec<IslandInfo>, [f64; 2]), ErrorResponse> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs.get(id).ok_or_else(|| job_not_found(id))?;
+        match &job.snapshot.result {
+            Some(result) => Ok((result.islands.clone(), job.sheet_size)),
+            None => Err(ErrorResponse {
+                error: format!("Job {} is {}, only completed jobs can be exported", id, job.snapshot.status.as_str()),
+                code: "JOB_NOT_COMPLETED".to_string(),
+            }),
+        }
+    }
+
+    /// Events already due to a new subscriber, plus a receiver for the rest
+    /// (`None` once the job has finished).
+    fn subscribe(&self, id: &str) -> Option<(Vec<JobEvent>, Option<broadcast::Receiver<JobEvent>>)> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs.get(id)?;
+
+        if job.snapshot.status.is_finished() {
+            return Some((vec![JobEvent::Finished(Box::new(job.snapshot.clone()))], None));
+        }
+        let mut pending = vec![job.progress()];
+        pending.extend(job.islands.iter().cloned().map(|island| JobEvent::Island(Box::new(island))));
+        Some((pending, Some(job.events.subscribe())))
+    }
+
+    fn update(&self, id: &str, apply: impl FnOnce(&mut Job) -> bool) -> bool {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.get_mut(id).is_some_and(apply)
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+fn job_not_found(id: &str) -> ErrorResponse {
+    ErrorResponse {
+        error: format!("Job {} not found", id),
+        code: "JOB_NOT_FOUND".to_string(),
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn create_job(
+    State(state): State<Arc<AppState>>,
+    client: Client,
+    payload: Result<Json<UnfoldRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let Json(payload) = payload?;
+    let request = crate::build_unfolding_request(payload, &state)?;
+    submit(&state, state.quota.acquire(&client)?, request)
+}
+
+/// Queues `request` under the client's `slot` and answers `202 Accepted` with the job's status URL.
+pub(crate) fn submit(
+    state: &AppState,
+    slot: ClientSlot,
+    request: UnfoldingRequest,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let job_id = state.jobs.submit(state.unfolding_core.clone(), request, slot)?;
+    info!("Job {} queued", job_id);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(JobAccepted {
+            status_url: format!("/jobs/{}", job_id),
+            job_id,
+            status: JobStatus::Queued,
+        }),
+    ))
+}
+
+#[instrument(skip(state))]
+pub async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobSnapshot>, ErrorResponse> {
+    state.jobs.get(&id).map(Json).ok_or_else(|| job_not_found(&id))
+}
+
+/// Streams a job as Server-Sent Events: `progress`, one `island` per laid-out island,
+/// and finally `completed`, `failed` or `cancelled` with the full job snapshot.
+#[instrument(skip(state))]
+pub async fn job_events(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ErrorResponse> {
+    let (pending, receiver) = state.jobs.subscribe(&id).ok_or_else(|| job_not_found(&id))?;
+
+    let events = stream::unfold((pending.into_iter(), receiver), |(mut pending, mut receiver)| async move {
+        let event = match pending.next() {
+            Some(event) => event,
+            None => loop {
+                match receiver.as_mut()?.recv().await {
+                    Ok(event) => break event,
+                    // Отставший подписчик теряет лишь промежуточные события, итоговое придёт последним
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+        };
+        if matches!(event, JobEvent::Finished(_)) {
+            receiver = None;
+        }
+        Some((Ok(event.to_sse()), (pending, receiver)))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[instrument(skip(state))]
+pub async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobSnapshot>, ErrorResponse> {
+    let snapshot = state.jobs.cancel(&id)?;
+    info!("Job {} cancelled", id);
+    Ok(Json(snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::quota::ClientQuota;
+    use pepakura_unfolding_core::{Mesh, ObjGrouping, QualityLevel, UnfoldingConfig};
+
+    const CUBE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+                        f 1 2 3 4\nf 5 8 7 6\nf 1 5 6 2\nf 3 7 8 4\nf 1 4 8 5\nf 2 6 7 3\n";
+
+    fn queue(config: JobQueueConfig) -> Arc<JobQueue> {
+        Arc::new(JobQueue::new(config, Arc::new(Metrics::default()), Arc::new(ResultCache::new(0, None))))
+    }
+
+    // Production ждёт на упаковке 500 мс, этого хватает, чтобы застать задачу выполняющейся
+    fn cube(quality_level: QualityLevel) -> UnfoldingRequest {
+        UnfoldingRequest {
+            mesh: Mesh::from_obj(CUBE, ObjGrouping::None).unwrap(),
+            config: UnfoldingConfig {
+                quality_level,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn submit(queue: &Arc<JobQueue>, request: UnfoldingRequest) -> String {
+        let slot = Arc::new(ClientQuota::new(0)).acquire(&Client("test".to_string())).unwrap();
+        queue.submit(UnfoldingCore::with_default_config(), request, slot).unwrap()
+    }
+
+    async fn wait_for(queue: &JobQueue, id: &str, status: JobStatus) -> JobSnapshot {
+        for _ in 0..500 {
+            let snapshot = queue.get(id).unwrap();
+            if snapshot.status == status {
+                return snapshot;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("job {} never became {:?}", id, status);
+    }
+
+    #[tokio::test]
+    async fn test_job_completes() {
+        let queue = queue(JobQueueConfig::default());
+        let id = submit(&queue, cube(QualityLevel::Draft));
+
+        let snapshot = wait_for(&queue, &id, JobStatus::Completed).await;
+        assert_eq!(snapshot.progress, 1.0);
+        assert!(snapshot.started_at.is_some() && snapshot.finished_at.is_some());
+        assert_eq!(snapshot.result.unwrap().islands.len(), 1);
+        assert!(queue.export_source(&id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_mesh_fails_job() {
+        let queue = queue(JobQueueConfig::default());
+        let mut request = cube(QualityLevel::Draft);
+        request.mesh.faces[0][0] = 100;
+        let id = submit(&queue, request);
+
+        let snapshot = wait_for(&queue, &id, JobStatus::Failed).await;
+        assert_eq!(snapshot.error.unwrap().code, "INVALID_MESH");
+        assert_eq!(queue.export_source(&id).unwrap_err().code, "JOB_NOT_COMPLETED");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_queued_and_running_jobs() {
+        let queue = queue(JobQueueConfig {
+            max_concurrent_jobs: 1,
+            ..Default::default()
+        });
+        let running = submit(&queue, cube(QualityLevel::Production));
+        let queued = submit(&queue, cube(QualityLevel::Draft));
+        wait_for(&queue, &running, JobStatus::Running).await;
+        assert_eq!(queue.get(&queued).unwrap().status, JobStatus::Queued);
+        assert_eq!(queue.counts(), (1, 1));
+
+        assert_eq!(queue.cancel(&queued).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(queue.cancel(&running).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(queue.cancel(&running).err().unwrap().code, "JOB_FINISHED");
+        assert_eq!(queue.cancel("job-0").err().unwrap().code, "JOB_NOT_FOUND");
+
+        // Развёртка, заметившая отмену, не перезаписывает статус
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(queue.get(&running).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(queue.counts(), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_queued_job_releases_client_slot() {
+        let queue = queue(JobQueueConfig::default());
+        // Все исполнители заняты, задача остаётся в очереди
+        let _busy = Arc::clone(&queue.slots).acquire_many_owned(queue.config.max_concurrent_jobs as u32).await;
+        let quota = Arc::new(ClientQuota::new(1));
+        let client = Client("test".to_string());
+        let slot = quota.acquire(&client).unwrap();
+        let id = queue.submit(UnfoldingCore::with_default_config(), cube(QualityLevel::Draft), slot).unwrap();
+        assert_eq!(quota.acquire(&client).err().unwrap().code, "TOO_MANY_JOBS");
+
+        queue.cancel(&id).unwrap();
+        for _ in 0..100 {
+            if quota.acquire(&client).is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("cancelled job {} kept the client's slot", id);
+    }
+
+    #[tokio::test]
+    async fn test_job_timeout_fails_with_timeout() {
+        let queue = queue(JobQueueConfig {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let id = submit(&queue, cube(QualityLevel::Production));
+
+        let snapshot = wait_for(&queue, &id, JobStatus::Failed).await;
+        assert_eq!(snapshot.error.unwrap().code, "TIMEOUT");
+    }
+
+    #[tokio::test]
+    async fn test_queue_full_rejects_submissions() {
+        let queue = queue(JobQueueConfig {
+            max_concurrent_jobs: 1,
+            max_queued_jobs: 1,
+            ..Default::default()
+        });
+        let running = submit(&queue, cube(QualityLevel::Production));
+        wait_for(&queue, &running, JobStatus::Running).await;
+        submit(&queue, cube(QualityLevel::Draft));
+
+        let slot = Arc::new(ClientQuota::new(0)).acquire(&Client("test".to_string())).unwrap();
+        let rejected = queue.submit(UnfoldingCore::with_default_config(), cube(QualityLevel::Draft), slot);
+        assert_eq!(rejected.unwrap_err().code, "QUEUE_FULL");
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_receives_progress_then_final_event() {
+        let queue = queue(JobQueueConfig::default());
+        let id = submit(&queue, cube(QualityLevel::Standard));
+        let (pending, receiver) = queue.subscribe(&id).unwrap();
+        let mut receiver = receiver.unwrap();
+
+        let mut events = pending;
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+            let finished = matches!(event, JobEvent::Finished(_));
+            events.push(event);
+            if finished {
+                break;
+            }
+        }
+
+        let stages: Vec<UnfoldStage> = events
+            .iter()
+            .filter_map(|event| match event {
+                JobEvent::Progress(progress) => progress.stage,
+                _ => None,
+            })
+            .collect();
+        assert!(stages.contains(&UnfoldStage::Validate) && stages.contains(&UnfoldStage::Packing));
+        assert!(events.iter().any(|event| matches!(event, JobEvent::Island(_))));
+        match events.last() {
+            Some(JobEvent::Finished(snapshot)) => assert_eq!(snapshot.status, JobStatus::Completed),
+            _ => panic!("the stream must end with the final snapshot"),
+        }
+
+        // После завершения подписчик сразу получает итоговое событие, без приёмника
+        let (replayed, receiver) = queue.subscribe(&id).unwrap();
+        assert!(receiver.is_none());
+        assert!(matches!(replayed.as_slice(), [JobEvent::Finished(_)]));
+    }
+
+    #[tokio::test]
+    async fn test_finished_jobs_are_pruned_without_new_submissions() {
+        let queue = queue(JobQueueConfig {
+            retention: Duration::from_millis(300),
+            ..Default::default()
+        });
+        let id = submit(&queue, cube(QualityLevel::Draft));
+        wait_for(&queue, &id, JobStatus::Completed).await;
+
+        // Запас на случай, если опрос в wait_for заметил завершение с опозданием
+        queue.prune();
+        assert!(queue.get(&id).is_some());
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        queue.prune();
+        assert!(queue.get(&id).is_none());
+    }
+}
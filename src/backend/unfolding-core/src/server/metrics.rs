@@ -0,0 +1,289 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use pepakura_unfolding_core::{Result, UnfoldingError, UnfoldingRequest, UnfoldingResult};
+
+use crate::AppState;
+
+// Границы корзин гистограмм, как в клиентах Prometheus по умолчанию
+const SECONDS_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+const SIZE_BUCKETS: &[f64] = &[100.0, 1_000.0, 10_000.0, 50_000.0, 100_000.0, 500_000.0, 1_000_000.0];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    buckets: &'static [f64],
+    /// Observations per bucket (not cumulative); the last slot is `+Inf`.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self.buckets.iter().position(|&bound| value <= bound).unwrap_or(self.buckets.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        let bounds = self.buckets.iter().map(f64::to_string).chain(["+Inf".to_string()]);
+        for (bound, count) in bounds.zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, cumulative);
+        }
+        let labels = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Debug)]
+struct Registry {
+    requests: BTreeMap<(String, String, u16), u64>,
+    request_seconds: BTreeMap<(String, String), Histogram>,
+    unfold_seconds: BTreeMap<&'static str, Histogram>,
+    mesh_vertices: Histogram,
+    mesh_faces: Histogram,
+    errors: BTreeMap<&'static str, u64>,
+}
+
+/// Counters and histograms served by `GET /metrics` in the Prometheus text format.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            registry: Mutex::new(Registry {
+                requests: BTreeMap::new(),
+                request_seconds: BTreeMap::new(),
+                unfold_seconds: BTreeMap::new(),
+                mesh_vertices: Histogram::new(SIZE_BUCKETS),
+                mesh_faces: Histogram::new(SIZE_BUCKETS),
+                errors: BTreeMap::new(),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut registry = self.registry();
+        *registry.requests.entry((method.to_string(), route.to_string(), status)).or_default() += 1;
+        registry
+            .request_seconds
+            .entry((method.to_string(), route.to_string()))
+            .or_insert_with(|| Histogram::new(SECONDS_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Runs `unfold` for `request`, recording the mesh size, the duration and any error.
+    pub fn observe_unfold(
+        &self,
+        request: &UnfoldingRequest,
+        unfold: impl FnOnce() -> Result<UnfoldingResult>,
+    ) -> Result<UnfoldingResult> {
+        let start_time = Instant::now();
+        let result = unfold();
+        let elapsed = start_time.elapsed();
+
+        let mut registry = self.registry();
+        registry.mesh_vertices.observe(request.mesh.vertices.len() as f64);
+        registry.mesh_faces.observe(request.mesh.faces.len() as f64);
+        registry
+            .unfold_seconds
+            .entry(request.config.quality_level.name())
+            .or_insert_with(|| Histogram::new(SECONDS_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+        if let Err(e) = &result {
+            *registry.errors.entry(error_kind(e)).or_default() += 1;
+        }
+        result
+    }
+
+    /// Prometheus text exposition; job gauges are sampled by the caller at scrape time.
+    pub fn render(&self, queued_jobs: usize, running_jobs: usize) -> String {
+        let registry = self.registry();
+        let mut out = String::new();
+
+        out.push_str("# HELP unfold_http_requests_total HTTP requests by method, route and status.\n");
+        out.push_str("# TYPE unfold_http_requests_total counter\n");
+        for ((method, route, status), count) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "unfold_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(method),
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        out.push_str("# HELP unfold_http_request_duration_seconds HTTP request latency by method and route.\n");
+        out.push_str("# TYPE unfold_http_request_duration_seconds histogram\n");
+        for ((method, route), histogram) in &registry.request_seconds {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            histogram.render(&mut out, "unfold_http_request_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP unfold_duration_seconds Time spent unfolding, by quality level.\n");
+        out.push_str("# TYPE unfold_duration_seconds histogram\n");
+        for (quality, histogram) in &registry.unfold_seconds {
+            histogram.render(&mut out, "unfold_duration_seconds", &format!("quality=\"{}\"", quality));
+        }
+
+        out.push_str("# HELP unfold_mesh_vertices Vertex count of unfolded meshes.\n");
+        out.push_str("# TYPE unfold_mesh_vertices histogram\n");
+        registry.mesh_vertices.render(&mut out, "unfold_mesh_vertices", "");
+        out.push_str("# HELP unfold_mesh_faces Face count of unfolded meshes.\n");
+        out.push_str("# TYPE unfold_mesh_faces histogram\n");
+        registry.mesh_faces.render(&mut out, "unfold_mesh_faces", "");
+
+        out.push_str("# HELP unfold_errors_total Failed unfolds by error kind.\n");
+        out.push_str("# TYPE unfold_errors_total counter\n");
+        for (kind, count) in &registry.errors {
+            let _ = writeln!(out, "unfold_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        out.push_str("# HELP unfold_jobs_queued Jobs waiting for a free slot.\n");
+        out.push_str("# TYPE unfold_jobs_queued gauge\n");
+        let _ = writeln!(out, "unfold_jobs_queued {}", queued_jobs);
+        out.push_str("# HELP unfold_jobs_running Jobs being unfolded right now.\n");
+        out.push_str("# TYPE unfold_jobs_running gauge\n");
+        let _ = writeln!(out, "unfold_jobs_running {}", running_jobs);
+        out
+    }
+}
+
+fn error_kind(error: &UnfoldingError) -> &'static str {
+    match error {
+        UnfoldingError::InvalidMesh(_) => "invalid_mesh",
+        UnfoldingError::ProcessingFailed(_) => "processing_failed",
+        UnfoldingError::MathError(_) => "math_error",
+        UnfoldingError::InvalidConfig(_) => "invalid_config",
+        UnfoldingError::Cancelled => "cancelled",
+        UnfoldingError::MeshTooLarge(_) => "mesh_too_large",
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Middleware counting every request under its route pattern (`/jobs/:id`, not the concrete path).
+pub async fn track_requests(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    // Несовпавшие пути сводим в одну метку, чтобы сканеры не раздували число рядов
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let method = request.method().clone();
+    let start_time = Instant::now();
+
+    let response = next.run(request).await;
+    state
+        .metrics
+        .record_request(method.as_str(), &route, response.status().as_u16(), start_time.elapsed());
+    response
+}
+
+/// `GET /metrics`: Prometheus scrape endpoint.
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let (queued, running) = state.jobs.counts();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(queued, running),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pepakura_unfolding_core::{Mesh, UnfoldingConfig, UnfoldingCore, Vector3};
+
+    fn triangle() -> UnfoldingRequest {
+        let vertex = |x, y| Vector3 { x, y, z: 0.0 };
+        UnfoldingRequest {
+            mesh: Mesh::new(vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)], vec![vec![0, 1, 2]]),
+            config: UnfoldingConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let metrics = Metrics::default();
+        metrics.record_request("GET", "/health", 200, Duration::from_millis(3));
+        metrics.record_request("GET", "/health", 200, Duration::from_millis(300));
+        metrics.record_request("POST", "/odd\"route\\\n", 404, Duration::from_secs(200));
+        let request = triangle();
+        let core = UnfoldingCore::with_default_config();
+        assert!(metrics.observe_unfold(&request, || core.unfold_mesh(&request)).is_ok());
+        assert!(metrics.observe_unfold(&request, || Err(UnfoldingError::Cancelled)).is_err());
+
+        let text = metrics.render(2, 1);
+
+        for (family, kind) in [
+            ("unfold_http_requests_total", "counter"),
+            ("unfold_http_request_duration_seconds", "histogram"),
+            ("unfold_duration_seconds", "histogram"),
+            ("unfold_mesh_vertices", "histogram"),
+            ("unfold_mesh_faces", "histogram"),
+            ("unfold_errors_total", "counter"),
+            ("unfold_jobs_queued", "gauge"),
+            ("unfold_jobs_running", "gauge"),
+        ] {
+            assert!(text.contains(&format!("# TYPE {} {}\n", family, kind)), "{} is missing", family);
+        }
+        assert!(text.contains("unfold_http_requests_total{method=\"GET\",route=\"/health\",status=\"200\"} 2\n"));
+        assert!(text.contains("route=\"/odd\\\"route\\\\\\n\",status=\"404\"} 1\n"));
+        assert!(text.contains("unfold_errors_total{kind=\"cancelled\"} 1\n"));
+        assert!(text.contains("unfold_jobs_queued 2\n") && text.contains("unfold_jobs_running 1\n"));
+        assert!(text.contains("unfold_duration_seconds_count{quality=\"standard\"} 2\n"));
+
+        // Корзины каждого ряда накопительные: не убывают и заканчиваются на _count
+        let mut series: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+        for line in text.lines().filter(|line| line.contains("_bucket{")) {
+            let (name_labels, value) = line.rsplit_once(' ').unwrap();
+            let (name, labels) = name_labels.split_once('{').unwrap();
+            let labels = labels.split("le=").next().unwrap();
+            series.entry(format!("{}{}", name, labels)).or_default().push(value.parse().unwrap());
+        }
+        assert_eq!(series.len(), 5);
+        for (name, counts) in &series {
+            assert!(counts.windows(2).all(|pair| pair[0] <= pair[1]), "{} is not cumulative", name);
+        }
+        let health = &series["unfold_http_request_duration_seconds_bucketmethod=\"GET\",route=\"/health\","];
+        assert_eq!(health.first(), Some(&1));
+        assert_eq!(health.last(), Some(&2));
+        assert!(text.contains("unfold_http_request_duration_seconds_count{method=\"GET\",route=\"/health\"} 2\n"));
+    }
+}
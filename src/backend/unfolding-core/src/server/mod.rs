@@ -0,0 +1,10 @@
+// Компоненты HTTP-сервера, вынесенные из main.rs
+pub mod cache;
+pub mod export;
+pub mod jobs;
+pub mod metrics;
+pub mod profiles;
+pub mod quota;
+pub mod request_id;
+pub mod settings;
+pub mod upload;
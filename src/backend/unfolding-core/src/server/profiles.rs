@@ -0,0 +1,218 @@
+use axum::{extract::State, response::Json};
+use serde::Deserialize;
+use std::{collections::BTreeMap, sync::Arc};
+use tracing::instrument;
+
+use pepakura_unfolding_core::{QualityLevel, SegmentationMode, UnfoldingConfig, UnfoldingMode};
+
+use crate::{AppState, ErrorResponse};
+
+/// Profile used when a request does not name one.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Named base configs that requests start from before their own overrides are applied.
+#[derive(Debug, Clone)]
+pub struct Profiles(BTreeMap<String, UnfoldingConfig>);
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self::new(UnfoldingConfig::default())
+    }
+}
+
+impl Profiles {
+    /// Built-in profiles, each derived from `default`.
+    pub fn new(default: UnfoldingConfig) -> Self {
+        let profiles = [
+            (DEFAULT_PROFILE, default.clone()),
+            // Быстрый просмотр: без исправления наложений и без клапанов
+            (
+                "preview",
+                UnfoldingConfig {
+                    quality_level: QualityLevel::Draft,
+                    add_tabs: false,
+                    ..default.clone()
+                },
+            ),
+            (
+                "a3",
+                UnfoldingConfig {
+                    sheet_size: [297.0, 420.0],
+                    ..default.clone()
+                },
+            ),
+            (
+                "letter",
+                UnfoldingConfig {
+                    sheet_size: [215.9, 279.4],
+                    ..default.clone()
+                },
+            ),
+            // Плотный картон: компенсируем толщину на сгибах
+            (
+                "cardstock",
+                UnfoldingConfig {
+                    quality_level: QualityLevel::High,
+                    paper_thickness: 0.3,
+                    ..default
+                },
+            ),
+        ];
+        Self(profiles.into_iter().map(|(name, config)| (name.to_string(), config)).collect())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UnfoldingConfig> {
+        self.0.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Applies `overrides` on top of the requested profile and validates the result.
+    pub fn resolve(&self, overrides: ConfigOverrides) -> Result<UnfoldingConfig, ErrorResponse> {
+        let profile = overrides.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+        let mut config = self.get(profile).cloned().ok_or_else(|| {
+            invalid_config(format!(
+                "unknown profile '{}' (expected one of: {})",
+                profile,
+                self.names().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+
+        if let Some(quality) = overrides.quality {
+            config.quality_level = QualityLevel::from_name(&quality).ok_or_else(|| {
+                let names: Vec<&str> = QualityLevel::ALL.iter().map(QualityLevel::name).collect();
+                invalid_config(format!("unknown quality '{}' (expected one of: {})", quality, names.join(", ")))
+            })?;
+        }
+        if let Some(sheet_size) = overrides.sheet_size {
+            config.sheet_size = sheet_size;
+        }
+        if let Some(optimize_folding_lines) = overrides.optimize_folding_lines {
+            config.optimize_folding_lines = optimize_folding_lines;
+        }
+        if let Some(add_tabs) = overrides.add_tabs {
+            config.add_tabs = add_tabs;
+        }
+        if let Some(tolerance) = overrides.tolerance {
+            config.tolerance = tolerance;
+        }
+        if let Some(min_component_area) = overrides.min_component_area {
+            config.min_component_area = min_component_area;
+        }
+        if let Some(segmentation) = overrides.segmentation {
+            config.segmentation = segmentation;
+        }
+        if let Some(paper_thickness) = overrides.paper_thickness {
+            config.paper_thickness = paper_thickness;
+        }
+        if let Some(unfolding_mode) = overrides.unfolding_mode {
+            config.unfolding_mode = unfolding_mode;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Config options a request may set; anything left out comes from the profile.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigOverrides {
+    pub profile: Option<String>,
+    /// `draft`, `standard`, `high` or `production`.
+    pub quality: Option<String>,
+    pub sheet_size: Option<[f64; 2]>,
+    pub optimize_folding_lines: Option<bool>,
+    pub add_tabs: Option<bool>,
+    pub tolerance: Option<f64>,
+    pub min_component_area: Option<f64>,
+    pub segmentation: Option<SegmentationMode>,
+    pub paper_thickness: Option<f64>,
+    pub unfolding_mode: Option<UnfoldingMode>,
+}
+
+fn invalid_config(error: String) -> ErrorResponse {
+    ErrorResponse {
+        error,
+        code: "INVALID_CONFIG".to_string(),
+    }
+}
+
+/// `GET /profiles`: every server-side profile with its full config.
+#[instrument(skip(state))]
+pub async fn list_profiles(State(state): State<Arc<AppState>>) -> Json<BTreeMap<String, UnfoldingConfig>> {
+    Json(state.profiles.0.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_profile_and_quality_rejected() {
+        let profiles = Profiles::default();
+        let unknown_profile = ConfigOverrides {
+            profile: Some("poster".to_string()),
+            ..Default::default()
+        };
+        let unknown_quality = ConfigOverrides {
+            quality: Some("ultra".to_string()),
+            ..Default::default()
+        };
+
+        let error = profiles.resolve(unknown_profile).unwrap_err();
+        assert_eq!(error.code, "INVALID_CONFIG");
+        assert!(error.error.contains("cardstock"));
+        let error = profiles.resolve(unknown_quality).unwrap_err();
+        assert_eq!(error.code, "INVALID_CONFIG");
+        assert!(error.error.contains("production"));
+    }
+
+    #[test]
+    fn test_overrides_beat_profile_values() {
+        let profiles = Profiles::default();
+        let cardstock = profiles
+            .resolve(ConfigOverrides {
+                profile: Some("cardstock".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(cardstock.quality_level, QualityLevel::High);
+        assert_eq!(cardstock.paper_thickness, 0.3);
+
+        let overridden = profiles
+            .resolve(ConfigOverrides {
+                profile: Some("cardstock".to_string()),
+                quality: Some("Draft".to_string()),
+                paper_thickness: Some(0.1),
+                sheet_size: Some([100.0, 150.0]),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(overridden.quality_level, QualityLevel::Draft);
+        assert_eq!(overridden.paper_thickness, 0.1);
+        assert_eq!(overridden.sheet_size, [100.0, 150.0]);
+        // Не переопределённое берётся из профиля
+        assert!(overridden.add_tabs);
+    }
+
+    #[test]
+    fn test_overrides_are_validated() {
+        let error = Profiles::default()
+            .resolve(ConfigOverrides {
+                paper_thickness: Some(-1.0),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert_eq!(error.code, "INVALID_CONFIG");
+    }
+
+    #[test]
+    fn test_unknown_override_rejected_outside_requests() {
+        // Секция unfolding в файле настроек разбирается без flatten, опечатка там — ошибка
+        let parsed = serde_json::from_str::<ConfigOverrides>(r#"{"paper_thicknes": 0.3}"#);
+        assert!(parsed.is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{AppState, ErrorResponse};
+
+/// Set by api_gateway to the API key or address the request came from; read only with
+/// `trust_client_id`.
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+/// `Retry-After` sent with `TOO_MANY_JOBS`: unfolds usually finish within seconds.
+pub const RETRY_AFTER_SECS: u64 = 5;
+
+/// Who sent the request: the peer address, or the gateway's `X-Client-Id` when
+/// `trust_client_id` is on.
+///
+/// Any caller can set the header, so it is only trusted when the core is reachable through
+/// the gateway alone.
+#[derive(Debug, Clone)]
+pub struct Client(pub String);
+
+impl Client {
+    fn identify(parts: &Parts, trust_client_id: bool) -> Self {
+        let forwarded = parts
+            .headers
+            .get(CLIENT_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| trust_client_id && !id.is_empty() && id.len() <= 256);
+        if let Some(id) = forwarded {
+            return Self(id.to_string());
+        }
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map_or_else(|| "unknown".to_string(), |ConnectInfo(addr)| format!("ip:{}", addr.ip()));
+        Self(peer)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Client {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        Ok(Self::identify(parts, state.trust_client_id))
+    }
+}
+
+/// Unfolds (synchronous or queued) each client may have in flight at once.
+#[derive(Debug)]
+pub struct ClientQuota {
+    /// 0 disables the cap.
+    limit: usize,
+    active: Mutex<HashMap<String, usize>>,
+}
+
+impl ClientQuota {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Reserves one of `client`'s slots until the returned guard is dropped.
+    pub fn acquire(self: &Arc<Self>, client: &Client) -> Result<ClientSlot, ErrorResponse> {
+        if self.limit == 0 {
+            return Ok(ClientSlot(None));
+        }
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        let count = active.entry(client.0.clone()).or_insert(0);
+        if *count >= self.limit {
+            return Err(ErrorResponse {
+                error: format!(
+                    "Client {} already has {} unfolds in progress, the limit is {}",
+                    client.0, count, self.limit
+                ),
+                code: "TOO_MANY_JOBS".to_string(),
+            });
+        }
+        *count += 1;
+        Ok(ClientSlot(Some((Arc::clone(self), client.0.clone()))))
+    }
+
+    fn release(&self, client: &str) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = active.get_mut(client) {
+            *count -= 1;
+            // Пустые записи убираем, чтобы таблица не росла с числом разных клиентов
+            if *count == 0 {
+                active.remove(client);
+            }
+        }
+    }
+}
+
+/// A reserved slot; released on drop.
+#[derive(Debug)]
+pub struct ClientSlot(Option<(Arc<ClientQuota>, String)>);
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        if let Some((quota, client)) = &self.0 {
+            quota.release(client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_limited_per_client() {
+        let quota = Arc::new(ClientQuota::new(2));
+        let (alice, bob) = (Client("key:alice".to_string()), Client("key:bob".to_string()));
+
+        let first = quota.acquire(&alice).unwrap();
+        let _second = quota.acquire(&alice).unwrap();
+        assert_eq!(quota.acquire(&alice).err().unwrap().code, "TOO_MANY_JOBS");
+        let _other = quota.acquire(&bob).unwrap();
+
+        drop(first);
+        let _third = quota.acquire(&alice).unwrap();
+        assert_eq!(quota.acquire(&alice).err().unwrap().code, "TOO_MANY_JOBS");
+    }
+
+    #[test]
+    fn test_released_slots_leave_no_entries() {
+        let quota = Arc::new(ClientQuota::new(1));
+        let slots: Vec<ClientSlot> =
+            (0..3).map(|index| quota.acquire(&Client(format!("ip:10.0.0.{}", index))).unwrap()).collect();
+        assert_eq!(quota.active.lock().unwrap().len(), 3);
+
+        drop(slots);
+        assert!(quota.active.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_client_id_header_needs_trust() {
+        let parts = |client_id: &str| {
+            let mut request = axum::http::Request::builder().header(CLIENT_ID_HEADER, client_id).body(()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 7], 51000))));
+            request.into_parts().0
+        };
+
+        assert_eq!(Client::identify(&parts("key:alice"), false).0, "ip:10.0.0.7");
+        assert_eq!(Client::identify(&parts("key:alice"), true).0, "key:alice");
+        assert_eq!(Client::identify(&parts(""), true).0, "ip:10.0.0.7");
+    }
+
+    #[test]
+    fn test_zero_limit_is_unlimited() {
+        let quota = Arc::new(ClientQuota::new(0));
+        let client = Client("key:alice".to_string());
+        let slots: Vec<ClientSlot> = (0..10).map(|_| quota.acquire(&client).unwrap()).collect();
+        assert_eq!(slots.len(), 10);
+        assert!(quota.active.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{info_span, Instrument};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Request id of the request being handled by the current task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Identity of one request: the caller's `X-Request-Id` and the W3C trace it belongs to.
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    pub request_id: String,
+    pub trace_id: String,
+    /// Span of the caller (the gateway) from `traceparent`, if it sent one.
+    pub parent_id: Option<String>,
+}
+
+impl TraceContext {
+    /// Reads `X-Request-Id` and `traceparent`; malformed values are replaced. Without an
+    /// incoming id the trace id is used, so logs of a request can be found by either.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let parent = headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+        let (trace_id, parent_id) = match parent {
+            Some((trace_id, parent_id)) => (trace_id, Some(parent_id)),
+            None => (random_hex(2), None),
+        };
+        let request_id = headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map_or_else(|| trace_id.clone(), str::to_string);
+
+        Self {
+            request_id,
+            trace_id,
+            parent_id,
+        }
+    }
+}
+
+/// Runs the request inside a `request` span carrying its id and trace id, makes the id
+/// available to [`current`] and returns it in `X-Request-Id`.
+pub async fn propagate(request: Request, next: Next) -> Response {
+    let context = TraceContext::from_headers(request.headers());
+    let span = info_span!(
+        "request",
+        request_id = %context.request_id,
+        trace_id = %context.trace_id,
+        parent_id = context.parent_id.as_deref(),
+        method = %request.method(),
+        path = request.uri().path(),
+    );
+
+    let mut response = REQUEST_ID
+        .scope(context.request_id.clone(), next.run(request))
+        .instrument(span)
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&context.request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+// Принимаем только короткие печатные идентификаторы, чтобы чужой заголовок не ломал логи
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len())
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.:".contains(&byte))
+}
+
+/// `00-<trace id>-<parent id>-<flags>`; all-zero ids and version `ff` are invalid per the W3C spec.
+fn parse_traceparent(value: &str) -> Option<(String, String)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+    // Версия 00 состоит ровно из четырёх полей; более новые версии могут добавлять поля в конце
+    if version == "00" && parts.next().is_some() {
+        return None;
+    }
+
+    let is_hex = |part: &str, len: usize| {
+        part.len() == len && part.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && is_hex(parent_id, 16)
+        && is_hex(flags, 2)
+        && trace_id.bytes().any(|byte| byte != b'0')
+        && parent_id.bytes().any(|byte| byte != b'0');
+    valid.then(|| (trace_id.to_string(), parent_id.to_string()))
+}
+
+// Случайность без отдельной зависимости: ключи RandomState случайны для процесса
+fn random_hex(words: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    (0..words)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            hasher.write_u128(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos()),
+            );
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn test_parse_traceparent() {
+        let valid = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        assert_eq!(
+            parse_traceparent(&valid),
+            Some((TRACE_ID.to_string(), PARENT_ID.to_string()))
+        );
+
+        let invalid = [
+            format!("00-{}-{}-01", "0".repeat(32), PARENT_ID),
+            format!("00-{}-{}-01", TRACE_ID, "0".repeat(16)),
+            format!("ff-{}-{}-01", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}-01-extra", TRACE_ID, PARENT_ID),
+            format!("00-{}-{}", TRACE_ID, PARENT_ID),
+        ];
+        for value in invalid {
+            assert_eq!(parse_traceparent(&value), None, "{}", value);
+        }
+        // Более поздние версии могут нести дополнительные поля
+        let future = format!("01-{}-{}-01-extra", TRACE_ID, PARENT_ID);
+        assert!(parse_traceparent(&future).is_some());
+    }
+
+    #[test]
+    fn test_context_from_headers() {
+        let mut headers = HeaderMap::new();
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        headers.insert(TRACEPARENT_HEADER, HeaderValue::from_str(&traceparent).unwrap());
+        let context = TraceContext::from_headers(&headers);
+        assert_eq!(context.trace_id, TRACE_ID);
+        assert_eq!(context.parent_id.as_deref(), Some(PARENT_ID));
+        assert_eq!(context.request_id, TRACE_ID);
+
+        headers.insert(TRACEPARENT_HEADER, HeaderValue::from_static("00-1-2-01-extra"));
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("req-1"));
+        let context = TraceContext::from_headers(&headers);
+        assert_eq!(context.trace_id.len(), 32);
+        assert_eq!(context.parent_id, None);
+        assert_eq!(context.request_id, "req-1");
+    }
+}
@@ -0,0 +1,429 @@
+use clap::{Parser, ValueEnum};
+use config::{Config, ConfigError, Environment, File};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use pepakura_unfolding_core::{Mesh, MeshLimits};
+
+use crate::{
+    server::{
+        cache::ResultCache,
+        jobs::JobQueueConfig,
+        profiles::{ConfigOverrides, Profiles},
+        quota::ClientQuota,
+        upload::UPLOAD_BODY_LIMIT,
+    },
+    ErrorResponse,
+};
+
+/// Prefix of the environment variables read into [`Settings`], e.g. `UNFOLD_PORT`.
+/// Nested keys use a double underscore: `UNFOLD_UNFOLDING__QUALITY=high`.
+const ENV_PREFIX: &str = "UNFOLD";
+
+/// Command line of the server. Every flag overrides the same key from the environment
+/// and the config file.
+#[derive(Parser, Debug)]
+#[command(version, about = "Pepakura unfolding HTTP server")]
+pub struct Cli {
+    /// Config file; TOML, JSON or YAML, picked by extension.
+    #[arg(short, long, env = "UNFOLD_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on.
+    #[arg(long)]
+    pub host: Option<IpAddr>,
+    /// Port to listen on.
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Tracing filter, e.g. `info` or `pepakura_unfolding_core=debug,tower_http=warn`.
+    #[arg(long)]
+    pub log_level: Option<String>,
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+    /// Async runtime worker threads (defaults to the number of CPUs).
+    #[arg(long)]
+    pub workers: Option<usize>,
+    /// Largest JSON body accepted by `/unfold` and `/jobs`, in bytes.
+    #[arg(long)]
+    pub max_body_bytes: Option<usize>,
+    /// Largest body accepted by `/upload`, in bytes.
+    #[arg(long)]
+    pub max_upload_bytes: Option<usize>,
+    #[arg(long)]
+    pub max_vertices: Option<usize>,
+    #[arg(long)]
+    pub max_faces: Option<usize>,
+    /// Most vertices a single face may have.
+    #[arg(long)]
+    pub max_face_vertices: Option<usize>,
+    /// Seconds `/unfold` and `/upload` may compute before failing with `TIMEOUT`; 0 disables the limit.
+    #[arg(long)]
+    pub request_timeout_secs: Option<u64>,
+    #[arg(long)]
+    pub max_concurrent_jobs: Option<usize>,
+    /// Jobs that may wait for a free slot; at least 1.
+    #[arg(long)]
+    pub max_queued_jobs: Option<usize>,
+    /// Unfolds one client may have queued or running at once; 0 disables the cap.
+    #[arg(long)]
+    pub max_jobs_per_client: Option<usize>,
+    /// Seconds a job may run before it fails with `TIMEOUT`; 0 disables the limit.
+    #[arg(long)]
+    pub job_timeout_secs: Option<u64>,
+    /// Take the client from api_gateway's `X-Client-Id` instead of the peer address; only
+    /// for a core that nothing but the gateway can reach.
+    #[arg(long)]
+    pub trust_client_id: bool,
+    /// Seconds finished jobs stay available.
+    #[arg(long)]
+    pub job_retention_secs: Option<u64>,
+    /// Unfold results kept in memory; 0 disables the in-memory cache.
+    #[arg(long)]
+    pub cache_entries: Option<usize>,
+    /// Directory where unfold results are also stored on disk.
+    #[arg(long)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Server configuration: defaults, then the config file, then `UNFOLD_*` environment
+/// variables, then command-line flags.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub host: IpAddr,
+    pub port: u16,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub workers: Option<usize>,
+    pub max_body_bytes: usize,
+    pub max_upload_bytes: usize,
+    pub max_vertices: usize,
+    pub max_faces: usize,
+    pub max_face_vertices: usize,
+    pub request_timeout_secs: u64,
+    pub max_concurrent_jobs: usize,
+    pub max_queued_jobs: usize,
+    pub max_jobs_per_client: usize,
+    pub trust_client_id: bool,
+    pub job_timeout_secs: u64,
+    pub job_retention_secs: u64,
+    pub cache_entries: usize,
+    pub cache_dir: Option<PathBuf>,
+    /// Overrides applied to the `default` profile, which the other built-in profiles start from.
+    pub unfolding: ConfigOverrides,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let jobs = JobQueueConfig::default();
+        Self {
+            host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8080,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            workers: None,
+            max_body_bytes: 16 * 1024 * 1024,
+            max_upload_bytes: UPLOAD_BODY_LIMIT,
+            max_vertices: 100_000,
+            max_faces: 200_000,
+            max_face_vertices: 64,
+            request_timeout_secs: 120,
+            max_concurrent_jobs: jobs.max_concurrent_jobs,
+            max_queued_jobs: jobs.max_queued_jobs,
+            max_jobs_per_client: 2,
+            trust_client_id: false,
+            job_timeout_secs: jobs.timeout.map_or(0, |timeout| timeout.as_secs()),
+            job_retention_secs: jobs.retention.as_secs(),
+            cache_entries: 64,
+            cache_dir: None,
+            unfolding: ConfigOverrides::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Merges the config file named by `cli` (if any), the environment and `cli` itself.
+    pub fn load(cli: Cli) -> Result<Self, ConfigError> {
+        let mut builder = Config::builder();
+        if let Some(path) = &cli.config {
+            builder = builder.add_source(File::from(path.as_path()));
+        }
+        builder = builder.add_source(
+            Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("unfolding.sheet_size"),
+        );
+
+        // config хранит только 64-битные целые
+        let count = |value: Option<usize>| value.map(|value| value as u64);
+        builder = builder
+            .set_override_option("host", cli.host.map(|host| host.to_string()))?
+            .set_override_option("port", cli.port)?
+            .set_override_option("log_level", cli.log_level)?
+            .set_override_option("log_format", cli.log_format.map(|format| format.name().to_string()))?
+            .set_override_option("workers", count(cli.workers))?
+            .set_override_option("max_body_bytes", count(cli.max_body_bytes))?
+            .set_override_option("max_upload_bytes", count(cli.max_upload_bytes))?
+            .set_override_option("max_vertices", count(cli.max_vertices))?
+            .set_override_option("max_faces", count(cli.max_faces))?
+            .set_override_option("max_face_vertices", count(cli.max_face_vertices))?
+            .set_override_option("request_timeout_secs", cli.request_timeout_secs)?
+            .set_override_option("max_concurrent_jobs", count(cli.max_concurrent_jobs))?
+            .set_override_option("max_queued_jobs", count(cli.max_queued_jobs))?
+            .set_override_option("max_jobs_per_client", count(cli.max_jobs_per_client))?
+            .set_override_option("trust_client_id", cli.trust_client_id.then_some(true))?
+            .set_override_option("job_timeout_secs", cli.job_timeout_secs)?
+            .set_override_option("job_retention_secs", cli.job_retention_secs)?
+            .set_override_option("cache_entries", count(cli.cache_entries))?
+            .set_override_option("cache_dir", cli.cache_dir.map(|dir| dir.display().to_string()))?;
+
+        let settings: Settings = builder.build()?.try_deserialize()?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        // Ошибки в секции unfolding тоже должны останавливать запуск до инициализации логов
+        self.profiles()?;
+        let positive = [
+            ("workers", self.workers.unwrap_or(1)),
+            ("max_body_bytes", self.max_body_bytes),
+            ("max_upload_bytes", self.max_upload_bytes),
+            ("max_vertices", self.max_vertices),
+            ("max_faces", self.max_faces),
+            ("max_face_vertices", self.max_face_vertices),
+            ("max_concurrent_jobs", self.max_concurrent_jobs),
+            // При нуле любая отправка задачи получала бы QUEUE_FULL
+            ("max_queued_jobs", self.max_queued_jobs),
+        ];
+        match positive.iter().find(|(_, value)| *value == 0) {
+            Some((key, _)) => Err(ConfigError::Message(format!("{} must be greater than 0", key))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_body_bytes: self.max_body_bytes,
+            max_upload_bytes: self.max_upload_bytes,
+            max_vertices: self.max_vertices,
+            max_faces: self.max_faces,
+            max_face_vertices: self.max_face_vertices,
+            request_timeout_secs: self.request_timeout_secs,
+            job_timeout_secs: self.job_timeout_secs,
+        }
+    }
+
+    pub fn job_queue(&self) -> JobQueueConfig {
+        JobQueueConfig {
+            max_concurrent_jobs: self.max_concurrent_jobs,
+            max_queued_jobs: self.max_queued_jobs,
+            retention: Duration::from_secs(self.job_retention_secs),
+            timeout: seconds(self.job_timeout_secs),
+        }
+    }
+
+    pub fn quota(&self) -> ClientQuota {
+        ClientQuota::new(self.max_jobs_per_client)
+    }
+
+    pub fn cache(&self) -> ResultCache {
+        ResultCache::new(self.cache_entries, self.cache_dir.clone())
+    }
+
+    /// Built-in profiles on top of the configured `unfolding` defaults.
+    pub fn profiles(&self) -> Result<Profiles, ConfigError> {
+        let default = Profiles::default()
+            .resolve(self.unfolding.clone())
+            .map_err(|e| ConfigError::Message(format!("unfolding: {}", e.error)))?;
+        Ok(Profiles::new(default))
+    }
+}
+
+impl LogFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+/// Resource limits applied to every request, as listed by `/info`. Timeouts of 0 are disabled.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Limits {
+    pub max_body_bytes: usize,
+    pub max_upload_bytes: usize,
+    pub max_vertices: usize,
+    pub max_faces: usize,
+    pub max_face_vertices: usize,
+    pub request_timeout_secs: u64,
+    pub job_timeout_secs: u64,
+}
+
+impl Limits {
+    pub fn request_timeout(&self) -> Option<Duration> {
+        seconds(self.request_timeout_secs)
+    }
+
+    /// Vertex and face limits for the file parsers, so that `/upload` stops reading early.
+    pub fn mesh(&self) -> MeshLimits {
+        MeshLimits { max_vertices: self.max_vertices, max_faces: self.max_faces }
+    }
+
+    /// Rejects meshes with too many vertices or faces, or with an oversized face.
+    pub fn check(&self, mesh: &Mesh) -> Result<(), ErrorResponse> {
+        let too_large = |what: &str, count: usize, limit: usize| ErrorResponse {
+            error: format!("Mesh has {} {}, the limit is {}", count, what, limit),
+            code: "MESH_TOO_LARGE".to_string(),
+        };
+        if mesh.vertices.len() > self.max_vertices {
+            return Err(too_large("vertices", mesh.vertices.len(), self.max_vertices));
+        }
+        if mesh.faces.len() > self.max_faces {
+            return Err(too_large("faces", mesh.faces.len(), self.max_faces));
+        }
+        if let Some((index, face)) = mesh
+            .faces
+            .iter()
+            .enumerate()
+            .find(|(_, face)| face.len() > self.max_face_vertices)
+        {
+            return Err(ErrorResponse {
+                error: format!(
+                    "Face {} has {} vertices, the limit is {}",
+                    index,
+                    face.len(),
+                    self.max_face_vertices
+                ),
+                code: "MESH_TOO_LARGE".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Error for an unfold stopped after running for `limit`.
+pub fn timed_out(limit: Duration) -> ErrorResponse {
+    ErrorResponse {
+        error: format!("Unfolding exceeded the {}s time limit", limit.as_secs()),
+        code: "TIMEOUT".to_string(),
+    }
+}
+
+// 0 в настройках означает «без ограничения»
+fn seconds(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_secs(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn grid(vertices: usize, faces: usize, face_size: usize) -> Mesh {
+        let vertex = pepakura_unfolding_core::Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+        Mesh::new(vec![vertex; vertices], vec![vec![0; face_size]; faces])
+    }
+
+    #[test]
+    fn test_mesh_limits_are_inclusive() {
+        let limits = Settings {
+            max_vertices: 10,
+            max_faces: 5,
+            max_face_vertices: 4,
+            ..Default::default()
+        }
+        .limits();
+
+        assert!(limits.check(&grid(10, 5, 4)).is_ok());
+        for mesh in [grid(11, 5, 4), grid(10, 6, 4), grid(10, 5, 5)] {
+            let error = limits.check(&mesh).unwrap_err();
+            assert_eq!(error.code, "MESH_TOO_LARGE");
+        }
+        assert!(limits.check(&grid(10, 5, 5)).unwrap_err().error.starts_with("Face 0 has 5 vertices"));
+    }
+
+    #[test]
+    fn test_zero_timeouts_disable_limits() {
+        let limits = Settings {
+            request_timeout_secs: 0,
+            ..Default::default()
+        }
+        .limits();
+        assert_eq!(limits.request_timeout(), None);
+        assert_eq!(Settings::default().limits().request_timeout(), Some(Duration::from_secs(120)));
+        assert_eq!(timed_out(Duration::from_secs(120)).code, "TIMEOUT");
+    }
+
+    #[test]
+    fn test_zero_limits_rejected() {
+        for settings in [
+            Settings {
+                max_queued_jobs: 0,
+                ..Default::default()
+            },
+            Settings {
+                max_concurrent_jobs: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(settings.validate().is_err());
+        }
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    // Единственный тест, читающий переменные UNFOLD_*, чтобы параллельные тесты не мешали друг другу
+    #[test]
+    fn test_file_env_and_cli_layering() {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        writeln!(
+            file,
+            "port = 7000\nmax_faces = 10\nmax_vertices = 10\nlog_level = \"debug\"\n\
+             [unfolding]\npaper_thickness = 0.2\nquality = \"draft\""
+        )
+        .unwrap();
+        std::env::set_var("UNFOLD_MAX_FACES", "20");
+        std::env::set_var("UNFOLD_MAX_VERTICES", "20");
+        std::env::set_var("UNFOLD_UNFOLDING__QUALITY", "high");
+        std::env::set_var("UNFOLD_TRUST_CLIENT_ID", "true");
+
+        let path = file.path().to_str().unwrap();
+        let loaded = Settings::load(Cli::parse_from(["unfold", "--config", path, "--port", "9000", "--max-vertices", "30"]));
+        let keys = ["UNFOLD_MAX_FACES", "UNFOLD_MAX_VERTICES", "UNFOLD_UNFOLDING__QUALITY", "UNFOLD_TRUST_CLIENT_ID"];
+        for key in keys {
+            std::env::remove_var(key);
+        }
+        let settings = loaded.unwrap();
+
+        // Файл < окружение < командная строка, остальное — значения по умолчанию
+        assert_eq!(settings.log_level, "debug");
+        assert_eq!(settings.max_faces, 20);
+        assert_eq!(settings.max_vertices, 30);
+        assert_eq!(settings.port, 9000);
+        assert!(settings.trust_client_id && !Settings::default().trust_client_id);
+        assert_eq!(settings.max_face_vertices, Settings::default().max_face_vertices);
+        assert_eq!(settings.unfolding.paper_thickness, Some(0.2));
+        assert_eq!(settings.unfolding.quality.as_deref(), Some("high"));
+    }
+}
@@ -0,0 +1,465 @@
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json, Response},
+};
+use memchr::memmem;
+use std::{collections::HashMap, sync::Arc};
+use tracing::{info, instrument};
+
+use pepakura_unfolding_core::{
+    Mesh, MeshFormat, ObjGrouping, SegmentationMode, UnfoldingMode, UnfoldingRequest,
+};
+
+use crate::{
+    server::{
+        export::{self, ExportParams},
+        jobs,
+        profiles::ConfigOverrides,
+        quota::Client,
+        settings,
+    },
+    AppState, ErrorResponse,
+};
+
+/// Default for the largest accepted upload, raw or multipart (`max_upload_bytes`).
+pub const UPLOAD_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Query parameters and form fields `/upload` understands; anything else is `INVALID_CONFIG`.
+const KNOWN_PARAMS: &[&str] = &[
+    "format",
+    "filename",
+    "grouping",
+    "async",
+    "export",
+    "sheet_format",
+    "profile",
+    "quality",
+    "sheet_size",
+    "add_tabs",
+    "optimize_folding_lines",
+    "tolerance",
+    "min_component_area",
+    "paper_thickness",
+    "segmentation",
+    "max_dihedral_angle",
+    "unfolding_mode",
+    "max_strain",
+];
+
+// Значения по умолчанию для параметров, которые в JSON API задаются вложенными объектами
+const DEFAULT_MAX_DIHEDRAL_ANGLE: f64 = 30.0;
+const DEFAULT_MAX_STRAIN: f64 = 0.02;
+
+/// One part of a `multipart/form-data` body.
+struct FormPart {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+/// `POST /upload`: unfolds a mesh file sent either as the raw body or as the `file` field
+/// of a multipart form.
+///
+/// The format comes from the `format` parameter, the file name extension (`filename`
+/// parameter for raw bodies) or the content type, in that order. Config is read from query
+/// parameters and text form fields (form fields win): `profile`, `quality`, `sheet_size`
+/// (`210x297`), `add_tabs`, `optimize_folding_lines`, `tolerance`, `min_component_area`,
+/// `paper_thickness`, `segmentation` (`none`, `mesh_parts`, `automatic`), `max_dihedral_angle`,
+/// `unfolding_mode` (`exact`, `strips`), `max_strain` and `grouping` (`none`, `groups`,
+/// `materials`, OBJ only); unknown ones are rejected. With `async=true` the unfold is queued
+/// as a job instead.
+/// `export` (and `sheet_format`) or the `Accept` header ask for SVG/PDF/DXF/ZIP instead of JSON,
+/// as `format` does on `/unfold`.
+#[instrument(skip_all)]
+pub async fn upload_mesh(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<HashMap<String, String>>,
+    client: Client,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Response, ErrorResponse> {
+    let body = body?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    let (data, file_name, file_type) = if content_type.to_ascii_lowercase().starts_with("multipart/form-data") {
+        let file = take_file(parse_multipart(&content_type, &body)?, &mut params)?;
+        (file.data, file.file_name, file.content_type)
+    } else {
+        (body.to_vec(), params.get("filename").cloned(), Some(content_type))
+    };
+
+    reject_unknown_params(&params)?;
+    let format = resolve_format(&params, file_name.as_deref(), file_type.as_deref())?;
+    let grouping = match params.get("grouping").map(String::as_str) {
+        None | Some("groups") => ObjGrouping::Groups,
+        Some("materials") => ObjGrouping::Materials,
+        Some("none") => ObjGrouping::None,
+        Some(other) => return Err(invalid_config(&format!("unknown grouping '{}'", other))),
+    };
+    let config = state.profiles.resolve(config_overrides(&params)?)?;
+    let export = export::requested_export(
+        &ExportParams {
+            format: params.get("export").cloned(),
+            sheet_format: params.get("sheet_format").cloned(),
+        },
+        &headers,
+    )?;
+    let sheet_size = config.sheet_size;
+    info!("Upload of {} bytes as {}", data.len(), format.name());
+
+    // Разбор крупных файлов тоже нагружает процессор, поэтому он идёт в счёт квоты клиента
+    let slot = state.quota.acquire(&client)?;
+    let limits = state.limits.mesh();
+    // Разбор нельзя прервать, так что слот остаётся у потока до его конца, даже после тайм-аута
+    let parser = tokio::task::spawn_blocking(move || {
+        let mesh = Mesh::from_bytes_limited(&data, format, grouping, limits);
+        (mesh, slot)
+    });
+    let parsed = match state.limits.request_timeout() {
+        Some(limit) => tokio::time::timeout(limit, parser).await.map_err(|_| settings::timed_out(limit))?,
+        None => parser.await,
+    };
+    let (mesh, slot) = parsed.map_err(|e| ErrorResponse {
+        error: format!("Mesh parser stopped unexpectedly: {}", e),
+        code: "PROCESSING_ERROR".to_string(),
+    })?;
+    let mesh = mesh?;
+    state.limits.check(&mesh)?;
+    let request = UnfoldingRequest { mesh, config };
+
+    if params.get("async").is_some_and(|value| value == "true") {
+        return Ok(jobs::submit(&state, slot, request)?.into_response());
+    }
+
+    let _slot = slot;
+    let response = crate::run_unfold(&state, request).await?;
+    info!("Unfolding completed in {}ms", response.processing_time_ms);
+    let file_stem = file_name
+        .as_deref()
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .and_then(|name| name.rsplit_once('.').map(|(stem, _)| stem))
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("unfold")
+        .to_string();
+    match export {
+        Some(export) => export::export_response(export, response.islands, sheet_size, &file_stem).await,
+        None => Ok(Json(response).into_response()),
+    }
+}
+
+fn resolve_format(
+    params: &HashMap<String, String>,
+    file_name: Option<&str>,
+    content_type: Option<&str>,
+) -> Result<MeshFormat, ErrorResponse> {
+    if let Some(name) = params.get("format") {
+        return MeshFormat::from_name(name).ok_or_else(|| unsupported_format(&format!("format '{}'", name)));
+    }
+    file_name
+        .and_then(MeshFormat::from_file_name)
+        .or_else(|| content_type.and_then(MeshFormat::from_content_type))
+        .ok_or_else(|| {
+            let what = match (file_name, content_type) {
+                (Some(name), _) => format!("file '{}'", name),
+                (None, Some(content_type)) if !content_type.is_empty() => format!("content type '{}'", content_type),
+                _ => "upload without file name or content type".to_string(),
+            };
+            unsupported_format(&what)
+        })
+}
+
+// Опечатка в имени параметра иначе молча дала бы развёртку с настройками по умолчанию
+fn reject_unknown_params(params: &HashMap<String, String>) -> Result<(), ErrorResponse> {
+    match params.keys().filter(|key| !KNOWN_PARAMS.contains(&key.as_str())).min() {
+        Some(key) => Err(invalid_config(&format!("unknown parameter '{}'", key))),
+        None => Ok(()),
+    }
+}
+
+fn config_overrides(params: &HashMap<String, String>) -> Result<ConfigOverrides, ErrorResponse> {
+    let number = |key: &str| -> Result<Option<f64>, ErrorResponse> {
+        params
+            .get(key)
+            .map(|value| {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| invalid_config(&format!("{} must be a number, got '{}'", key, value)))
+            })
+            .transpose()
+    };
+
+    let flag = |key: &str| -> Result<Option<bool>, ErrorResponse> {
+        params
+            .get(key)
+            .map(|value| match value.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(true),
+                "false" | "0" | "no" | "off" => Ok(false),
+                _ => Err(invalid_config(&format!("{} must be true or false, got '{}'", key, value))),
+            })
+            .transpose()
+    };
+    let sheet_size = params
+        .get("sheet_size")
+        .map(|value| {
+            value
+                .split(['x', 'X', ','])
+                .map(|side| side.trim().parse::<f64>().ok())
+                .collect::<Option<Vec<f64>>>()
+                .and_then(|sides| <[f64; 2]>::try_from(sides).ok())
+                .ok_or_else(|| invalid_config(&format!("sheet_size must look like 210x297, got '{}'", value)))
+        })
+        .transpose()?;
+
+    let max_dihedral_angle = number("max_dihedral_angle")?;
+    let segmentation = match params.get("segmentation").map(String::as_str) {
+        None if max_dihedral_angle.is_some() => Some("automatic"),
+        other => other,
+    };
+    let segmentation = match segmentation {
+        None => None,
+        Some("none") => Some(SegmentationMode::None),
+        Some("mesh_parts") => Some(SegmentationMode::MeshParts),
+        Some("automatic") => Some(SegmentationMode::Automatic {
+            max_dihedral_angle: max_dihedral_angle.unwrap_or(DEFAULT_MAX_DIHEDRAL_ANGLE),
+        }),
+        Some(other) => return Err(invalid_config(&format!("unknown segmentation '{}'", other))),
+    };
+
+    let max_strain = number("max_strain")?;
+    let unfolding_mode = match params.get("unfolding_mode").map(String::as_str) {
+        None if max_strain.is_some() => Some(UnfoldingMode::Strips {
+            max_strain: max_strain.unwrap_or(DEFAULT_MAX_STRAIN),
+        }),
+        None => None,
+        Some("exact") => Some(UnfoldingMode::Exact),
+        Some("strips") => Some(UnfoldingMode::Strips {
+            max_strain: max_strain.unwrap_or(DEFAULT_MAX_STRAIN),
+        }),
+        Some(other) => return Err(invalid_config(&format!("unknown unfolding_mode '{}'", other))),
+    };
+
+    Ok(ConfigOverrides {
+        profile: params.get("profile").cloned(),
+        quality: params.get("quality").cloned(),
+        sheet_size,
+        optimize_folding_lines: flag("optimize_folding_lines")?,
+        add_tabs: flag("add_tabs")?,
+        tolerance: number("tolerance")?,
+        min_component_area: number("min_component_area")?,
+        segmentation,
+        paper_thickness: number("paper_thickness")?,
+        unfolding_mode,
+    })
+}
+
+// Первая часть с именем file или с именем файла — сам файл, остальные части — текстовые параметры
+fn take_file(parts: Vec<FormPart>, params: &mut HashMap<String, String>) -> Result<FormPart, ErrorResponse> {
+    let mut file = None;
+    for part in parts {
+        if file.is_none() && (part.name == "file" || part.file_name.is_some()) {
+            file = Some(part);
+        } else {
+            params.insert(part.name, String::from_utf8_lossy(&part.data).into_owned());
+        }
+    }
+    file.ok_or_else(|| invalid_request("multipart body has no 'file' field"))
+}
+
+// Минимальный разбор multipart/form-data: части целиком в памяти, размер ограничен max_upload_bytes
+fn parse_multipart(content_type: &str, body: &[u8]) -> Result<Vec<FormPart>, ErrorResponse> {
+    let boundary = content_type
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+        .ok_or_else(|| invalid_request("multipart content type has no boundary"))?;
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let separator = format!("\r\n--{}", boundary).into_bytes();
+    // Тело может занимать десятки мегабайт, поиск разделителя должен быть линейным
+    let separator_finder = memmem::Finder::new(&separator);
+
+    let mut rest = memmem::find(body, &delimiter)
+        .map(|start| &body[start + delimiter.len()..])
+        .ok_or_else(|| invalid_request("multipart body has no parts"))?;
+    let mut parts = Vec::new();
+
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or_else(|| invalid_request("malformed multipart delimiter"))?;
+
+        let header_end =
+            memmem::find(rest, b"\r\n\r\n").ok_or_else(|| invalid_request("multipart part has no headers"))?;
+        let headers = String::from_utf8_lossy(&rest[..header_end]);
+        let content = &rest[header_end + 4..];
+        let content_end = separator_finder
+            .find(content)
+            .ok_or_else(|| invalid_request("multipart body is truncated"))?;
+
+        let mut part = FormPart {
+            name: String::new(),
+            file_name: None,
+            content_type: None,
+            data: content[..content_end].to_vec(),
+        };
+        for line in headers.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    let Some((key, value)) = param.trim().split_once('=') else {
+                        continue;
+                    };
+                    let value = value.trim_matches('"').to_string();
+                    match key.to_ascii_lowercase().as_str() {
+                        "name" => part.name = value,
+                        "filename" => part.file_name = Some(value),
+                        _ => {}
+                    }
+                }
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.trim().to_string());
+            }
+        }
+        parts.push(part);
+        rest = &content[content_end + separator.len()..];
+    }
+}
+
+fn invalid_request(message: &str) -> ErrorResponse {
+    ErrorResponse {
+        error: message.to_string(),
+        code: "INVALID_REQUEST".to_string(),
+    }
+}
+
+fn invalid_config(message: &str) -> ErrorResponse {
+    ErrorResponse {
+        error: message.to_string(),
+        code: "INVALID_CONFIG".to_string(),
+    }
+}
+
+fn unsupported_format(what: &str) -> ErrorResponse {
+    let supported: Vec<&str> = MeshFormat::ALL.iter().map(|format| format.name()).collect();
+    ErrorResponse {
+        error: format!("Unsupported mesh format: {} (supported: {})", what, supported.join(", ")),
+        code: "UNSUPPORTED_FORMAT".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Имя поля, имя файла, Content-Type и содержимое
+    type Part<'a> = (&'a str, Option<&'a str>, Option<&'a str>, &'a [u8]);
+
+    fn form(boundary: &str, parts: &[Part]) -> Vec<u8> {
+        let mut body = b"preamble is ignored\r\n".to_vec();
+        for (name, file_name, content_type, data) in parts {
+            body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", boundary, name).bytes());
+            if let Some(file_name) = file_name {
+                body.extend(format!("; filename=\"{}\"", file_name).bytes());
+            }
+            if let Some(content_type) = content_type {
+                body.extend(format!("\r\nContent-Type: {}", content_type).bytes());
+            }
+            body.extend(b"\r\n\r\n");
+            body.extend(*data);
+            body.extend(b"\r\n");
+        }
+        body.extend(format!("--{}--\r\n", boundary).bytes());
+        body
+    }
+
+    #[test]
+    fn test_multipart_with_quoted_boundary() {
+        let body = form(
+            "x-y z",
+            &[
+                ("quality", None, None, b"high"),
+                ("file", Some("cube.obj"), Some("model/obj"), b"v 0 0 0\r\nv 1 0 0\r\n"),
+            ],
+        );
+        let parts = parse_multipart("multipart/form-data; boundary=\"x-y z\"", &body).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "quality");
+        assert_eq!(parts[0].data, b"high");
+        assert_eq!(parts[1].file_name.as_deref(), Some("cube.obj"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("model/obj"));
+        // CRLF внутри файла сохраняется, отрезается только тот, что стоит перед разделителем
+        assert_eq!(parts[1].data, b"v 0 0 0\r\nv 1 0 0\r\n");
+    }
+
+    #[test]
+    fn test_multipart_requires_crlf_and_boundary() {
+        let body = form("b", &[("file", Some("a.stl"), None, b"solid")]);
+        assert!(parse_multipart("multipart/form-data", &body).is_err());
+        assert!(parse_multipart("multipart/form-data; boundary=other", &body).is_err());
+
+        let bare_lf = String::from_utf8(body).unwrap().replace("\r\n", "\n");
+        assert!(parse_multipart("multipart/form-data; boundary=b", bare_lf.as_bytes()).is_err());
+
+        let truncated = b"--b\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nsolid";
+        assert_eq!(
+            parse_multipart("multipart/form-data; boundary=b", truncated).err().unwrap().error,
+            "multipart body is truncated"
+        );
+    }
+
+    #[test]
+    fn test_multipart_without_file_part() {
+        let body = form("b", &[("quality", None, None, b"draft")]);
+        let parts = parse_multipart("multipart/form-data; boundary=b", &body).unwrap();
+        let mut params = HashMap::new();
+
+        let error = take_file(parts, &mut params).err().unwrap();
+        assert_eq!(error.code, "INVALID_REQUEST");
+        assert_eq!(params.get("quality").map(String::as_str), Some("draft"));
+    }
+
+    #[test]
+    fn test_format_inference_order() {
+        let mut params = HashMap::new();
+        let resolve = |params: &HashMap<String, String>, file_name, content_type| {
+            resolve_format(params, file_name, content_type).ok()
+        };
+
+        assert_eq!(resolve(&params, Some("mesh.obj"), Some("model/stl")), Some(MeshFormat::Obj));
+        assert_eq!(resolve(&params, Some("mesh"), Some("model/stl")), Some(MeshFormat::Stl));
+        assert_eq!(resolve(&params, None, Some("model/ply; charset=binary")), Some(MeshFormat::Ply));
+        assert_eq!(resolve(&params, None, None), None);
+
+        params.insert("format".to_string(), "glb".to_string());
+        assert_eq!(resolve(&params, Some("mesh.obj"), Some("model/stl")), Some(MeshFormat::Gltf));
+        params.insert("format".to_string(), "3ds".to_string());
+        assert_eq!(
+            resolve_format(&params, Some("mesh.obj"), None).err().unwrap().code,
+            "UNSUPPORTED_FORMAT"
+        );
+    }
+
+    #[test]
+    fn test_unknown_params_rejected() {
+        let params = |keys: &[&str]| keys.iter().map(|key| (key.to_string(), "1".to_string())).collect();
+
+        assert!(reject_unknown_params(&params(&["quality", "max_strain", "async", "filename"])).is_ok());
+        let error = reject_unknown_params(&params(&["quality", "tolerence", "add_tab"])).unwrap_err();
+        assert_eq!(error.code, "INVALID_CONFIG");
+        assert_eq!(error.error, "unknown parameter 'add_tab'");
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::Mesh;
+
+// Ребро задаётся упорядоченной парой индексов вершин, чтобы (a, b) и (b, a) совпадали
+pub(crate) type EdgeKey = (usize, usize);
+
+pub(crate) fn edge_key(a: usize, b: usize) -> EdgeKey {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Maps every undirected edge of the mesh to the faces that use it.
+pub(crate) fn build_edge_map(mesh: &Mesh) -> HashMap<EdgeKey, Vec<usize>> {
+    let mut edges: HashMap<EdgeKey, Vec<usize>> = HashMap::new();
+
+    for (face_index, face) in mesh.faces.iter().enumerate() {
+        for i in 0..face.len() {
+            let key = edge_key(face[i], face[(i + 1) % face.len()]);
+            edges.entry(key).or_default().push(face_index);
+        }
+    }
+
+    edges
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            // Меньший индекс остаётся корнем, чтобы порядок компонент был стабильным
+            let (root, child) = if root_a < root_b { (root_a, root_b) } else { (root_b, root_a) };
+            self.parent[child] = root;
+        }
+    }
+}
+
+/// Groups faces into shells connected through shared edges.
+///
+/// Components are ordered by their lowest face index and each component lists
+/// its faces in ascending order, so identifiers are stable for a given mesh.
+pub(crate) fn connected_components(mesh: &Mesh) -> Vec<Vec<usize>> {
+    connected_components_where(mesh, |_, _| true)
+}
+
+/// Same as [`connected_components`], but two faces sharing an edge are only
+/// joined when `join(face_a, face_b)` allows it.
+pub(crate) fn connected_components_where<F>(mesh: &Mesh, join: F) -> Vec<Vec<usize>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut set = DisjointSet::new(mesh.faces.len());
+
+    for faces in build_edge_map(mesh).values() {
+        for pair in faces.windows(2) {
+            if join(pair[0], pair[1]) {
+                set.union(pair[0], pair[1]);
+            }
+        }
+    }
+
+    let mut component_of_root: HashMap<usize, usize> = HashMap::new();
+    let mut components: Vec<Vec<usize>> = Vec::new();
+
+    for face_index in 0..mesh.faces.len() {
+        let root = set.find(face_index);
+        let component = *component_of_root.entry(root).or_insert_with(|| {
+            components.push(Vec::new());
+            components.len() - 1
+        });
+        components[component].push(face_index);
+    }
+
+    components
+}
+
+/// Splits a subset of faces into groups connected through shared edges,
+/// without walking through faces outside the subset.
+pub(crate) fn connected_subsets(
+    mesh: &Mesh,
+    edge_map: &HashMap<EdgeKey, Vec<usize>>,
+    face_indices: &[usize],
+) -> Vec<Vec<usize>> {
+    let mut sorted = face_indices.to_vec();
+    sorted.sort_unstable();
+    let mut unvisited: HashSet<usize> = sorted.iter().copied().collect();
+    let mut groups = Vec::new();
+
+    for &seed in &sorted {
+        if !unvisited.remove(&seed) {
+            continue;
+        }
+        let mut group = vec![seed];
+        let mut stack = vec![seed];
+        while let Some(face_index) = stack.pop() {
+            let face = &mesh.faces[face_index];
+            for i in 0..face.len() {
+                let key = edge_key(face[i], face[(i + 1) % face.len()]);
+                for &neighbour in edge_map.get(&key).into_iter().flatten() {
+                    if unvisited.remove(&neighbour) {
+                        group.push(neighbour);
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+        group.sort_unstable();
+        groups.push(group);
+    }
+
+    groups
+}